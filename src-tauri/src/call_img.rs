@@ -2,7 +2,8 @@ use std::fs;
 use std::path::{Path, PathBuf};
 
 use base64::Engine;
-use std::sync::OnceLock;
+use sha2::{Digest, Sha256};
+use std::sync::RwLock;
 use tauri::{AppHandle, Manager};
 
 use crate::db::ensure_dirs;
@@ -10,81 +11,98 @@ use crate::desc::decrypt_image;
 
 const ENV_FILES: [&str; 3] = [".env.production", ".env", ".env.development"];
 
+/// Resultado da última busca pela chave; `None` = ainda não buscou (ou foi invalidado).
+static KEY_CACHE: RwLock<Option<Option<String>>> = RwLock::new(None);
+
+/// Descarta a chave em cache; a próxima `load_env_key` procura de novo
+/// (ex.: depois que init_app grava descrypt.key).
+pub fn invalidate_key_cache() {
+    if let Ok(mut cache) = KEY_CACHE.write() {
+        *cache = None;
+    }
+}
+
 pub fn load_env_key(resource_dir: Option<&Path>, data_dir: Option<&Path>) -> Option<String> {
-    static KEY_CACHE: OnceLock<Option<String>> = OnceLock::new();
-    KEY_CACHE
-        .get_or_init(|| {
-            // 1) compile-time env (quando definido no build)
-            for k in [option_env!("DESCRYPT_KEY"), option_env!("DECRYPT_KEY")] {
-                if let Some(val) = k {
-                    if !val.trim().is_empty() {
-                        return Some(val.to_string());
-                    }
-                }
-            }
-            // 2) variavel de ambiente em runtime
-            for name in ["DESCRYPT_KEY", "DECRYPT_KEY"] {
-                let direct = std::env::var(name).unwrap_or_default();
-                if !direct.trim().is_empty() {
-                    return Some(direct);
-                }
-            }
-            // 3) tenta carregar .env em dirs comuns (cwd, pai, bin/resources, data_dir)
-            let mut dirs: Vec<PathBuf> = Vec::new();
-            if let Ok(cwd) = std::env::current_dir() {
-                dirs.push(cwd.clone());
-                if let Some(parent) = cwd.parent() {
-                    dirs.push(parent.to_path_buf());
-                }
-            }
-            if let Ok(exe) = std::env::current_exe() {
-                if let Some(bin_dir) = exe.parent() {
-                    dirs.push(bin_dir.to_path_buf());
-                    dirs.push(bin_dir.join("resources"));
-                    if let Some(parent) = bin_dir.parent() {
-                        dirs.push(parent.to_path_buf());
-                        dirs.push(parent.join("Resources"));
-                    }
-                }
-            }
-            if let Some(res) = resource_dir {
-                dirs.push(res.to_path_buf());
-            }
-            if let Some(data) = data_dir {
-                dirs.push(data.to_path_buf());
+    if let Some(cached) = KEY_CACHE.read().ok().and_then(|c| c.clone()) {
+        return cached;
+    }
+    let found = find_env_key(resource_dir, data_dir);
+    if let Ok(mut cache) = KEY_CACHE.write() {
+        *cache = Some(found.clone());
+    }
+    found
+}
+
+fn find_env_key(resource_dir: Option<&Path>, data_dir: Option<&Path>) -> Option<String> {
+    // 1) compile-time env (quando definido no build)
+    for k in [option_env!("DESCRYPT_KEY"), option_env!("DECRYPT_KEY")] {
+        if let Some(val) = k {
+            if !val.trim().is_empty() {
+                return Some(val.to_string());
             }
-            dirs.retain(|d| d.exists());
-            dirs.dedup();
-
-            for d in dirs.iter() {
-                for f in ENV_FILES.iter() {
-                    let candidate = d.join(f);
-                    if candidate.exists() {
-                        let _ = dotenvy::from_path(&candidate);
-                    }
-                }
+        }
+    }
+    // 2) variavel de ambiente em runtime
+    for name in ["DESCRYPT_KEY", "DECRYPT_KEY"] {
+        let direct = std::env::var(name).unwrap_or_default();
+        if !direct.trim().is_empty() {
+            return Some(direct);
+        }
+    }
+    // 3) tenta carregar .env em dirs comuns (cwd, pai, bin/resources, data_dir)
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    if let Ok(cwd) = std::env::current_dir() {
+        dirs.push(cwd.clone());
+        if let Some(parent) = cwd.parent() {
+            dirs.push(parent.to_path_buf());
+        }
+    }
+    if let Ok(exe) = std::env::current_exe() {
+        if let Some(bin_dir) = exe.parent() {
+            dirs.push(bin_dir.to_path_buf());
+            dirs.push(bin_dir.join("resources"));
+            if let Some(parent) = bin_dir.parent() {
+                dirs.push(parent.to_path_buf());
+                dirs.push(parent.join("Resources"));
             }
-            for name in ["DESCRYPT_KEY", "DECRYPT_KEY"] {
-                let from_file = std::env::var(name).unwrap_or_default();
-                if !from_file.trim().is_empty() {
-                    return Some(from_file);
-                }
+        }
+    }
+    if let Some(res) = resource_dir {
+        dirs.push(res.to_path_buf());
+    }
+    if let Some(data) = data_dir {
+        dirs.push(data.to_path_buf());
+    }
+    dirs.retain(|d| d.exists());
+    dirs.dedup();
+
+    for d in dirs.iter() {
+        for f in ENV_FILES.iter() {
+            let candidate = d.join(f);
+            if candidate.exists() {
+                let _ = dotenvy::from_path(&candidate);
             }
-            // 4) fallback: arquivo descrypt.key em dirs conhecidos
-            for d in dirs.iter() {
-                let key_file = d.join("descrypt.key");
-                if key_file.exists() {
-                    if let Ok(txt) = std::fs::read_to_string(&key_file) {
-                        let trimmed = txt.trim();
-                        if !trimmed.is_empty() {
-                            return Some(trimmed.to_string());
-                        }
-                    }
+        }
+    }
+    for name in ["DESCRYPT_KEY", "DECRYPT_KEY"] {
+        let from_file = std::env::var(name).unwrap_or_default();
+        if !from_file.trim().is_empty() {
+            return Some(from_file);
+        }
+    }
+    // 4) fallback: arquivo descrypt.key em dirs conhecidos
+    for d in dirs.iter() {
+        let key_file = d.join("descrypt.key");
+        if key_file.exists() {
+            if let Ok(txt) = std::fs::read_to_string(&key_file) {
+                let trimmed = txt.trim();
+                if !trimmed.is_empty() {
+                    return Some(trimmed.to_string());
                 }
             }
-            None
-        })
-        .clone()
+        }
+    }
+    None
 }
 
 pub fn resolve_key(app: &AppHandle, data_dir: &Path) -> Option<String> {
@@ -104,6 +122,23 @@ pub fn resolve_key(app: &AppHandle, data_dir: &Path) -> Option<String> {
     None
 }
 
+/// Chaves a tentar, em ordem: a atual e, durante uma troca de chave, a anterior
+/// (`descrypt.key.old` na pasta de dados).
+pub fn resolve_keys(app: &AppHandle, data_dir: &Path) -> Vec<String> {
+    candidate_keys(resolve_key(app, data_dir), data_dir)
+}
+
+fn candidate_keys(primary: Option<String>, data_dir: &Path) -> Vec<String> {
+    let mut keys: Vec<String> = primary.into_iter().collect();
+    if let Ok(txt) = fs::read_to_string(data_dir.join("descrypt.key.old")) {
+        let old = txt.trim();
+        if !old.is_empty() && !keys.iter().any(|k| k == old) {
+            keys.push(old.to_string());
+        }
+    }
+    keys
+}
+
 fn guess_mime(path: &Path, bytes: &[u8]) -> &'static str {
     if bytes.len() >= 8 {
         if bytes[0..8] == [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A] {
@@ -123,6 +158,12 @@ fn guess_mime(path: &Path, bytes: &[u8]) -> &'static str {
     if bytes.len() >= 2 && bytes[0] == b'B' && bytes[1] == b'M' {
         return "image/bmp";
     }
+    if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        return "image/gif";
+    }
+    if bytes.starts_with(b"II*\0") || bytes.starts_with(b"MM\0*") {
+        return "image/tiff";
+    }
     let ext = path
         .extension()
         .and_then(|e| e.to_str())
@@ -134,6 +175,8 @@ fn guess_mime(path: &Path, bytes: &[u8]) -> &'static str {
         "png" => "image/png",
         "webp" => "image/webp",
         "bmp" => "image/bmp",
+        "gif" => "image/gif",
+        "tif" | "tiff" => "image/tiff",
         _ => "application/octet-stream",
     }
 }
@@ -187,31 +230,100 @@ fn ensure_inside_dir(path: PathBuf, base: &Path) -> Result<PathBuf, String> {
     }
 }
 
-fn decrypt_if_needed(
-    data: Vec<u8>,
-    key_env: Option<&String>,
-    path: &Path,
-) -> Result<Vec<u8>, String> {
+/// Erro dos comandos de imagem quando o arquivo é criptografado e não há chave; o front
+/// testa este código em vez da mensagem.
+pub const KEY_REQUIRED_ERROR: &str = "KEY_REQUIRED";
+
+/// Erros de leitura de imagem criptografada; a falta de chave fica separada de arquivo corrompido.
+#[derive(Debug, PartialEq)]
+pub enum DecryptError {
+    KeyRequired,
+    Failed(String),
+}
+
+impl DecryptError {
+    /// Texto devolvido pelos comandos: `KEY_REQUIRED_ERROR` ou a mensagem da falha.
+    fn into_command_error(self) -> String {
+        match self {
+            DecryptError::KeyRequired => KEY_REQUIRED_ERROR.to_string(),
+            failed => failed.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for DecryptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecryptError::KeyRequired => {
+                write!(
+                    f,
+                    "Imagem criptografada requer chave (DESCRYPT_KEY não configurada)."
+                )
+            }
+            DecryptError::Failed(e) => write!(f, "Falha ao descriptografar: {}", e),
+        }
+    }
+}
+
+/// Tenta cada chave na ordem; a primeira que abrir o arquivo vence.
+fn decrypt_if_needed(data: Vec<u8>, keys: &[String], path: &Path) -> Result<Vec<u8>, DecryptError> {
     let encrypted = data.len() > 5 && &data[..4] == b"CIMG";
     if !encrypted {
         return Ok(data);
     }
-    let Some(key) = key_env.map(|s| s.as_str()) else {
+    let mut last_err = None;
+    for key in keys.iter().filter(|k| !k.trim().is_empty()) {
+        match decrypt_image(&data, key) {
+            Ok(p) => return Ok(p),
+            Err(e) => last_err = Some(e),
+        }
+    }
+    let Some(e) = last_err else {
         eprintln!("decrypt_image: arquivo criptografado, mas DESCRYPT_KEY não encontrado");
-        return Err("Arquivo criptografado sem chave configurada.".to_string());
+        return Err(DecryptError::KeyRequired);
     };
-    match decrypt_image(&data, key) {
-        Ok(p) => Ok(p),
-        Err(e) => {
-            eprintln!(
-                "decrypt_image: falha ao descriptografar {} ({} bytes): {}",
-                path.display(),
-                data.len(),
-                e
-            );
-            Err(format!("Falha ao descriptografar: {}", e))
+    eprintln!(
+        "decrypt_image: falha ao descriptografar {} ({} bytes): {}",
+        path.display(),
+        data.len(),
+        e
+    );
+    Err(DecryptError::Failed(e.to_string()))
+}
+
+/// Testa uma chave candidata contra um arquivo CIMG de amostra; não grava nada nem mexe no cache de chave.
+pub fn validate_key(app: &AppHandle, key: &str, sample_path: &str) -> Result<bool, String> {
+    let (_data_dir, _dbf, imgs_dir) = ensure_dirs(app).map_err(|e| e.to_string())?;
+    validate_key_in(&imgs_dir, key, sample_path)
+}
+
+fn validate_key_in(imgs_dir: &Path, key: &str, sample_path: &str) -> Result<bool, String> {
+    let requested = {
+        let p = PathBuf::from(sample_path);
+        if p.is_absolute() {
+            p
+        } else {
+            imgs_dir.join(p)
         }
+    };
+    let requested = ensure_inside_dir(requested, imgs_dir)?;
+    let Some((_, bytes)) = read_with_cimg_fallback(&requested) else {
+        return Err(format!(
+            "Falha ao ler amostra (não encontrada): {}",
+            requested.display()
+        ));
+    };
+    key_decrypts(&bytes, key)
+}
+
+fn key_decrypts(data: &[u8], key: &str) -> Result<bool, String> {
+    if !(data.len() > 5 && &data[..4] == b"CIMG") {
+        return Err("Amostra não é uma imagem criptografada (CIMG).".to_string());
     }
+    if key.trim().is_empty() {
+        return Ok(false);
+    }
+    Ok(decrypt_image(data, key).is_ok())
 }
 
 fn print_cache_name(path: &Path, imgs_dir: &Path) -> String {
@@ -278,39 +390,90 @@ pub fn prepare_image_for_print(app: &AppHandle, path_or_rel: String) -> Result<P
         return Ok(cache_path);
     }
 
-    let key_env = resolve_key(app, &data_dir);
-    let decoded = decrypt_if_needed(bytes, key_env.as_ref(), &source_path)?;
+    let keys = resolve_keys(app, &data_dir);
+    let decoded =
+        decrypt_if_needed(bytes, &keys, &source_path).map_err(DecryptError::into_command_error)?;
     fs::write(&cache_path, decoded).map_err(|e| e.to_string())?;
     Ok(cache_path)
 }
 
+fn to_data_url(path: &Path, bytes: Vec<u8>) -> String {
+    let mime = guess_mime(path, &bytes);
+    let encoded = base64::engine::general_purpose::STANDARD.encode(&bytes);
+    format!("data:{};base64,{}", mime, encoded)
+}
+
+/// Data URL mais um etag (sha256 do arquivo em disco) para o front invalidar o cache.
+#[derive(Debug, serde::Serialize)]
+pub struct ImageMeta {
+    pub data_url: String,
+    pub etag: String,
+}
+
+pub fn read_image_meta(app: &AppHandle, path_or_rel: String) -> Result<ImageMeta, String> {
+    let (data_dir, _dbf, imgs_dir) = ensure_dirs(app).map_err(|e| e.to_string())?;
+    let requested = {
+        let p = PathBuf::from(&path_or_rel);
+        if p.is_absolute() {
+            p
+        } else {
+            imgs_dir.join(p)
+        }
+    };
+    let requested = ensure_inside_dir(requested, &imgs_dir)?;
+    image_meta_at(&requested, &resolve_keys(app, &data_dir))
+}
+
+fn image_meta_at(path: &Path, keys: &[String]) -> Result<ImageMeta, String> {
+    let Some((source_path, bytes)) = read_with_cimg_fallback(path) else {
+        return Err(format!(
+            "Falha ao ler imagem (não encontrada): {}",
+            path.display()
+        ));
+    };
+    let etag = Sha256::digest(&bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let bytes =
+        decrypt_if_needed(bytes, keys, &source_path).map_err(DecryptError::into_command_error)?;
+    Ok(ImageMeta {
+        data_url: to_data_url(&source_path, bytes),
+        etag,
+    })
+}
+
 pub fn read_image_base64(app: &AppHandle, path_or_rel: String) -> Result<String, String> {
-    // monta caminho absoluto
     let (data_dir, _dbf, imgs_dir) = ensure_dirs(app).map_err(|e| e.to_string())?;
+    read_image_data_url(&imgs_dir, &path_or_rel, || resolve_keys(app, &data_dir))
+}
+
+/// Lê a imagem dentro de `imgs_dir` (que pode estar fora da pasta de dados);
+/// as chaves só são buscadas quando o arquivo existe.
+pub(crate) fn read_image_data_url(
+    imgs_dir: &Path,
+    path_or_rel: &str,
+    keys: impl FnOnce() -> Vec<String>,
+) -> Result<String, String> {
+    // monta caminho absoluto
     let abs_try = {
-        let p = std::path::PathBuf::from(&path_or_rel);
+        let p = std::path::PathBuf::from(path_or_rel);
         if p.is_absolute() {
             p
         } else {
             imgs_dir.join(p)
         }
     };
-    let abs_try = ensure_inside_dir(abs_try, &imgs_dir)?;
+    let abs_try = ensure_inside_dir(abs_try, imgs_dir)?;
     let _name_norm = abs_try
         .file_name()
         .and_then(|s| s.to_str())
         .map(|s| s.trim_end_matches(".cimg"))
         .map(|s| s.to_ascii_lowercase());
 
-    fn to_data_url(path: &std::path::Path, bytes: Vec<u8>) -> String {
-        let mime = guess_mime(path, &bytes);
-        let encoded = base64::engine::general_purpose::STANDARD.encode(&bytes);
-        format!("data:{};base64,{}", mime, encoded)
-    }
-
     if let Some((source_path, bytes)) = read_with_cimg_fallback(&abs_try) {
-        let key_env = resolve_key(app, &data_dir);
-        let bytes = decrypt_if_needed(bytes, key_env.as_ref(), &source_path)?;
+        let bytes = decrypt_if_needed(bytes, &keys(), &source_path)
+            .map_err(DecryptError::into_command_error)?;
         return Ok(to_data_url(&source_path, bytes));
     }
 
@@ -323,3 +486,158 @@ pub fn read_image_base64(app: &AppHandle, path_or_rel: String) -> Result<String,
         abs_try.display()
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_written_after_failed_lookup_is_found_after_invalidate() {
+        let dir =
+            std::env::temp_dir().join(format!("catalogo_ips_key_cache_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        invalidate_key_cache();
+        if load_env_key(None, Some(&dir)).is_some() {
+            // Chave vinda do ambiente/build: não há busca falha para reproduzir.
+            let _ = fs::remove_dir_all(&dir);
+            return;
+        }
+        fs::write(dir.join("descrypt.key"), "nova-chave\n").unwrap();
+        assert_eq!(load_env_key(None, Some(&dir)), None);
+        invalidate_key_cache();
+        assert_eq!(
+            load_env_key(None, Some(&dir)).as_deref(),
+            Some("nova-chave")
+        );
+        invalidate_key_cache();
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn key_validation_sample_must_be_inside_images_dir() {
+        let dir = std::env::temp_dir().join(format!("catalogo_ips_vkey_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let imgs_dir = dir.join("images");
+        fs::create_dir_all(imgs_dir.join("IPS")).unwrap();
+        let data = crate::desc::encrypt_image(b"\x89PNG", "segredo", 1_000).unwrap();
+        fs::write(imgs_dir.join("IPS").join("7111.png.cimg"), &data).unwrap();
+        fs::write(dir.join("fora.png.cimg"), &data).unwrap();
+
+        assert_eq!(
+            validate_key_in(&imgs_dir, "segredo", "IPS/7111.png"),
+            Ok(true)
+        );
+        assert_eq!(
+            validate_key_in(&imgs_dir, "errada", "IPS/7111.png"),
+            Ok(false)
+        );
+        assert!(validate_key_in(&imgs_dir, "segredo", "../fora.png.cimg").is_err());
+        let outside = dir.join("fora.png.cimg").to_string_lossy().into_owned();
+        assert!(validate_key_in(&imgs_dir, "segredo", &outside).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn encrypted_image_without_key_reports_key_required() {
+        let data = crate::desc::encrypt_image(b"\x89PNG", "segredo", 1_000).unwrap();
+        let path = Path::new("IPS/7111.png.cimg");
+        assert_eq!(
+            decrypt_if_needed(data.clone(), &[], path),
+            Err(DecryptError::KeyRequired)
+        );
+        assert_eq!(
+            decrypt_if_needed(data.clone(), &[" ".to_string()], path),
+            Err(DecryptError::KeyRequired)
+        );
+        assert!(matches!(
+            decrypt_if_needed(data.clone(), &["errada".to_string()], path),
+            Err(DecryptError::Failed(_))
+        ));
+        assert_eq!(
+            decrypt_if_needed(data, &["segredo".to_string()], path).unwrap(),
+            b"\x89PNG"
+        );
+        assert_eq!(
+            DecryptError::KeyRequired.into_command_error(),
+            KEY_REQUIRED_ERROR
+        );
+        assert!(DecryptError::Failed("tag".into())
+            .into_command_error()
+            .starts_with("Falha ao descriptografar"));
+        // Arquivo comum passa direto, com ou sem chave.
+        assert_eq!(
+            decrypt_if_needed(b"plain".to_vec(), &[], path).unwrap(),
+            b"plain"
+        );
+    }
+
+    #[test]
+    fn files_under_old_and_new_keys_both_decrypt() {
+        let dir =
+            std::env::temp_dir().join(format!("catalogo_ips_two_keys_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = Path::new("IPS/7111.png.cimg");
+        let old_file = crate::desc::encrypt_image(b"antiga", "chave-antiga", 1_000).unwrap();
+        let new_file = crate::desc::encrypt_image(b"nova", "chave-nova", 1_000).unwrap();
+
+        // Só a chave atual: comportamento de sempre.
+        let single = candidate_keys(Some("chave-nova".into()), &dir);
+        assert_eq!(single, vec!["chave-nova"]);
+        assert!(decrypt_if_needed(old_file.clone(), &single, path).is_err());
+
+        fs::write(dir.join("descrypt.key.old"), "chave-antiga\n").unwrap();
+        let keys = candidate_keys(Some("chave-nova".into()), &dir);
+        assert_eq!(keys, vec!["chave-nova", "chave-antiga"]);
+        assert_eq!(decrypt_if_needed(new_file, &keys, path).unwrap(), b"nova");
+        assert_eq!(decrypt_if_needed(old_file, &keys, path).unwrap(), b"antiga");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn candidate_key_is_checked_against_sample() {
+        let data = crate::desc::encrypt_image(b"\x89PNG", "segredo", 1_000).unwrap();
+        assert_eq!(key_decrypts(&data, "segredo"), Ok(true));
+        assert_eq!(key_decrypts(&data, "errada"), Ok(false));
+        assert_eq!(key_decrypts(&data, ""), Ok(false));
+        assert!(key_decrypts(b"\x89PNG plain", "segredo").is_err());
+    }
+
+    #[test]
+    fn etag_follows_file_content() {
+        let dir = std::env::temp_dir().join(format!("catalogo_ips_etag_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("7111.png");
+        fs::write(&path, b"\x89PNG\r\n\x1a\nv1").unwrap();
+        let first = image_meta_at(&path, &[]).unwrap();
+        assert!(first.data_url.starts_with("data:image/png;base64,"));
+        assert_eq!(image_meta_at(&path, &[]).unwrap().etag, first.etag);
+
+        fs::write(&path, b"\x89PNG\r\n\x1a\nv2").unwrap();
+        let second = image_meta_at(&path, &[]).unwrap();
+        assert_ne!(second.etag, first.etag);
+        assert_ne!(second.data_url, first.data_url);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn mime_comes_from_magic_bytes() {
+        let unnamed = Path::new("sem_extensao");
+        let cases: [(&[u8], &str); 7] = [
+            (b"\x89PNG\r\n\x1a\n....", "image/png"),
+            (b"\xFF\xD8\xFF\xE0", "image/jpeg"),
+            (b"RIFF\0\0\0\0WEBPVP8 ", "image/webp"),
+            (b"BM\0\0", "image/bmp"),
+            (b"GIF89a\x01\0", "image/gif"),
+            (b"GIF87a\x01\0", "image/gif"),
+            (b"II*\0\x08\0", "image/tiff"),
+        ];
+        for (bytes, mime) in cases {
+            assert_eq!(guess_mime(unnamed, bytes), mime);
+        }
+        assert_eq!(guess_mime(unnamed, b"MM\0*\0\0"), "image/tiff");
+        assert_eq!(guess_mime(Path::new("a.GIF"), b"??"), "image/gif");
+        assert_eq!(guess_mime(unnamed, b"??"), "application/octet-stream");
+    }
+}
@@ -8,10 +8,98 @@ use tauri::{AppHandle, Manager};
 pub const DB_FILE_NAME: &str = "catalog.db";
 pub const IMAGES_DIR_NAME: &str = "images";
 pub const META_DB_VERSION_KEY: &str = "db_version";
+/// Versão do esquema gravada pelo `migrate` (independente da versão dos dados).
+pub const META_SCHEMA_VERSION_KEY: &str = "schema_version";
 pub const META_MANIFEST_HASH_KEY: &str = "manifest_hash";
+pub const META_PLACEHOLDER_HASHES_KEY: &str = "placeholder_hashes";
+/// Nomes extras de pasta de lançamento, separados por vírgula (ex.: "novidades,launches").
+pub const META_LAUNCH_DIRS_KEY: &str = "launch_dirs";
+/// Hash do conteúdo gravado pela última importação (ver `catalog_fingerprint`).
+pub const META_IMPORT_FINGERPRINT_KEY: &str = "import_fingerprint";
+/// Origem da última importação bem-sucedida (ver `importer::ImportProvenance`).
+pub const META_LAST_IMPORT_AT_KEY: &str = "last_import_at";
+pub const META_LAST_IMPORT_FILE_KEY: &str = "last_import_file";
+pub const META_LAST_IMPORT_ROWS_KEY: &str = "last_import_rows";
+/// Variável de ambiente que troca a pasta de dados (instalação portátil, catálogo em rede).
+pub const DATA_DIR_ENV: &str = "CATALOG_DATA_DIR";
+/// Arquivo na pasta padrão que guarda a pasta escolhida por `set_data_dir_cmd`.
+const DATA_DIR_OVERRIDE_FILE: &str = "data_dir.txt";
+/// Variável de ambiente que leva as imagens para fora da pasta de dados (outro disco).
+pub const IMAGES_DIR_ENV: &str = "CATALOG_IMAGES_DIR";
+/// Arquivo na pasta de dados que guarda a pasta escolhida por `set_images_dir_cmd`.
+const IMAGES_DIR_OVERRIDE_FILE: &str = "images_dir.txt";
 
 pub fn app_data_dir(app: &AppHandle) -> Result<PathBuf> {
-    Ok(app.path().app_local_data_dir()?)
+    let default = app.path().app_local_data_dir().map_err(|e| {
+        anyhow::anyhow!(
+            "pasta de dados do app (app_local_data_dir) indisponível: {}",
+            e
+        )
+    })?;
+    Ok(resolve_data_dir(&default, std::env::var(DATA_DIR_ENV).ok()))
+}
+
+/// Ordem: variável de ambiente, escolha salva na pasta padrão, pasta padrão.
+pub fn resolve_data_dir(default: &Path, env_value: Option<String>) -> PathBuf {
+    if let Some(dir) = env_value.filter(|v| !v.trim().is_empty()) {
+        return PathBuf::from(dir.trim());
+    }
+    fs::read_to_string(default.join(DATA_DIR_OVERRIDE_FILE))
+        .ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| default.to_path_buf())
+}
+
+/// Salva (ou remove, com `None`) a pasta de dados personalizada; vale a partir da próxima chamada.
+pub fn set_data_dir_override(default: &Path, dir: Option<&Path>) -> Result<()> {
+    let marker = default.join(DATA_DIR_OVERRIDE_FILE);
+    match dir {
+        Some(dir) => {
+            fs::create_dir_all(dir)?;
+            fs::create_dir_all(default)?;
+            fs::write(&marker, dir.to_string_lossy().as_bytes())?;
+        }
+        None => {
+            if marker.exists() {
+                fs::remove_file(&marker)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Ordem: variável de ambiente, escolha salva na pasta de dados, `data/images`.
+pub fn resolve_images_dir(data: &Path, env_value: Option<String>) -> PathBuf {
+    if let Some(dir) = env_value.filter(|v| !v.trim().is_empty()) {
+        return PathBuf::from(dir.trim());
+    }
+    fs::read_to_string(data.join(IMAGES_DIR_OVERRIDE_FILE))
+        .ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| data.join(IMAGES_DIR_NAME))
+}
+
+/// Salva (ou remove, com `None`) a pasta de imagens personalizada; as imagens já
+/// baixadas não são movidas.
+pub fn set_images_dir_override(data: &Path, dir: Option<&Path>) -> Result<()> {
+    let marker = data.join(IMAGES_DIR_OVERRIDE_FILE);
+    match dir {
+        Some(dir) => {
+            fs::create_dir_all(dir)?;
+            fs::create_dir_all(data)?;
+            fs::write(&marker, dir.to_string_lossy().as_bytes())?;
+        }
+        None => {
+            if marker.exists() {
+                fs::remove_file(&marker)?;
+            }
+        }
+    }
+    Ok(())
 }
 
 pub fn db_path(app: &AppHandle) -> Result<PathBuf> {
@@ -19,12 +107,15 @@ pub fn db_path(app: &AppHandle) -> Result<PathBuf> {
 }
 
 pub fn ensure_dirs(app: &AppHandle) -> Result<(PathBuf, PathBuf, PathBuf)> {
-    let data = app_data_dir(app)?;
+    ensure_dirs_in(app_data_dir(app)?)
+}
+
+pub fn ensure_dirs_in(data: PathBuf) -> Result<(PathBuf, PathBuf, PathBuf)> {
     if !data.exists() {
         fs::create_dir_all(&data)?;
     }
     let db = data.join(DB_FILE_NAME);
-    let imgs = data.join(IMAGES_DIR_NAME);
+    let imgs = resolve_images_dir(&data, std::env::var(IMAGES_DIR_ENV).ok());
     if !imgs.exists() {
         fs::create_dir_all(&imgs)?;
     }
@@ -36,3 +127,38 @@ pub fn open_db(path: &Path) -> Result<Connection> {
     conn.busy_timeout(Duration::from_secs(30))?;
     Ok(conn)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn override_moves_db_and_images_to_custom_dir() {
+        let base =
+            std::env::temp_dir().join(format!("catalogo_ips_datadir_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        let default = base.join("padrao");
+        let custom = base.join("rede").join("catalogo");
+        assert_eq!(resolve_data_dir(&default, None), default);
+
+        set_data_dir_override(&default, Some(&custom)).unwrap();
+        assert!(custom.is_dir());
+        let (data, db, imgs) = ensure_dirs_in(resolve_data_dir(&default, None)).unwrap();
+        assert_eq!(data, custom);
+        assert!(imgs.starts_with(&custom) && imgs.is_dir());
+        open_db(&db).unwrap();
+        assert!(custom.join(DB_FILE_NAME).is_file());
+        assert!(!default.join(DB_FILE_NAME).exists());
+
+        // Variável de ambiente tem prioridade sobre a escolha salva.
+        let env_dir = base.join("env");
+        assert_eq!(
+            resolve_data_dir(&default, Some(env_dir.to_string_lossy().to_string())),
+            env_dir
+        );
+
+        set_data_dir_override(&default, None).unwrap();
+        assert_eq!(resolve_data_dir(&default, None), default);
+        let _ = fs::remove_dir_all(&base);
+    }
+}
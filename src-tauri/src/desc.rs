@@ -1,22 +1,46 @@
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::OsRng;
 use aes_gcm::{aead::Aead, Aes256Gcm, KeyInit, Nonce};
 use anyhow::anyhow;
 use pbkdf2::pbkdf2_hmac;
 use sha2::Sha256;
 
 const MAGIC: &[u8] = b"CIMG";
-const VERSION: u8 = 1;
+/// v1: MAGIC | 1 | salt | nonce | dados (iterações fixas em `V1_KDF_ITERS`).
+const VERSION_V1: u8 = 1;
+/// v2: MAGIC | 2 | iterações (u32 LE) | salt | nonce | dados.
+const VERSION_V2: u8 = 2;
 const SALT_LEN: usize = 16;
 const NONCE_LEN: usize = 12;
-const KDF_ITERS: u32 = 200_000;
+const V1_KDF_ITERS: u32 = 200_000;
+/// Padrão para novos arquivos; pode ser aumentado sem quebrar os já gerados.
+pub const DEFAULT_KDF_ITERS: u32 = 200_000;
+/// Teto de segurança: um cabeçalho adulterado não pode travar o app no PBKDF2.
+const MAX_KDF_ITERS: u32 = 10_000_000;
 
 pub fn decrypt_image(data: &[u8], password: &str) -> anyhow::Result<Vec<u8>> {
     if data.len() < MAGIC.len() + 1 + SALT_LEN + NONCE_LEN {
         anyhow::bail!("arquivo curto");
     }
-    if &data[..MAGIC.len()] != MAGIC || data[MAGIC.len()] != VERSION {
+    if &data[..MAGIC.len()] != MAGIC {
         anyhow::bail!("formato desconhecido");
     }
     let mut offset = MAGIC.len() + 1;
+    let iters = match data[MAGIC.len()] {
+        VERSION_V1 => V1_KDF_ITERS,
+        VERSION_V2 => {
+            if data.len() < offset + 4 + SALT_LEN + NONCE_LEN {
+                anyhow::bail!("arquivo curto");
+            }
+            let iters = u32::from_le_bytes(data[offset..offset + 4].try_into()?);
+            offset += 4;
+            if iters == 0 || iters > MAX_KDF_ITERS {
+                anyhow::bail!("iterações de KDF inválidas: {}", iters);
+            }
+            iters
+        }
+        _ => anyhow::bail!("formato desconhecido"),
+    };
     let salt = &data[offset..offset + SALT_LEN];
     offset += SALT_LEN;
     let nonce_bytes: [u8; NONCE_LEN] = data[offset..offset + NONCE_LEN].try_into()?;
@@ -30,12 +54,84 @@ pub fn decrypt_image(data: &[u8], password: &str) -> anyhow::Result<Vec<u8>> {
         anyhow::bail!("senha de descriptografia ausente");
     }
 
-    let mut key = [0u8; 32];
-    pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, KDF_ITERS, &mut key);
-    let cipher = Aes256Gcm::new_from_slice(&key)?;
+    let cipher = cipher_for(password, salt, iters)?;
     let plaintext = cipher
         // aes-gcm decrypt espera um Nonce por referÇõncia; from_slice jÇÁ retorna &Nonce
         .decrypt(nonce, ciphertext)
         .map_err(|e| anyhow!(format!("decrypt fail: {}", e)))?;
     Ok(plaintext)
 }
+
+/// Criptografa no formato v2, gravando `iters` no cabeçalho. No app, só a exportação
+/// criptografada do catalog.db usa; as imagens são geradas fora dele.
+pub fn encrypt_image(data: &[u8], password: &str, iters: u32) -> anyhow::Result<Vec<u8>> {
+    if password.trim().is_empty() {
+        anyhow::bail!("senha de criptografia ausente");
+    }
+    if iters == 0 || iters > MAX_KDF_ITERS {
+        anyhow::bail!("iterações de KDF inválidas: {}", iters);
+    }
+    let mut header = MAGIC.to_vec();
+    header.push(VERSION_V2);
+    header.extend_from_slice(&iters.to_le_bytes());
+    seal(header, data, password, iters)
+}
+
+fn cipher_for(password: &str, salt: &[u8], iters: u32) -> anyhow::Result<Aes256Gcm> {
+    let mut key = [0u8; 32];
+    pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, iters, &mut key);
+    Ok(Aes256Gcm::new_from_slice(&key)?)
+}
+
+/// Acrescenta salt + nonce aleatórios e o texto cifrado ao cabeçalho.
+fn seal(mut out: Vec<u8>, data: &[u8], password: &str, iters: u32) -> anyhow::Result<Vec<u8>> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce_bytes = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce_bytes);
+    #[allow(deprecated)]
+    let nonce = Nonce::from_slice(&nonce_bytes);
+    let ciphertext = cipher_for(password, &salt, iters)?
+        .encrypt(nonce, data)
+        .map_err(|e| anyhow!(format!("encrypt fail: {}", e)))?;
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce_bytes);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Gera um arquivo no formato antigo, como os já publicados.
+    fn encrypt_v1(data: &[u8], password: &str) -> Vec<u8> {
+        let mut header = MAGIC.to_vec();
+        header.push(VERSION_V1);
+        seal(header, data, password, V1_KDF_ITERS).unwrap()
+    }
+
+    #[test]
+    fn v1_files_still_decrypt() {
+        let enc = encrypt_v1(b"imagem antiga", "segredo");
+        assert_eq!(enc[MAGIC.len()], VERSION_V1);
+        assert_eq!(decrypt_image(&enc, "segredo").unwrap(), b"imagem antiga");
+        assert!(decrypt_image(&enc, "outra").is_err());
+    }
+
+    #[test]
+    fn v2_round_trips_with_custom_iterations() {
+        let enc = encrypt_image(b"imagem nova", "segredo", 1_000).unwrap();
+        assert_eq!(enc[MAGIC.len()], VERSION_V2);
+        assert_eq!(
+            u32::from_le_bytes(enc[MAGIC.len() + 1..MAGIC.len() + 5].try_into().unwrap()),
+            1_000
+        );
+        assert_eq!(decrypt_image(&enc, "segredo").unwrap(), b"imagem nova");
+        assert!(decrypt_image(&enc, "outra").is_err());
+
+        let mut tampered = enc.clone();
+        tampered[MAGIC.len() + 1..MAGIC.len() + 5].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(decrypt_image(&tampered, "segredo").is_err());
+    }
+}
@@ -1,7 +1,14 @@
-use crate::db::{ensure_dirs, open_db};
+use crate::db::{
+    ensure_dirs, open_db, META_IMPORT_FINGERPRINT_KEY, META_LAST_IMPORT_AT_KEY,
+    META_LAST_IMPORT_FILE_KEY, META_LAST_IMPORT_ROWS_KEY,
+};
 use anyhow::Result;
-use calamine::{open_workbook_auto, Reader};
-use rusqlite::{params, OptionalExtension};
+use calamine::{open_workbook_auto, Data, Range, Reader};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Client;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tauri::AppHandle;
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -10,27 +17,164 @@ pub struct ImportResult {
     pub upserted_products: usize,
     pub linked_vehicles: usize,
     pub new_db_version: i64,
+    /// Vínculos de veículo cuja MONTADORA veio vazia ou ilegível.
+    pub vehicles_without_make: usize,
+    /// Amostra (sem repetição) dos veículos afetados, para diagnóstico.
+    pub vehicles_without_make_sample: Vec<String>,
+    /// Códigos lidos de células numéricas: zeros à esquerda ("007111") podem ter se perdido.
+    pub numeric_codes: usize,
+    /// Contagem por aba importada, na ordem em que foram lidas.
+    pub sheets: Vec<SheetImportCount>,
+    /// Veículos e montadoras distintos que passaram a existir com esta importação.
+    pub created_vehicles: usize,
+    pub created_makes: usize,
+    /// Linhas puladas por célula ilegível em MARCA/CODIGO/DESCRICAO.
+    pub skipped_rows: usize,
+    /// Detalhe das linhas puladas (até `ROW_ERROR_LIMIT`).
+    pub row_errors: Vec<RowError>,
+    /// Códigos que aparecem mais de uma vez no arquivo (vale a última linha), na ordem da primeira repetição.
+    pub duplicate_codes_in_file: Vec<String>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct RowError {
+    pub sheet: String,
+    /// Número da linha como aparece na planilha (1 = cabeçalho).
+    pub row: usize,
+    pub message: String,
+}
+
+/// Máximo de linhas com problema detalhadas no resultado.
+const ROW_ERROR_LIMIT: usize = 200;
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct SheetImportCount {
+    pub sheet: String,
+    pub processed_rows: usize,
+    pub upserted_products: usize,
+}
+
+/// Quantos nomes de veículos sem montadora entram na amostra do resultado.
+const MISSING_MAKE_SAMPLE_LIMIT: usize = 20;
+
+/// Maiúscula sem acento (só o que aparece em português/espanhol/francês).
+pub(crate) fn fold_char(c: char) -> char {
+    match c {
+        'á' | 'à' | 'ã' | 'â' | 'ä' | 'Á' | 'À' | 'Ã' | 'Â' | 'Ä' => 'A',
+        'é' | 'è' | 'ê' | 'ë' | 'É' | 'È' | 'Ê' | 'Ë' => 'E',
+        'í' | 'ì' | 'î' | 'ï' | 'Í' | 'Ì' | 'Î' | 'Ï' => 'I',
+        'ó' | 'ò' | 'õ' | 'ô' | 'ö' | 'Ó' | 'Ò' | 'Õ' | 'Ô' | 'Ö' => 'O',
+        'ú' | 'ù' | 'û' | 'ü' | 'Ú' | 'Ù' | 'Û' | 'Ü' => 'U',
+        'ç' | 'Ç' => 'C',
+        'ñ' | 'Ñ' => 'N',
+        other => other.to_ascii_uppercase(),
+    }
 }
 
 /// Normaliza cabeçalhos para uma chave ASCII previsível.
 fn norm(s: &str) -> String {
     s.trim()
         .chars()
-        .map(|c| match c {
-            'á' | 'à' | 'ã' | 'â' | 'ä' | 'Á' | 'À' | 'Ã' | 'Â' | 'Ä' => 'A',
-            'é' | 'è' | 'ê' | 'ë' | 'É' | 'È' | 'Ê' | 'Ë' => 'E',
-            'í' | 'ì' | 'î' | 'ï' | 'Í' | 'Ì' | 'Î' | 'Ï' => 'I',
-            'ó' | 'ò' | 'õ' | 'ô' | 'ö' | 'Ó' | 'Ò' | 'Õ' | 'Ô' | 'Ö' => 'O',
-            'ú' | 'ù' | 'û' | 'ü' | 'Ú' | 'Ù' | 'Û' | 'Ü' => 'U',
-            'ç' | 'Ç' => 'C',
-            other => other.to_ascii_uppercase(),
-        })
+        .map(fold_char)
         .filter(|c| c.is_ascii_alphanumeric())
         .collect()
 }
 
-fn header_key(s: &str) -> &'static str {
+/// Chave de comparação sem acento, sem caixa e com espaços colapsados
+/// ("Citroën" e " CITROEN" viram a mesma marca; usada também na busca de veículos).
+pub(crate) fn fold_key(s: &str) -> String {
+    s.split_whitespace()
+        .map(|w| w.chars().map(fold_char).collect::<String>())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Idioma dos cabeçalhos da planilha. Os nomes em português valem sempre; `En`/`Es`
+/// acrescentam os sinônimos do idioma (ver `HeaderLocale::synonyms`).
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum HeaderLocale {
+    #[default]
+    Pt,
+    En,
+    Es,
+}
+
+impl HeaderLocale {
+    /// Aceita "pt", "en", "es" e variantes regionais ("pt-BR", "es_AR"); ausente = português.
+    pub fn parse(locale: Option<&str>) -> Result<Self, String> {
+        let Some(raw) = locale.map(str::trim).filter(|l| !l.is_empty()) else {
+            return Ok(HeaderLocale::Pt);
+        };
+        let lang = raw
+            .split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match lang.as_str() {
+            "pt" => Ok(HeaderLocale::Pt),
+            "en" => Ok(HeaderLocale::En),
+            "es" => Ok(HeaderLocale::Es),
+            _ => Err(format!(
+                "Idioma de cabeçalho não suportado: {raw} (use pt, en ou es)"
+            )),
+        }
+    }
+
+    /// Cabeçalho já normalizado (`norm`) → chave de coluna.
+    fn synonyms(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            HeaderLocale::Pt => &[],
+            HeaderLocale::En => &[
+                ("BRAND", "brand"),
+                ("MANUFACTURER", "brand"),
+                ("CODE", "code"),
+                ("PARTNUMBER", "code"),
+                ("SKU", "code"),
+                ("DESCRIPTION", "description"),
+                ("GROUP", "group"),
+                ("TYPE", "group"),
+                ("SUBCATEGORY", "subcategory"),
+                ("CATEGORY", "category"),
+                ("APPLICATION", "application"),
+                ("APPLICATIONS", "application"),
+                ("VEHICLE", "vehicles"),
+                ("VEHICLES", "vehicles"),
+                ("MAKE", "make"),
+                ("MAKES", "make"),
+                ("BARCODE", "ean_gtin"),
+                ("STOCK", "stock"),
+                ("QTY", "stock"),
+                ("QUANTITY", "stock"),
+                ("ACTIVE", "active"),
+                ("HEIGHT", "altura"),
+                ("WIDTH", "largura"),
+                ("LENGTH", "comprimento"),
+            ],
+            HeaderLocale::Es => &[
+                ("DESCRIPCION", "description"),
+                ("APLICACION", "application"),
+                ("APLICACIONES", "application"),
+                ("VEHICULO", "vehicles"),
+                ("VEHICULOS", "vehicles"),
+                ("MARCAVEHICULO", "make"),
+                ("ARMADORA", "make"),
+                ("EXISTENCIAS", "stock"),
+                ("STOCK", "stock"),
+                ("CANTIDAD", "stock"),
+                ("ESTADO", "active"),
+                ("ALTO", "altura"),
+                ("ANCHO", "largura"),
+                ("LARGO", "comprimento"),
+            ],
+        }
+    }
+}
+
+fn header_key(s: &str, locale: HeaderLocale) -> &'static str {
     let n = norm(s);
+    if let Some((_, key)) = locale.synonyms().iter().find(|(h, _)| *h == n) {
+        return key;
+    }
     if ["FABRICANTE", "MARCA"].contains(&n.as_str()) {
         "brand"
     } else if ["CODIGO", "COD", "REFERENCIA", "REF"].contains(&n.as_str()) {
@@ -39,6 +183,8 @@ fn header_key(s: &str) -> &'static str {
         "description"
     } else if ["GRUPO", "GRUPODEPRODUTOS", "TIPO"].contains(&n.as_str()) {
         "group"
+    } else if ["SUBCATEGORIA", "SUBCATEGORIAS"].contains(&n.as_str()) {
+        "subcategory"
     } else if ["CATEGORIA", "LINHA", "LINHAVEICULO", "LINHADEVEICULO"].contains(&n.as_str()) {
         "category"
     } else if ["APLICACAO", "APLICACOES"].contains(&n.as_str()) {
@@ -56,6 +202,12 @@ fn header_key(s: &str) -> &'static str {
         || n.contains("CODIGODEBARRAS")
     {
         "ean_gtin"
+    } else if ["TAGS", "TAG", "ETIQUETAS"].contains(&n.as_str()) {
+        "tags"
+    } else if ["ESTOQUE", "QTD", "QTDE", "QUANTIDADE"].contains(&n.as_str()) {
+        "stock"
+    } else if ["STATUS", "ATIVO", "SITUACAO"].contains(&n.as_str()) {
+        "active"
     } else if n == "ALTURA" || n == "ALT" || n.starts_with("ALTURA") {
         "altura"
     } else if n == "LARGURA" || n == "LARG" || n.starts_with("LARGURA") {
@@ -68,18 +220,445 @@ fn header_key(s: &str) -> &'static str {
     }
 }
 
-pub fn import_excel(app: AppHandle, path: String) -> Result<ImportResult, String> {
+/// Sem `sheets` e sem `all_sheets`, importa só a primeira aba (comportamento antigo).
+pub fn import_excel(
+    app: AppHandle,
+    path: String,
+    sheets: Option<Vec<String>>,
+    all_sheets: bool,
+    locale: Option<String>,
+) -> Result<ImportResult, String> {
+    let locale = HeaderLocale::parse(locale.as_deref())?;
     let (_, dbf, _) = ensure_dirs(&app).map_err(|e| e.to_string())?;
-    let mut wb = open_workbook_auto(&path).map_err(|e| format!("Falha abrindo XLSX: {e}"))?;
+    let ranges = read_sheets(&path, sheets.as_deref(), all_sheets)?;
+    let mut conn = open_db(&dbf).map_err(|e| e.to_string())?;
+    import_sheets_from(&mut conn, &ranges, &path, locale)
+}
+
+/// Quando e de onde veio o catálogo atual (gravado no meta ao fim de cada importação).
+#[derive(Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct ImportProvenance {
+    /// UTC, "AAAA-MM-DDTHH:MM:SSZ".
+    pub at: String,
+    /// Arquivo local: só o nome, sem a pasta. URL: host e caminho sem query
+    /// ("docs.google.com/spreadsheets/d/<id>" para o Google Sheets).
+    pub file: String,
+    pub rows: usize,
+}
+
+/// `import_sheets` seguido do registro da origem; `source` é o caminho ou a URL lida.
+pub(crate) fn import_sheets_from(
+    conn: &mut Connection,
+    sheets: &[(String, Range<Data>)],
+    source: &str,
+    locale: HeaderLocale,
+) -> Result<ImportResult, String> {
+    let result = import_sheets(conn, sheets, locale)?;
+    record_import_provenance(conn, source, result.processed_rows).map_err(|e| e.to_string())?;
+    Ok(result)
+}
+
+/// Nome do arquivo para caminhos locais; para URLs, host e caminho (sem query), já que o
+/// último segmento de um link do Google Sheets é só "edit" ou "export".
+fn source_label(source: &str) -> String {
+    if let Ok(url) = reqwest::Url::parse(source.trim()) {
+        if let Some(host) = url.host_str() {
+            let marker = "/spreadsheets/d/";
+            let path = match url.path().find(marker) {
+                Some(pos) => {
+                    let rest = &url.path()[pos + marker.len()..];
+                    let id = rest.split('/').next().unwrap_or_default();
+                    format!("{}{id}", &url.path()[..pos + marker.len()])
+                }
+                None => url.path().trim_end_matches('/').to_string(),
+            };
+            return format!("{host}{path}");
+        }
+    }
+    let without_query = source.split(['?', '#']).next().unwrap_or_default();
+    without_query
+        .trim_end_matches(['/', '\\'])
+        .rsplit(['/', '\\'])
+        .next()
+        .filter(|name| !name.is_empty())
+        .unwrap_or(source)
+        .to_string()
+}
+
+fn record_import_provenance(conn: &Connection, source: &str, rows: usize) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO meta(key, value) VALUES(?1, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))",
+        params![META_LAST_IMPORT_AT_KEY],
+    )?;
+    conn.execute(
+        "INSERT OR REPLACE INTO meta(key, value) VALUES(?1, ?2)",
+        params![META_LAST_IMPORT_FILE_KEY, source_label(source)],
+    )?;
+    conn.execute(
+        "INSERT OR REPLACE INTO meta(key, value) VALUES(?1, ?2)",
+        params![META_LAST_IMPORT_ROWS_KEY, rows.to_string()],
+    )?;
+    Ok(())
+}
+
+/// None enquanto nenhuma importação foi feita neste banco.
+pub(crate) fn import_provenance(conn: &Connection) -> Result<Option<ImportProvenance>> {
+    let get = |key: &str| -> Result<Option<String>> {
+        Ok(conn
+            .query_row("SELECT value FROM meta WHERE key = ?1", params![key], |r| {
+                r.get(0)
+            })
+            .optional()?)
+    };
+    let Some(at) = get(META_LAST_IMPORT_AT_KEY)? else {
+        return Ok(None);
+    };
+    Ok(Some(ImportProvenance {
+        at,
+        file: get(META_LAST_IMPORT_FILE_KEY)?.unwrap_or_default(),
+        rows: get(META_LAST_IMPORT_ROWS_KEY)?
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0),
+    }))
+}
+
+/// Primeira execução sem catalog.db empacotado: importa a planilha `seed.xlsx` que veio
+/// junto. Só roda com o banco sem nenhum produto, para nunca sobrescrever dados do usuário.
+pub(crate) fn import_seed_if_empty(
+    conn: &mut Connection,
+    seed: &Path,
+) -> Result<Option<ImportResult>, String> {
+    let has_products: Option<i64> = conn
+        .query_row("SELECT 1 FROM products LIMIT 1", [], |row| row.get(0))
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if has_products.is_some() || !seed.is_file() {
+        return Ok(None);
+    }
+    let ranges = read_sheets(&seed.to_string_lossy(), None, false)?;
+    import_sheets_from(conn, &ranges, &seed.to_string_lossy(), HeaderLocale::Pt).map(Some)
+}
+
+/// Limite da planilha remota; acima disso é mais provável ser a URL errada.
+const MAX_REMOTE_SHEET_BYTES: u64 = 50 * 1024 * 1024;
+
+/// Cliente HTTP das importações por URL, criado na primeira e reaproveitado depois.
+static SHEET_CLIENT: std::sync::OnceLock<Client> = std::sync::OnceLock::new();
+
+fn sheet_client() -> Result<&'static Client, String> {
+    if let Some(client) = SHEET_CLIENT.get() {
+        return Ok(client);
+    }
+    let client = Client::builder()
+        .timeout(Duration::from_secs(60))
+        .build()
+        .map_err(|e| e.to_string())?;
+    Ok(SHEET_CLIENT.get_or_init(|| client))
+}
+
+/// Baixa a planilha (XLSX/ODS/XLS ou link do Google Sheets) e roda a importação normal.
+pub async fn import_excel_from_url(
+    app: AppHandle,
+    url: String,
+    sheets: Option<Vec<String>>,
+    all_sheets: bool,
+    locale: Option<String>,
+) -> Result<ImportResult, String> {
+    let locale = HeaderLocale::parse(locale.as_deref())?;
+    let (data_dir, dbf, _) = ensure_dirs(&app).map_err(|e| e.to_string())?;
+    let file =
+        download_spreadsheet(sheet_client()?, &url, &data_dir, MAX_REMOTE_SHEET_BYTES).await?;
+    let ranges = read_sheets(&file.0.to_string_lossy(), sheets.as_deref(), all_sheets);
+    drop(file);
+    let mut conn = open_db(&dbf).map_err(|e| e.to_string())?;
+    import_sheets_from(&mut conn, &ranges?, &url, locale)
+}
+
+/// Links de edição do Google Sheets viram o endpoint de exportação em XLSX.
+fn spreadsheet_download_url(url: &str) -> String {
+    let marker = "docs.google.com/spreadsheets/d/";
+    match url.find(marker) {
+        Some(pos) if !url.contains("/export") => {
+            let rest = &url[pos + marker.len()..];
+            let id = rest.split(['/', '?', '#']).next().unwrap_or_default();
+            format!("https://docs.google.com/spreadsheets/d/{id}/export?format=xlsx")
+        }
+        _ => url.to_string(),
+    }
+}
+
+/// Extensão pelo conteúdo: o calamine escolhe o leitor pela extensão do arquivo.
+fn spreadsheet_extension(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"PK\x03\x04") {
+        // ODS grava o arquivo "mimetype" primeiro, sem compressão.
+        if bytes.get(30..38) == Some(b"mimetype".as_slice()) {
+            Some("ods")
+        } else {
+            Some("xlsx")
+        }
+    } else if bytes.starts_with(&[0xD0, 0xCF, 0x11, 0xE0]) {
+        Some("xls")
+    } else {
+        None
+    }
+}
+
+/// Planilha baixada para uma importação; o arquivo é apagado quando o valor sai de escopo.
+#[derive(Debug)]
+struct RemoteSheetFile(PathBuf);
+
+impl Drop for RemoteSheetFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Numera os downloads do processo: importações simultâneas não dividem o mesmo arquivo.
+static REMOTE_SHEET_SEQ: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+/// Baixa para `dir/import_remote_<pid>_<n>.<ext>`, recusando páginas HTML (ex.: login do
+/// Google) e arquivos maiores que `max_bytes`. O corpo é lido aos pedaços e o download para
+/// assim que passa do limite, mesmo quando o servidor não manda Content-Length.
+async fn download_spreadsheet(
+    client: &Client,
+    url: &str,
+    dir: &Path,
+    max_bytes: u64,
+) -> Result<RemoteSheetFile, String> {
+    let url = spreadsheet_download_url(url.trim());
+    let mut resp = client
+        .get(&url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("Falha baixando planilha: {e}"))?;
+    let content_type = resp
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    if content_type.starts_with("text/") {
+        return Err(format!(
+            "URL não retornou uma planilha (content-type {content_type}); verifique se o arquivo é público"
+        ));
+    }
+    let too_big = || {
+        format!(
+            "Planilha remota excede o limite de {} MB",
+            max_bytes / (1024 * 1024)
+        )
+    };
+    if resp.content_length().unwrap_or(0) > max_bytes {
+        return Err(too_big());
+    }
+    let mut bytes: Vec<u8> = Vec::new();
+    while let Some(chunk) = resp
+        .chunk()
+        .await
+        .map_err(|e| format!("Falha baixando planilha: {e}"))?
+    {
+        if (bytes.len() + chunk.len()) as u64 > max_bytes {
+            return Err(too_big());
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    let ext = spreadsheet_extension(&bytes)
+        .ok_or_else(|| "Conteúdo baixado não é XLSX, XLS nem ODS".to_string())?;
+    let seq = REMOTE_SHEET_SEQ.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let dest =
+        RemoteSheetFile(dir.join(format!("import_remote_{}_{seq}.{ext}", std::process::id())));
+    std::fs::write(&dest.0, &bytes).map_err(|e| e.to_string())?;
+    Ok(dest)
+}
+
+/// Abas selecionadas da planilha (XLSX, XLS ou ODS), com o nome de cada uma.
+fn read_sheets(
+    path: &str,
+    sheets: Option<&[String]>,
+    all_sheets: bool,
+) -> Result<Vec<(String, Range<Data>)>, String> {
+    let mut wb = open_workbook_auto(path).map_err(|e| format!("Falha abrindo planilha: {e}"))?;
     let sheet_names = wb.sheet_names().to_vec();
-    let sheet = sheet_names
-        .get(0)
-        .ok_or_else(|| "Planilha vazia".to_string())?
-        .to_string();
-    let range = wb.worksheet_range(&sheet).map_err(|e| e.to_string())?;
-
-    let mut rows = range.rows();
-    let header = rows.next().ok_or("XLSX sem cabeçalho")?;
+    let selected: Vec<String> = match sheets {
+        _ if all_sheets => sheet_names.clone(),
+        Some(wanted) if !wanted.is_empty() => {
+            for name in wanted {
+                if !sheet_names.contains(name) {
+                    return Err(format!("Aba não encontrada: {name}"));
+                }
+            }
+            wanted.to_vec()
+        }
+        _ => sheet_names.iter().take(1).cloned().collect(),
+    };
+    if selected.is_empty() {
+        return Err("Planilha vazia".to_string());
+    }
+    selected
+        .into_iter()
+        .map(|name| {
+            let range = wb.worksheet_range(&name).map_err(|e| e.to_string())?;
+            Ok((name, range))
+        })
+        .collect()
+}
+
+/// Primeira aba da planilha (XLSX, XLS ou ODS).
+#[cfg(test)]
+fn read_first_sheet(path: &str) -> Result<Range<Data>, String> {
+    read_sheets(path, None, false)?
+        .pop()
+        .map(|(_, range)| range)
+        .ok_or_else(|| "Planilha vazia".to_string())
+}
+
+/// Texto de uma célula. Números inteiros gravados como float (comum em ODS/LibreOffice)
+/// saem sem casas decimais, inclusive quando o float traz lixo de arredondamento.
+fn cell_text(c: &Data) -> String {
+    match c {
+        Data::Float(f) if f.is_finite() && f.abs() < 1e15 && (f - f.round()).abs() < 1e-6 => {
+            format!("{}", f.round() as i64)
+        }
+        // Erro de fórmula (#N/A, #VALOR!) não é conteúdo; bytes inválidos já chegam como U+FFFD.
+        Data::Error(_) => String::new(),
+        Data::String(s) if s.contains('\u{FFFD}') => s.replace('\u{FFFD}', ""),
+        other => other.to_string(),
+    }
+}
+
+/// Motivo para pular a linha quando uma coluna-chave está ilegível.
+fn unreadable_cell(row: &[Data], cols: &[(usize, &str)]) -> Option<String> {
+    cols.iter().find_map(|(i, name)| match row.get(*i) {
+        Some(Data::Error(e)) => Some(format!("{name}: célula com erro ({e})")),
+        Some(Data::String(s)) if s.contains('\u{FFFD}') => {
+            Some(format!("{name}: caracteres inválidos na célula"))
+        }
+        _ => None,
+    })
+}
+
+/// Código a partir da célula crua. Números saem sem notação científica nem ".0";
+/// o segundo valor indica que a célula era numérica (possível perda de zeros à esquerda).
+fn code_from_cell(c: Option<&Data>) -> (String, bool) {
+    match c {
+        Some(Data::Int(i)) => (i.to_string(), true),
+        Some(Data::Float(f)) if f.is_finite() && f.fract() == 0.0 => (format!("{f:.0}"), true),
+        Some(d @ Data::Float(_)) => (cell_text(d), true),
+        Some(d) => (normalize_code(&d.to_string()), false),
+        None => (String::new(), false),
+    }
+}
+
+/// Tipo do produto = primeira palavra da descrição, ignorando espaços e pontuação
+/// nas pontas ("  - Pastilha, dianteira" -> "PASTILHA").
+pub(crate) fn product_type(description: &str) -> String {
+    description
+        .split_whitespace()
+        .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric()))
+        .find(|w| !w.is_empty())
+        .unwrap_or_default()
+        .to_uppercase()
+}
+
+/// Código canônico: sem espaços nas pontas, espaços internos colapsados e maiúsculo.
+pub(crate) fn normalize_code(s: &str) -> String {
+    s.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_uppercase()
+}
+
+/// Nome canônico do veículo: mesma regra do código, para "Gol  1.0" e "GOL 1.0" caírem na mesma linha.
+pub(crate) fn normalize_vehicle_name(s: &str) -> String {
+    normalize_code(s)
+}
+
+/// Chave de busca do código: só letras/dígitos, sem acento e maiúscula ("ab-71.11" -> "AB7111").
+pub(crate) fn code_search_key(s: &str) -> String {
+    s.chars()
+        .map(fold_char)
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+/// Estoque vem como número ou texto ("12", "12.0", "12,5"); vazio/inválido = desconhecido.
+fn parse_stock(s: &str) -> Option<i64> {
+    let clean = s.trim().replace(',', ".");
+    if clean.is_empty() {
+        return None;
+    }
+    clean
+        .parse::<i64>()
+        .ok()
+        .or_else(|| clean.parse::<f64>().ok().map(|f| f.round() as i64))
+}
+
+/// STATUS/ATIVO da planilha: 1 = ativo, 0 = descontinuado; vazio/desconhecido = NULL (conta como ativo).
+fn parse_active(s: &str) -> Option<i64> {
+    match norm(s).as_str() {
+        "" => None,
+        "1" | "S" | "SIM" | "ATIVO" | "ATIVA" | "A" => Some(1),
+        "0" | "N" | "NAO" | "INATIVO" | "INATIVA" | "I" | "DESCONTINUADO" | "DESCONTINUADA"
+        | "FORADELINHA" => Some(0),
+        _ => None,
+    }
+}
+
+fn find_or_create_category(conn: &Connection, parent_id: Option<i64>, name: &str) -> Option<i64> {
+    let found: Option<i64> = conn
+        .query_row(
+            "SELECT id FROM categories WHERE parent_id IS ?1 AND UPPER(name) = UPPER(?2)",
+            params![parent_id, name],
+            |r| r.get(0),
+        )
+        .optional()
+        .unwrap_or(None);
+    if found.is_some() {
+        return found;
+    }
+    conn.execute(
+        "INSERT INTO categories(parent_id, name) VALUES(?1, ?2)",
+        params![parent_id, name],
+    )
+    .ok()?;
+    Some(conn.last_insert_rowid())
+}
+
+/// Resolve categoria/subcategoria da linha; o produto aponta para o nível mais profundo.
+fn product_category_id(conn: &Connection, category: &str, subcategory: &str) -> Option<i64> {
+    if category.is_empty() {
+        return None;
+    }
+    let parent = find_or_create_category(conn, None, category)?;
+    if subcategory.is_empty() {
+        return Some(parent);
+    }
+    find_or_create_category(conn, Some(parent), subcategory)
+}
+
+/// Posições das colunas reconhecidas numa aba; `usize::MAX` = coluna ausente.
+#[derive(Clone, Copy)]
+struct SheetColumns {
+    // order: brand, code, description, group, application, vehicles, oem, similar
+    idx: (usize, usize, usize, usize, usize, usize, usize, usize),
+    idx_details: usize,
+    idx_make: usize,
+    idx_ean_gtin: usize,
+    idx_altura: usize,
+    idx_largura: usize,
+    idx_comprimento: usize,
+    idx_category: usize,
+    idx_stock: usize,
+    idx_subcategory: usize,
+    idx_tags: usize,
+    idx_product_category: usize,
+    idx_active: usize,
+}
+
+/// Detecta as colunas pelo cabeçalho; `None` quando faltam código ou descrição.
+fn detect_columns(header: &[Data], locale: HeaderLocale) -> Option<SheetColumns> {
     let mut idx = (
         usize::MAX,
         usize::MAX,
@@ -97,10 +676,15 @@ pub fn import_excel(app: AppHandle, path: String) -> Result<ImportResult, String
     let mut idx_largura: usize = usize::MAX;
     let mut idx_comprimento: usize = usize::MAX;
     let mut idx_category: usize = usize::MAX;
+    let mut idx_stock: usize = usize::MAX;
+    let mut idx_subcategory: usize = usize::MAX;
+    let mut idx_tags: usize = usize::MAX;
+    let mut idx_category_name: usize = usize::MAX;
+    let mut idx_active: usize = usize::MAX;
 
     // order: brand, code, description, group, application, vehicles, oem, similar
     for (i, cell) in header.iter().enumerate() {
-        let key = header_key(&cell.to_string());
+        let key = header_key(&cell.to_string(), locale);
         match key {
             "brand" if idx.0 == usize::MAX => idx.0 = i,
             "code" if idx.1 == usize::MAX => idx.1 = i,
@@ -116,8 +700,15 @@ pub fn import_excel(app: AppHandle, path: String) -> Result<ImportResult, String
             "altura" if idx_altura == usize::MAX => idx_altura = i,
             "largura" if idx_largura == usize::MAX => idx_largura = i,
             "comprimento" if idx_comprimento == usize::MAX => idx_comprimento = i,
+            "stock" if idx_stock == usize::MAX => idx_stock = i,
+            "subcategory" if idx_subcategory == usize::MAX => idx_subcategory = i,
+            "tags" if idx_tags == usize::MAX => idx_tags = i,
+            "active" if idx_active == usize::MAX => idx_active = i,
             _ => {}
         }
+        if idx_category_name == usize::MAX && norm(&cell.to_string()) == "CATEGORIA" {
+            idx_category_name = i;
+        }
 
         let t = norm(&cell.to_string());
         if idx_details == usize::MAX
@@ -128,11 +719,76 @@ pub fn import_excel(app: AppHandle, path: String) -> Result<ImportResult, String
     }
 
     if idx.1 == usize::MAX || idx.2 == usize::MAX {
-        return Err("Cabeçalhos mínimos ausentes (código/descrição)".into());
+        return None;
     }
 
-    let mut conn = open_db(&dbf).map_err(|e| e.to_string())?;
-    super::core::migrate(&conn).map_err(|e| e.to_string())?;
+    // Com o par CATEGORIA/SUBCATEGORIA, CATEGORIA passa a ser a categoria do produto;
+    // a linha do veículo fica só com LINHA e variações.
+    let mut idx_product_category = usize::MAX;
+    if idx_subcategory != usize::MAX && idx_category_name != usize::MAX {
+        idx_product_category = idx_category_name;
+        if idx_category == idx_category_name {
+            idx_category = header
+                .iter()
+                .enumerate()
+                .find(|(i, c)| {
+                    *i != idx_category_name && header_key(&c.to_string(), locale) == "category"
+                })
+                .map(|(i, _)| i)
+                .unwrap_or(usize::MAX);
+        }
+    }
+
+    Some(SheetColumns {
+        idx,
+        idx_details,
+        idx_make,
+        idx_ean_gtin,
+        idx_altura,
+        idx_largura,
+        idx_comprimento,
+        idx_category,
+        idx_stock,
+        idx_subcategory,
+        idx_tags,
+        idx_product_category,
+        idx_active,
+    })
+}
+
+#[cfg(test)]
+pub(crate) fn import_range(
+    conn: &mut Connection,
+    range: &Range<Data>,
+) -> Result<ImportResult, String> {
+    import_sheets(conn, &[(String::new(), range.clone())], HeaderLocale::Pt)
+}
+
+/// Importa as abas numa única carga: a base é limpa uma vez e cada aba tem seu próprio
+/// cabeçalho. Abas sem código/descrição (capas, índices) são ignoradas.
+pub(crate) fn import_sheets(
+    conn: &mut Connection,
+    sheets: &[(String, Range<Data>)],
+    locale: HeaderLocale,
+) -> Result<ImportResult, String> {
+    let mut plans: Vec<(&str, SheetColumns, &Range<Data>)> = Vec::new();
+    for (name, range) in sheets {
+        let Some(header) = range.rows().next() else {
+            continue;
+        };
+        if let Some(cols) = detect_columns(header, locale) {
+            plans.push((name.as_str(), cols, range));
+        }
+    }
+    if plans.is_empty() {
+        return Err(if sheets.iter().all(|(_, r)| r.rows().next().is_none()) {
+            "XLSX sem cabeçalho".into()
+        } else {
+            "Cabeçalhos mínimos ausentes (código/descrição)".into()
+        });
+    }
+
+    super::core::migrate(conn).map_err(|e| e.to_string())?;
     let tx = conn.transaction().map_err(|e| e.to_string())?;
 
     let mut processed = 0usize;
@@ -140,14 +796,36 @@ pub fn import_excel(app: AppHandle, path: String) -> Result<ImportResult, String
     let mut linked = 0usize;
     let current_year = crate::years::current_year();
 
+    // Tags manuais sobrevivem à reimportação (por código), exceto nos produtos cuja aba
+    // traz coluna TAGS: esses ficam só com as tags da planilha.
+    let preserved_tags: Vec<(String, i64)> = {
+        let mut stmt = tx
+            .prepare("SELECT p.code, pt.tag_id FROM product_tags pt JOIN products p ON p.id = pt.product_id")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, i64>(1)?)))
+            .map_err(|e| e.to_string())?;
+        rows.filter_map(|r| r.ok()).collect()
+    };
+    let mut codes_with_sheet_tags: std::collections::HashSet<String> =
+        std::collections::HashSet::new();
+
+    // Nomes de antes da limpeza: "criados" são os que a planilha trouxe e o banco não tinha.
+    let vehicles_before = table_names(&tx, "vehicles");
+    let makes_before = table_names(&tx, "makes");
+
     // Limpa tabelas principais antes de reimportar para evitar sobras da planilha anterior.
+    tx.execute("DELETE FROM product_tags", []).ok();
     tx.execute("DELETE FROM product_vehicles", []).ok();
     tx.execute("DELETE FROM vehicle_makes", []).ok();
     tx.execute("DELETE FROM vehicles", []).ok();
     tx.execute("DELETE FROM makes", []).ok();
     tx.execute("DELETE FROM products", []).ok();
+    tx.execute("DELETE FROM categories", []).ok();
     tx.execute("DELETE FROM brand_groups", []).ok();
     tx.execute("DELETE FROM brands", []).ok();
+    let unknown_brand_id = super::core::ensure_unknown_brand(&tx).map_err(|e| e.to_string())?;
+    let mut brand_ids: std::collections::HashMap<String, i64> = std::collections::HashMap::new();
 
     tx.execute("ALTER TABLE vehicles ADD COLUMN make TEXT", [])
         .ok();
@@ -176,83 +854,124 @@ pub fn import_excel(app: AppHandle, path: String) -> Result<ImportResult, String
     )
     .ok();
 
-    for row in rows {
-        processed += 1;
-        let cell = |i: usize| -> String {
-            if i == usize::MAX {
-                return String::new();
+    let mut vehicles_without_make = 0usize;
+    let mut numeric_codes = 0usize;
+    let mut vehicles_without_make_sample: Vec<String> = Vec::new();
+    let mut sheet_counts: Vec<SheetImportCount> = Vec::new();
+    let mut skipped_rows = 0usize;
+    let mut row_errors: Vec<RowError> = Vec::new();
+    let mut seen_codes: std::collections::HashSet<String> = std::collections::HashSet::new();
+    let mut duplicate_codes_in_file: Vec<String> = Vec::new();
+    for (sheet_name, cols, range) in plans {
+        let SheetColumns {
+            idx,
+            idx_details,
+            idx_make,
+            idx_ean_gtin,
+            idx_altura,
+            idx_largura,
+            idx_comprimento,
+            idx_category,
+            idx_stock,
+            idx_subcategory,
+            idx_tags,
+            idx_product_category,
+            idx_active,
+        } = cols;
+        let (processed_before, upserted_before) = (processed, upserted);
+        let first_row = range.start().map(|(r, _)| r as usize).unwrap_or(0) + 1;
+        for (offset, row) in range.rows().enumerate().skip(1) {
+            processed += 1;
+            let key_cols = [(idx.0, "MARCA"), (idx.1, "CODIGO"), (idx.2, "DESCRICAO")];
+            if let Some(message) = unreadable_cell(row, &key_cols) {
+                skipped_rows += 1;
+                if row_errors.len() < ROW_ERROR_LIMIT {
+                    row_errors.push(RowError {
+                        sheet: sheet_name.to_string(),
+                        row: first_row + offset,
+                        message,
+                    });
+                }
+                continue;
             }
-            row.get(i)
-                .map(|c| c.to_string())
-                .unwrap_or_default()
-                .trim()
-                .to_string()
-        };
+            let cell = |i: usize| -> String {
+                if i == usize::MAX {
+                    return String::new();
+                }
+                row.get(i)
+                    .map(cell_text)
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string()
+            };
 
-        let brand_name = cell(idx.0);
-        let code = cell(idx.1);
-        if code.is_empty() {
-            continue;
-        }
+            let brand_name = cell(idx.0);
+            let (code, numeric_code) = code_from_cell(row.get(idx.1));
+            if code.is_empty() {
+                continue;
+            }
+            if numeric_code {
+                numeric_codes += 1;
+            }
+            if !seen_codes.insert(code.clone()) && !duplicate_codes_in_file.contains(&code) {
+                duplicate_codes_in_file.push(code.clone());
+            }
 
-        let description = cell(idx.2);
-        let pgroup = cell(idx.3);
-        let application = cell(idx.4);
-        let make_val = if idx_make != usize::MAX {
-            cell(idx_make)
-        } else {
-            String::new()
-        };
-        let details = if idx_details != usize::MAX {
-            cell(idx_details)
-        } else {
-            String::new()
-        };
-        // Veículos: se não existir coluna dedicada, derivamos da aplicação.
-        let vehicles_raw = if idx.5 != usize::MAX {
-            cell(idx.5)
-        } else {
-            application.clone()
-        };
-        let oem = cell(idx.6);
-        let similar = cell(idx.7);
-        let category = cell(idx_category);
-        let ean_gtin = cell(idx_ean_gtin);
-        let altura = cell(idx_altura);
-        let largura = cell(idx_largura);
-        let comprimento = cell(idx_comprimento);
-
-        let brand_id: i64 = if !brand_name.is_empty() {
-            let found: Option<i64> = tx
-                .query_row(
-                    "SELECT id FROM brands WHERE UPPER(TRIM(name)) = UPPER(TRIM(?1))",
-                    params![brand_name],
-                    |r| r.get(0),
-                )
-                .optional()
-                .unwrap_or(None);
-            if let Some(id) = found {
-                id
+            let description = cell(idx.2);
+            let pgroup = cell(idx.3);
+            let application = cell(idx.4);
+            let make_val = if idx_make != usize::MAX {
+                cell(idx_make)
             } else {
-                tx.execute(
-                    "INSERT INTO brands(name) VALUES(TRIM(?1))",
-                    params![brand_name],
-                )
-                .ok();
-                tx.query_row(
-                    "SELECT id FROM brands WHERE UPPER(TRIM(name)) = UPPER(TRIM(?1))",
-                    params![brand_name],
-                    |r| r.get(0),
-                )
-                .unwrap_or(1)
-            }
-        } else {
-            1
-        };
+                String::new()
+            };
+            let details = if idx_details != usize::MAX {
+                cell(idx_details)
+            } else {
+                String::new()
+            };
+            // Veículos: se não existir coluna dedicada, derivamos da aplicação.
+            let vehicles_raw = if idx.5 != usize::MAX {
+                cell(idx.5)
+            } else {
+                application.clone()
+            };
+            let oem = cell(idx.6);
+            let similar = cell(idx.7);
+            let category = cell(idx_category);
+            let ean_gtin = cell(idx_ean_gtin);
+            let altura = cell(idx_altura);
+            let largura = cell(idx_largura);
+            let comprimento = cell(idx_comprimento);
+            let stock = parse_stock(&cell(idx_stock));
+            let active = parse_active(&cell(idx_active));
+            let category_id =
+                product_category_id(&tx, &cell(idx_product_category), &cell(idx_subcategory));
 
-        tx.execute(
-            "INSERT INTO products(brand_id, code, description, pgroup, application, details, oem, similar, ean_gtin, altura, largura, comprimento) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
-             ON CONFLICT(code) DO UPDATE SET brand_id=excluded.brand_id, description=excluded.description, pgroup=excluded.pgroup, application=excluded.application, details=excluded.details, oem=excluded.oem, similar=excluded.similar, ean_gtin=excluded.ean_gtin, altura=excluded.altura, largura=excluded.largura, comprimento=excluded.comprimento",
+            let key = fold_key(&brand_name);
+            let brand_id: i64 = if key.is_empty() {
+                unknown_brand_id
+            } else if let Some(id) = brand_ids.get(&key) {
+                *id
+            } else {
+                // Primeira grafia vista é a que fica gravada para a marca.
+                let id = match super::core::find_brand_by_name(&tx, &brand_name) {
+                    Ok(Some(id)) => id,
+                    _ => tx
+                        .execute(
+                            "INSERT INTO brands(name) VALUES(TRIM(?1))",
+                            params![brand_name],
+                        )
+                        .map(|_| tx.last_insert_rowid())
+                        .unwrap_or(unknown_brand_id),
+                };
+                brand_ids.insert(key, id);
+                id
+            };
+
+            tx.execute(
+            "INSERT INTO products(brand_id, code, description, pgroup, application, details, oem, similar, ean_gtin, altura, largura, comprimento, stock, category_id, ptype, code_norm, active) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)
+             ON CONFLICT(code) DO UPDATE SET code_norm=excluded.code_norm, active=excluded.active, brand_id=excluded.brand_id, description=excluded.description, pgroup=excluded.pgroup, application=excluded.application, details=excluded.details, oem=excluded.oem, similar=excluded.similar, ean_gtin=excluded.ean_gtin, altura=excluded.altura, largura=excluded.largura, comprimento=excluded.comprimento, stock=excluded.stock, category_id=excluded.category_id, ptype=excluded.ptype",
             params![
                 brand_id,
                 code,
@@ -301,60 +1020,84 @@ pub fn import_excel(app: AppHandle, path: String) -> Result<ImportResult, String
                     None::<String>
                 } else {
                     Some(comprimento)
-                }
+                },
+                stock,
+                category_id,
+                product_type(&description),
+                code_search_key(&code),
+                active
             ],
         )
         .map_err(|e| e.to_string())?;
-        upserted += 1;
+            upserted += 1;
 
-        let pid: i64 = tx
-            .query_row(
-                "SELECT id FROM products WHERE code=?1",
-                params![code],
-                |r| r.get(0),
-            )
-            .map_err(|e| e.to_string())?;
+            let pid: i64 = tx
+                .query_row(
+                    "SELECT id FROM products WHERE code=?1",
+                    params![code],
+                    |r| r.get(0),
+                )
+                .map_err(|e| e.to_string())?;
 
-        if !vehicles_raw.is_empty() {
-            tx.execute(
-                "DELETE FROM product_vehicles WHERE product_id=?1",
-                params![pid],
-            )
-            .ok();
-            for v in
-                vehicles_raw.split(|c| c == ';' || c == ',' || c == '|' || c == '\n' || c == '\r')
-            {
-                let v = v.trim();
-                if v.is_empty() {
-                    continue;
-                }
-                let make_tokens: Vec<String> = make_val
-                    .split('/')
-                    .map(|t| t.trim())
-                    .filter(|t| !t.is_empty())
-                    .map(|t| t.to_ascii_uppercase())
-                    .collect();
-                let mut make_ids: Vec<i64> = Vec::new();
-                for mf in make_tokens.iter() {
-                    tx.execute(
-                        "INSERT OR IGNORE INTO makes(name) VALUES(?)",
-                        params![mf.clone()],
-                    )
-                    .ok();
-                    if let Some(mid) = tx
-                        .query_row("SELECT id FROM makes WHERE name=?1", params![mf], |r| {
-                            r.get(0)
-                        })
-                        .optional()
-                        .unwrap_or(None)
-                    {
-                        make_ids.push(mid);
+            if idx_tags != usize::MAX {
+                codes_with_sheet_tags.insert(code.clone());
+                for tag in cell(idx_tags).split([';', ',', '|']) {
+                    let tag = tag.trim();
+                    if !tag.is_empty() {
+                        super::core::add_product_tag(&tx, pid, tag).ok();
                     }
                 }
-                let primary_make = make_tokens.get(0).cloned().unwrap_or_default();
-                let primary_make_id = make_ids.get(0).copied();
-                let years = crate::years::vehicle_years_from_name(v, current_year);
+            }
+
+            if !vehicles_raw.is_empty() {
                 tx.execute(
+                    "DELETE FROM product_vehicles WHERE product_id=?1",
+                    params![pid],
+                )
+                .ok();
+                for v in vehicles_raw
+                    .split(|c| c == ';' || c == ',' || c == '|' || c == '\n' || c == '\r')
+                {
+                    let v = normalize_vehicle_name(v);
+                    let v = v.as_str();
+                    if v.is_empty() {
+                        continue;
+                    }
+                    let make_tokens: Vec<String> = make_val
+                        .split('/')
+                        .map(|t| t.trim())
+                        .filter(|t| !t.is_empty())
+                        .map(|t| t.to_ascii_uppercase())
+                        .collect();
+                    if make_tokens.is_empty() {
+                        vehicles_without_make += 1;
+                        if vehicles_without_make_sample.len() < MISSING_MAKE_SAMPLE_LIMIT
+                            && !vehicles_without_make_sample.iter().any(|n| n == v)
+                        {
+                            vehicles_without_make_sample.push(v.to_string());
+                        }
+                    }
+                    let mut make_ids: Vec<i64> = Vec::new();
+                    for mf in make_tokens.iter() {
+                        tx.execute(
+                            "INSERT OR IGNORE INTO makes(name) VALUES(?)",
+                            params![mf.clone()],
+                        )
+                        .ok();
+                        if let Some(mid) = tx
+                            .query_row("SELECT id FROM makes WHERE name=?1", params![mf], |r| {
+                                r.get(0)
+                            })
+                            .optional()
+                            .unwrap_or(None)
+                        {
+                            make_ids.push(mid);
+                        }
+                    }
+                    let primary_make = make_tokens.get(0).cloned().unwrap_or_default();
+                    let primary_make_id = make_ids.get(0).copied();
+                    let years = crate::years::vehicle_years_from_name(v, current_year);
+                    tx.execute(
                     "INSERT INTO vehicles(name, make, make_id, category, years) VALUES(?, ?, ?, ?, ?) ON CONFLICT(name) DO UPDATE SET make=COALESCE(NULLIF(excluded.make,''), vehicles.make), make_id=COALESCE(excluded.make_id, vehicles.make_id), category=COALESCE(NULLIF(excluded.category,''), vehicles.category), years=COALESCE(NULLIF(excluded.years,''), vehicles.years)",
                     params![
                         v,
@@ -377,39 +1120,711 @@ pub fn import_excel(app: AppHandle, path: String) -> Result<ImportResult, String
                     ],
                 )
                 .ok();
-                let vid: i64 = tx
-                    .query_row("SELECT id FROM vehicles WHERE name=?1", params![v], |r| {
-                        r.get(0)
-                    })
-                    .unwrap_or_else(|_| 0);
-                if vid != 0 {
-                    for mid in make_ids.iter() {
-                        tx.execute(
+                    let vid: i64 = tx
+                        .query_row("SELECT id FROM vehicles WHERE name=?1", params![v], |r| {
+                            r.get(0)
+                        })
+                        .unwrap_or_else(|_| 0);
+                    if vid != 0 {
+                        for mid in make_ids.iter() {
+                            tx.execute(
                             "INSERT OR IGNORE INTO vehicle_makes(vehicle_id, make_id) VALUES(?1,?2)",
                             params![vid, mid],
                         )
                         .ok();
-                    }
-                    tx.execute(
+                        }
+                        tx.execute(
                         "INSERT OR IGNORE INTO product_vehicles(product_id, vehicle_id) VALUES(?1,?2)",
                         params![pid, vid],
                     )
                     .ok();
-                    linked += 1;
+                        linked += 1;
+                    }
                 }
             }
         }
+        sheet_counts.push(SheetImportCount {
+            sheet: sheet_name.to_string(),
+            processed_rows: processed - processed_before,
+            upserted_products: upserted - upserted_before,
+        });
+    }
+
+    for (code, tag_id) in preserved_tags {
+        if codes_with_sheet_tags.contains(&code) {
+            continue;
+        }
+        tx.execute(
+            "INSERT OR IGNORE INTO product_tags(product_id, tag_id) SELECT id, ?2 FROM products WHERE code = ?1",
+            params![code, tag_id],
+        )
+        .ok();
     }
 
+    let created_vehicles = table_names(&tx, "vehicles")
+        .difference(&vehicles_before)
+        .count();
+    let created_makes = table_names(&tx, "makes").difference(&makes_before).count();
+    // Compara com o resultado da importação anterior, não com o banco antes de apagar:
+    // o migrate entre uma e outra deriva colunas (montadora, ptype) que a planilha não traz.
+    // Edições fora da planilha apagam a chave (`core::forget_import_fingerprint`).
+    let fingerprint = catalog_fingerprint(&tx).map_err(|e| e.to_string())?;
+    let previous: Option<String> = tx
+        .query_row(
+            "SELECT value FROM meta WHERE key = ?1",
+            params![META_IMPORT_FINGERPRINT_KEY],
+            |r| r.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    let changed = previous.as_deref() != Some(fingerprint.as_str());
+    tx.execute(
+        "INSERT OR REPLACE INTO meta(key, value) VALUES(?1, ?2)",
+        params![META_IMPORT_FINGERPRINT_KEY, fingerprint],
+    )
+    .map_err(|e| e.to_string())?;
     tx.commit().map_err(|e| e.to_string())?;
-    super::core::seed_brand_groups(&conn).map_err(|e| e.to_string())?;
-    let v = super::core::get_db_version(&conn).unwrap_or(0) + 1;
-    super::core::set_db_version(&conn, v).ok();
+    super::core::seed_brand_groups(conn).map_err(|e| e.to_string())?;
+    // Planilha idêntica à anterior não gera versão nova (a sync não reavalia à toa).
+    let mut v = super::core::get_db_version(conn).unwrap_or(0);
+    if changed {
+        v += 1;
+        super::core::set_db_version(conn, v).ok();
+    }
 
     Ok(ImportResult {
         processed_rows: processed,
         upserted_products: upserted,
         linked_vehicles: linked,
         new_db_version: v,
+        vehicles_without_make,
+        vehicles_without_make_sample,
+        numeric_codes,
+        sheets: sheet_counts,
+        created_vehicles,
+        created_makes,
+        skipped_rows,
+        row_errors,
+        duplicate_codes_in_file,
     })
 }
+
+/// Hash do conteúdo que a importação grava, pelas chaves naturais (código, nome), não pelos
+/// ids: a reimportação apaga e recria as linhas, então só o conteúdo diz se algo mudou.
+fn catalog_fingerprint(conn: &Connection) -> rusqlite::Result<String> {
+    use sha2::{Digest, Sha256};
+    const QUERIES: [&str; 5] = [
+        "SELECT p.code, b.name, p.description, p.application, p.details, p.oem, p.similar, p.pgroup,
+                p.ean_gtin, p.altura, p.largura, p.comprimento, p.stock, p.ptype, p.active,
+                c.name, cp.name
+         FROM products p JOIN brands b ON b.id = p.brand_id
+         LEFT JOIN categories c ON c.id = p.category_id
+         LEFT JOIN categories cp ON cp.id = c.parent_id
+         ORDER BY p.code",
+        "SELECT name FROM brands ORDER BY name",
+        "SELECT name, make, category, years FROM vehicles ORDER BY name",
+        "SELECT p.code, v.name FROM product_vehicles pv
+         JOIN products p ON p.id = pv.product_id JOIN vehicles v ON v.id = pv.vehicle_id
+         ORDER BY p.code, v.name",
+        "SELECT p.code, t.name FROM product_tags pt
+         JOIN products p ON p.id = pt.product_id JOIN tags t ON t.id = pt.tag_id
+         ORDER BY p.code, t.name",
+    ];
+    let mut hasher = Sha256::new();
+    for sql in QUERIES {
+        let mut stmt = conn.prepare(sql)?;
+        let columns = stmt.column_count();
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            for i in 0..columns {
+                let value: rusqlite::types::Value = row.get(i)?;
+                hasher.update(format!("{value:?}\u{1f}").as_bytes());
+            }
+            hasher.update(b"\n");
+        }
+        hasher.update(b"\x1d");
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// Nomes (`name`) da tabela já no formato que a importação grava; vazio se a tabela não existe.
+fn table_names(conn: &Connection, table: &str) -> std::collections::HashSet<String> {
+    let Ok(mut stmt) = conn.prepare(&format!("SELECT name FROM {table}")) else {
+        return Default::default();
+    };
+    stmt.query_map([], |r| r.get::<_, String>(0))
+        .map(|rows| {
+            rows.filter_map(|r| r.ok())
+                .map(|n| normalize_vehicle_name(&n))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Monta uma planilha em memória; células vazias viram `Data::Empty`.
+    pub(crate) fn sheet(rows: &[&[&str]]) -> Range<Data> {
+        let width = rows.iter().map(|r| r.len()).max().unwrap_or(1).max(1);
+        let mut range = Range::new((0, 0), (rows.len() as u32 - 1, width as u32 - 1));
+        for (r, row) in rows.iter().enumerate() {
+            for (c, value) in row.iter().enumerate() {
+                let cell = if value.is_empty() {
+                    Data::Empty
+                } else {
+                    Data::String(value.to_string())
+                };
+                range.set_value((r as u32, c as u32), cell);
+            }
+        }
+        range
+    }
+
+    /// XLSX mínimo (uma aba, células inlineStr) para os testes que leem arquivo de verdade.
+    pub(crate) fn write_xlsx(path: &Path, rows: &[&[&str]]) {
+        use std::io::Write;
+        let mut sheet_rows = String::new();
+        for (r, row) in rows.iter().enumerate() {
+            sheet_rows.push_str(&format!("<row r=\"{}\">", r + 1));
+            for value in row.iter() {
+                sheet_rows.push_str(&format!("<c t=\"inlineStr\"><is><t>{value}</t></is></c>"));
+            }
+            sheet_rows.push_str("</row>");
+        }
+        let files = [
+            ("[Content_Types].xml", r#"<?xml version="1.0" encoding="UTF-8"?><Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/><Override PartName="/xl/worksheets/sheet1.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/></Types>"#.to_string()),
+            ("_rels/.rels", r#"<?xml version="1.0" encoding="UTF-8"?><Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="xl/workbook.xml"/></Relationships>"#.to_string()),
+            ("xl/workbook.xml", r#"<?xml version="1.0" encoding="UTF-8"?><workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><sheets><sheet name="Produtos" sheetId="1" r:id="rId1"/></sheets></workbook>"#.to_string()),
+            ("xl/_rels/workbook.xml.rels", r#"<?xml version="1.0" encoding="UTF-8"?><Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet1.xml"/></Relationships>"#.to_string()),
+            ("xl/worksheets/sheet1.xml", format!(r#"<?xml version="1.0" encoding="UTF-8"?><worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetData>{sheet_rows}</sheetData></worksheet>"#)),
+        ];
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(path).unwrap());
+        for (name, body) in files {
+            zip.start_file(name, zip::write::FileOptions::default())
+                .unwrap();
+            zip.write_all(body.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn seed_xlsx_is_imported_only_into_empty_db() {
+        let dir =
+            std::env::temp_dir().join(format!("catalogo_ips_seed_xlsx_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let seed = dir.join("seed.xlsx");
+        write_xlsx(
+            &seed,
+            &[
+                &["MARCA", "CODIGO", "DESCRICAO"],
+                &["IPS", "7111", "PASTILHA"],
+                &["IPS", "7222", "DISCO"],
+            ],
+        );
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::core::migrate(&conn).unwrap();
+        assert!(
+            import_seed_if_empty(&mut conn, &dir.join("nao_existe.xlsx"))
+                .unwrap()
+                .is_none()
+        );
+
+        let result = import_seed_if_empty(&mut conn, &seed).unwrap().unwrap();
+        assert_eq!(result.upserted_products, 2);
+        let count = |conn: &Connection| -> i64 {
+            conn.query_row("SELECT COUNT(*) FROM products", [], |r| r.get(0))
+                .unwrap()
+        };
+        assert_eq!(count(&conn), 2);
+
+        // Banco já populado: a semente não roda de novo.
+        conn.execute("DELETE FROM products WHERE code = '7222'", [])
+            .unwrap();
+        assert!(import_seed_if_empty(&mut conn, &seed).unwrap().is_none());
+        assert_eq!(count(&conn), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn normalizes_codes() {
+        assert_eq!(normalize_code(" 7111 "), "7111");
+        assert_eq!(normalize_code("ab  12\t3"), "AB 12 3");
+    }
+
+    #[test]
+    fn trailing_spaces_do_not_duplicate_codes() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        import_range(
+            &mut conn,
+            &sheet(&[
+                &["MARCA", "CODIGO", "DESCRICAO"],
+                &["IPS", "7111 ", "PASTILHA"],
+                &["IPS", "7111", "PASTILHA DIANTEIRA"],
+            ]),
+        )
+        .unwrap();
+        let (count, description): (i64, String) = conn
+            .query_row(
+                "SELECT COUNT(*), MAX(description) FROM products WHERE code = '7111'",
+                [],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(description, "PASTILHA DIANTEIRA");
+    }
+
+    #[test]
+    fn accent_variants_share_one_brand() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        import_range(
+            &mut conn,
+            &sheet(&[
+                &["MARCA", "CODIGO", "DESCRICAO"],
+                &["Citroën", "1", "PASTILHA"],
+                &["CITROEN", "2", "DISCO"],
+                &[" citroen ", "3", "SAPATA"],
+            ]),
+        )
+        .unwrap();
+        let names: Vec<String> = conn
+            .prepare("SELECT name FROM brands WHERE name <> ?1")
+            .unwrap()
+            .query_map([crate::core::UNKNOWN_BRAND_NAME], |r| r.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(names, vec!["Citroën"]);
+        let distinct: i64 = conn
+            .query_row("SELECT COUNT(DISTINCT brand_id) FROM products", [], |r| {
+                r.get(0)
+            })
+            .unwrap();
+        assert_eq!(distinct, 1);
+    }
+
+    #[test]
+    fn counts_vehicles_without_make() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let result = import_range(
+            &mut conn,
+            &sheet(&[
+                &["MARCA", "CODIGO", "DESCRICAO", "VEICULOS", "MONTADORA"],
+                &["IPS", "1", "PASTILHA", "GOL; UNO", "VW/FIAT"],
+                &["IPS", "2", "PASTILHA", "CELTA", ""],
+            ]),
+        )
+        .unwrap();
+        assert_eq!(result.vehicles_without_make, 1);
+        assert_eq!(
+            result.vehicles_without_make_sample,
+            vec!["CELTA".to_string()]
+        );
+    }
+
+    #[test]
+    fn reports_created_vehicles_and_makes() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let result = import_range(
+            &mut conn,
+            &sheet(&[
+                &["MARCA", "CODIGO", "DESCRICAO", "VEICULOS", "MONTADORA"],
+                &["IPS", "1", "PASTILHA", "GOL; SAVEIRO", "VW"],
+                &["IPS", "2", "DISCO", "UNO", "FIAT"],
+                &["IPS", "3", "SAPATA", "GOL", "VW"],
+            ]),
+        )
+        .unwrap();
+        assert_eq!((result.created_vehicles, result.created_makes), (3, 2));
+    }
+
+    #[test]
+    fn reimporting_same_sheet_creates_nothing() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let rows = sheet(&[
+            &["MARCA", "CODIGO", "DESCRICAO", "VEICULOS", "MONTADORA"],
+            &["IPS", "1", "PASTILHA", "GOL; SAVEIRO", "VW"],
+            &["IPS", "2", "DISCO", "UNO", "FIAT"],
+        ]);
+        import_range(&mut conn, &rows).unwrap();
+        let again = import_range(&mut conn, &rows).unwrap();
+        assert_eq!((again.created_vehicles, again.created_makes), (0, 0));
+    }
+
+    #[test]
+    fn repeated_codes_are_reported_and_last_row_wins() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let result = import_range(
+            &mut conn,
+            &sheet(&[
+                &["MARCA", "CODIGO", "DESCRICAO"],
+                &["IPS", "1", "PASTILHA"],
+                &["IPS", "2", "DISCO"],
+                &["IPS", "1", "PASTILHA NOVA"],
+                &["IPS", "1", "PASTILHA FINAL"],
+            ]),
+        )
+        .unwrap();
+        assert_eq!(result.duplicate_codes_in_file, vec!["1".to_string()]);
+        let description: String = conn
+            .query_row(
+                "SELECT description FROM products WHERE code = '1'",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(description, "PASTILHA FINAL");
+    }
+
+    #[test]
+    fn identical_reimport_keeps_db_version() {
+        let rows: &[&[&str]] = &[
+            &["MARCA", "CODIGO", "DESCRICAO", "VEICULOS", "TAGS"],
+            &["IPS", "1", "PASTILHA", "GOL, UNO", "freio"],
+            &["IPS", "2", "DISCO", "PALIO", ""],
+        ];
+        let mut conn = Connection::open_in_memory().unwrap();
+        let first = import_range(&mut conn, &sheet(rows)).unwrap();
+        assert_eq!(first.new_db_version, 1);
+        let second = import_range(&mut conn, &sheet(rows)).unwrap();
+        assert_eq!(second.new_db_version, 1);
+        assert_eq!(crate::core::get_db_version(&conn).unwrap(), 1);
+
+        let changed = import_range(
+            &mut conn,
+            &sheet(&[
+                &["MARCA", "CODIGO", "DESCRICAO", "VEICULOS", "TAGS"],
+                &["IPS", "1", "PASTILHA", "GOL", "freio"],
+                &["IPS", "2", "DISCO", "PALIO", ""],
+            ]),
+        )
+        .unwrap();
+        assert_eq!(changed.new_db_version, 2);
+    }
+
+    #[test]
+    fn manual_tags_kept_for_sheets_without_tags_column() {
+        let freios = sheet(&[
+            &["MARCA", "CODIGO", "DESCRICAO", "TAGS"],
+            &["IPS", "1", "PASTILHA", "freio"],
+        ]);
+        let motor = sheet(&[&["MARCA", "CODIGO", "DESCRICAO"], &["IPS", "2", "CORREIA"]]);
+        let ranges = vec![("Freios".to_string(), freios), ("Motor".to_string(), motor)];
+        let mut conn = Connection::open_in_memory().unwrap();
+        import_sheets(&mut conn, &ranges, HeaderLocale::Pt).unwrap();
+        for code in ["1", "2"] {
+            let pid: i64 = conn
+                .query_row("SELECT id FROM products WHERE code = ?1", [code], |r| {
+                    r.get(0)
+                })
+                .unwrap();
+            crate::core::add_product_tag(&conn, pid, "manual").unwrap();
+        }
+
+        import_sheets(&mut conn, &ranges, HeaderLocale::Pt).unwrap();
+        let tags: Vec<(String, String)> = conn
+            .prepare(
+                "SELECT p.code, t.name FROM product_tags pt
+                 JOIN products p ON p.id = pt.product_id JOIN tags t ON t.id = pt.tag_id
+                 ORDER BY p.code, t.name",
+            )
+            .unwrap()
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(
+            tags,
+            vec![
+                ("1".to_string(), "freio".to_string()),
+                ("2".to_string(), "manual".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn import_records_provenance() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::core::migrate(&conn).unwrap();
+        assert_eq!(import_provenance(&conn).unwrap(), None);
+        let range = sheet(&[
+            &["MARCA", "CODIGO", "DESCRICAO"],
+            &["IPS", "7111", "PASTILHA"],
+            &["IPS", "7222", "DISCO"],
+        ]);
+        import_sheets_from(
+            &mut conn,
+            &[(String::new(), range)],
+            "C:\\Planilhas\\catalogo_2024.xlsx",
+            HeaderLocale::Pt,
+        )
+        .unwrap();
+
+        let prov = import_provenance(&conn).unwrap().unwrap();
+        assert_eq!(prov.file, "catalogo_2024.xlsx");
+        assert_eq!(prov.rows, 2);
+        assert_eq!(prov.at.len(), "2024-01-01T00:00:00Z".len());
+        assert!(prov.at.ends_with('Z'));
+        assert_eq!(
+            source_label("https://docs.google.com/spreadsheets/d/abc/export?format=xlsx"),
+            "docs.google.com/spreadsheets/d/abc"
+        );
+        assert_eq!(
+            source_label("https://docs.google.com/spreadsheets/d/abc/edit#gid=0"),
+            "docs.google.com/spreadsheets/d/abc"
+        );
+        assert_eq!(
+            source_label("https://example.com/planilhas/cat.xlsx?dl=1"),
+            "example.com/planilhas/cat.xlsx"
+        );
+        assert_eq!(source_label("/home/ips/catalogo.ods"), "catalogo.ods");
+    }
+
+    #[test]
+    fn spanish_headers_import_with_es_locale() {
+        let range = sheet(&[
+            &[
+                "MARCA",
+                "CÓDIGO",
+                "DESCRIPCIÓN",
+                "APLICACIÓN",
+                "EXISTENCIAS",
+            ],
+            &["IPS", "7111", "PASTILLA DE FRENO", "GOL", "4"],
+        ]);
+        let mut conn = Connection::open_in_memory().unwrap();
+        assert!(import_sheets(
+            &mut conn,
+            &[(String::new(), range.clone())],
+            HeaderLocale::Pt
+        )
+        .is_err());
+
+        let locale = HeaderLocale::parse(Some("es")).unwrap();
+        let result = import_sheets(&mut conn, &[(String::new(), range)], locale).unwrap();
+        assert_eq!(result.upserted_products, 1);
+        let (application, stock): (Option<String>, Option<i64>) = conn
+            .query_row(
+                "SELECT application, stock FROM products WHERE code = '7111'",
+                [],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(application.as_deref(), Some("GOL"));
+        assert_eq!(stock, Some(4));
+
+        assert_eq!(HeaderLocale::parse(Some("pt-BR")), Ok(HeaderLocale::Pt));
+        assert_eq!(HeaderLocale::parse(Some("en_US")), Ok(HeaderLocale::En));
+        assert!(HeaderLocale::parse(Some("fr")).is_err());
+    }
+
+    #[test]
+    fn vehicle_spacing_and_case_variants_share_one_vehicle() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        import_range(
+            &mut conn,
+            &sheet(&[
+                &["MARCA", "CODIGO", "DESCRICAO", "VEICULOS"],
+                &["IPS", "1", "PASTILHA", "Gol  1.0"],
+                &["IPS", "2", "DISCO", " GOL 1.0 "],
+            ]),
+        )
+        .unwrap();
+        let names: Vec<String> = conn
+            .prepare("SELECT name FROM vehicles")
+            .unwrap()
+            .query_map([], |r| r.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(names, vec!["GOL 1.0"]);
+        let linked: i64 = conn
+            .query_row(
+                "SELECT COUNT(DISTINCT pv.product_id) FROM product_vehicles pv JOIN vehicles v ON v.id = pv.vehicle_id WHERE v.name = 'GOL 1.0'",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(linked, 2);
+    }
+
+    #[test]
+    fn unreadable_rows_are_skipped_and_reported() {
+        let mut range = sheet(&[
+            &["MARCA", "CODIGO", "DESCRICAO", "APLICACAO"],
+            &["IPS", "1", "PASTILHA", "GOL"],
+            &["IPS", "2", "x", "UNO"],
+            &["IPS", "3", "DISCO", "x"],
+            &["IPS", "x", "SAPATA", ""],
+        ]);
+        range.set_value((2, 2), Data::String("PAST\u{FFFD}LHA".into()));
+        range.set_value((3, 3), Data::Error(calamine::CellErrorType::NA));
+        range.set_value((4, 1), Data::Error(calamine::CellErrorType::Value));
+        let mut conn = Connection::open_in_memory().unwrap();
+        let result = import_range(&mut conn, &range).unwrap();
+
+        assert_eq!((result.upserted_products, result.skipped_rows), (2, 2));
+        let rows: Vec<usize> = result.row_errors.iter().map(|e| e.row).collect();
+        assert_eq!(rows, vec![3, 5]);
+        assert!(result.row_errors[1].message.starts_with("CODIGO"));
+        // Erro fora das colunas-chave só esvazia a célula.
+        let application: Option<String> = conn
+            .query_row(
+                "SELECT application FROM products WHERE code = '3'",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(application.unwrap_or_default(), "");
+    }
+
+    #[test]
+    fn ods_numeric_codes_have_no_decimals() {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/numeric_codes.ods"
+        );
+        let range = read_first_sheet(path).unwrap();
+        let mut conn = Connection::open_in_memory().unwrap();
+        import_range(&mut conn, &range).unwrap();
+        let codes: Vec<String> = conn
+            .prepare("SELECT code FROM products ORDER BY code")
+            .unwrap()
+            .query_map([], |r| r.get(0))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(codes, vec!["1234", "5678", "7111"]);
+    }
+
+    #[test]
+    fn formats_code_cells() {
+        assert_eq!(
+            code_from_cell(Some(&Data::Float(7111.0))),
+            ("7111".to_string(), true)
+        );
+        assert_eq!(
+            code_from_cell(Some(&Data::Float(1e16))),
+            ("10000000000000000".to_string(), true)
+        );
+        assert_eq!(
+            code_from_cell(Some(&Data::Int(7111))),
+            ("7111".to_string(), true)
+        );
+        assert_eq!(
+            code_from_cell(Some(&Data::String(" 007111 ".into()))),
+            ("007111".to_string(), false)
+        );
+        assert_eq!(code_from_cell(Some(&Data::Empty)), (String::new(), false));
+    }
+
+    #[test]
+    fn imports_all_sheets_of_workbook() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/two_sheets.ods");
+        let ranges = read_sheets(path, None, true).unwrap();
+        let mut conn = Connection::open_in_memory().unwrap();
+        let result = import_sheets(&mut conn, &ranges, HeaderLocale::Pt).unwrap();
+        assert_eq!(result.upserted_products, 3);
+        let per_sheet: Vec<(&str, usize)> = result
+            .sheets
+            .iter()
+            .map(|s| (s.sheet.as_str(), s.processed_rows))
+            .collect();
+        assert_eq!(per_sheet, vec![("Freios", 2), ("Suspensao", 1)]);
+
+        let only = read_sheets(path, Some(&["Suspensao".to_string()]), false).unwrap();
+        let result = import_sheets(&mut conn, &only, HeaderLocale::Pt).unwrap();
+        assert_eq!(result.upserted_products, 1);
+        assert!(read_sheets(path, Some(&["Motor".to_string()]), false).is_err());
+    }
+
+    #[tokio::test]
+    async fn imports_spreadsheet_from_url() {
+        let body = std::fs::read(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/two_sheets.ods"
+        ))
+        .unwrap();
+        let (base_url, _hits) = crate::test_http::serve_raw(crate::test_http::ok_response(
+            "application/vnd.oasis.opendocument.spreadsheet",
+            &body,
+        ));
+        let url = format!("{base_url}catalogo.ods");
+        let dir = std::env::temp_dir().join(format!("catalogo_ips_url_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let client = Client::new();
+        let file = download_spreadsheet(&client, &url, &dir, MAX_REMOTE_SHEET_BYTES)
+            .await
+            .unwrap();
+        assert_eq!(file.0.extension().unwrap(), "ods");
+        let second = download_spreadsheet(&client, &url, &dir, MAX_REMOTE_SHEET_BYTES)
+            .await
+            .unwrap();
+        assert_ne!(file.0, second.0);
+        let second_path = second.0.clone();
+        drop(second);
+        assert!(!second_path.exists());
+        let ranges = read_sheets(&file.0.to_string_lossy(), None, true).unwrap();
+        let mut conn = Connection::open_in_memory().unwrap();
+        let result = import_sheets(&mut conn, &ranges, HeaderLocale::Pt).unwrap();
+        assert_eq!(result.upserted_products, 3);
+
+        let (html, _hits) = crate::test_http::serve_raw(crate::test_http::ok_response(
+            "text/html; charset=utf-8",
+            b"<html>login</html>",
+        ));
+        assert!(
+            download_spreadsheet(&client, &html, &dir, MAX_REMOTE_SHEET_BYTES)
+                .await
+                .is_err()
+        );
+
+        // Sem Content-Length: o limite vale para os bytes recebidos.
+        let mut unsized_resp =
+            b"HTTP/1.1 200 OK\r\nContent-Type: application/octet-stream\r\nConnection: close\r\n\r\n"
+                .to_vec();
+        unsized_resp.extend_from_slice(&body);
+        let (unsized_url, _hits) = crate::test_http::serve_raw(unsized_resp);
+        let err = download_spreadsheet(&client, &unsized_url, &dir, 512)
+            .await
+            .unwrap_err();
+        assert!(err.contains("excede o limite"), "{err}");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn google_sheets_links_use_export_endpoint() {
+        assert_eq!(
+            spreadsheet_download_url("https://docs.google.com/spreadsheets/d/abc123/edit#gid=0"),
+            "https://docs.google.com/spreadsheets/d/abc123/export?format=xlsx"
+        );
+        assert_eq!(
+            spreadsheet_download_url("https://example.com/cat.xlsx"),
+            "https://example.com/cat.xlsx"
+        );
+    }
+
+    #[test]
+    fn extracts_product_type() {
+        assert_eq!(product_type("  Pastilha   de freio"), "PASTILHA");
+        assert_eq!(product_type("- Pastilha, dianteira"), "PASTILHA");
+        assert_eq!(product_type("(Disco) ventilado"), "DISCO");
+        assert_eq!(product_type(" ... "), "");
+    }
+
+    #[test]
+    fn parses_stock_cells() {
+        assert_eq!(parse_stock("12"), Some(12));
+        assert_eq!(parse_stock(" 3,0 "), Some(3));
+        assert_eq!(parse_stock(""), None);
+        assert_eq!(parse_stock("n/d"), None);
+    }
+}
@@ -6,17 +6,23 @@ mod call_img;
 mod db;
 mod desc;
 mod importer;
+#[cfg(test)]
+mod test_http;
 mod years;
 
 mod core {
     use super::*;
     use crate::call_img::load_env_key;
-    use crate::db::{db_path, ensure_dirs, open_db, META_DB_VERSION_KEY, META_MANIFEST_HASH_KEY};
+    use crate::db::{
+        db_path, ensure_dirs, open_db, META_DB_VERSION_KEY, META_IMPORT_FINGERPRINT_KEY,
+        META_LAUNCH_DIRS_KEY, META_MANIFEST_HASH_KEY, META_PLACEHOLDER_HASHES_KEY,
+        META_SCHEMA_VERSION_KEY,
+    };
     use reqwest::{
         header::{ACCEPT_ENCODING, CONTENT_ENCODING},
         Client,
     };
-    use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+    use rusqlite::{named_params, params, Connection, OpenFlags, OptionalExtension};
     use serde_json::json;
     use sha2::{Digest, Sha256};
     use std::collections::{HashMap, HashSet};
@@ -32,7 +38,43 @@ mod core {
 
     const GROUP_EXPR_SQL: &str = "UPPER(TRIM(COALESCE(pgroup,'')))";
     const LAUNCH_CANON: &str = "lancamentos";
+    /// Mesmo formato de `META_LAUNCH_DIRS_KEY`; quando definida, tem prioridade sobre o meta.
+    const LAUNCH_DIRS_ENV: &str = "CATALOG_LAUNCH_DIRS";
+    /// Nomes de pasta de lançamento além de `LAUNCH_CANON`, que vale sempre.
+    static EXTRA_LAUNCH_TOKENS: std::sync::RwLock<Vec<String>> = std::sync::RwLock::new(Vec::new());
     const DEFAULT_IMG_CONCURRENCY: usize = 16;
+    /// Busca sem `limit` traz no máximo isso; evita devolver o catálogo inteiro para a grade.
+    const SEARCH_DEFAULT_LIMIT: i64 = 200;
+    /// Teto de `limit` na busca; `CATALOG_SEARCH_MAX_LIMIT` troca o valor.
+    const SEARCH_MAX_LIMIT: i64 = 2000;
+    const SEARCH_MAX_LIMIT_ENV: &str = "CATALOG_SEARCH_MAX_LIMIT";
+    /// Formatos que o webview exibe e o `guess_mime` reconhece; vale para índice, lançamentos e impressão.
+    const DEFAULT_IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "gif", "bmp"];
+    /// Marca sentinela para linhas sem fabricante; garante que o JOIN com brands nunca quebre.
+    pub(crate) const UNKNOWN_BRAND_NAME: &str = "SEM MARCA";
+
+    /// Extensões informadas pelo usuário (minúsculas, sem ponto, sem repetição);
+    /// `None` ou lista vazia mantém o padrão.
+    fn extension_allowlist(custom: Option<&[String]>, default: &[&str]) -> Vec<String> {
+        let mut out: Vec<String> = Vec::new();
+        for ext in custom.unwrap_or_default() {
+            let ext = ext.trim().trim_start_matches('.').to_ascii_lowercase();
+            if !ext.is_empty() && !out.contains(&ext) {
+                out.push(ext);
+            }
+        }
+        if out.is_empty() {
+            out = default.iter().map(|e| e.to_string()).collect();
+        }
+        out
+    }
+
+    fn has_allowed_extension(path: &Path, allow: &[String]) -> bool {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .map(|e| allow.iter().any(|a| a.eq_ignore_ascii_case(e)))
+            .unwrap_or(false)
+    }
 
     fn normalize_launch_token(s: &str) -> String {
         s.to_lowercase()
@@ -47,13 +89,78 @@ mod core {
     }
 
     fn is_launch_component(name: &str) -> bool {
-        normalize_launch_token(name) == LAUNCH_CANON
+        is_launch_component_in(name, &launch_tokens())
+    }
+
+    fn is_launch_component_in(name: &str, extra: &[String]) -> bool {
+        let token = normalize_launch_token(name);
+        token == LAUNCH_CANON || extra.contains(&token)
+    }
+
+    fn launch_tokens() -> Vec<String> {
+        EXTRA_LAUNCH_TOKENS
+            .read()
+            .map(|extra| extra.clone())
+            .unwrap_or_default()
+    }
+
+    /// `is_launch_path(filename)` no SQL, com os nomes extras passados, para a impressão
+    /// separar lançamentos pela mesma regra do `is_launch_path` do Rust.
+    fn register_launch_sql_fn(conn: &Connection, extra: Vec<String>) -> rusqlite::Result<()> {
+        use rusqlite::functions::FunctionFlags;
+        conn.create_scalar_function(
+            "is_launch_path",
+            1,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            move |ctx| {
+                let path: Option<String> = ctx.get(0)?;
+                Ok(path.is_some_and(|p| {
+                    p.replace('\\', "/")
+                        .split('/')
+                        .any(|c| is_launch_component_in(c, &extra))
+                }))
+            },
+        )
+    }
+
+    fn parse_launch_tokens(raw: &str) -> Vec<String> {
+        let mut out: Vec<String> = Vec::new();
+        for token in raw
+            .split([',', ';'])
+            .map(|t| normalize_launch_token(t.trim()))
+        {
+            if !token.is_empty() && token != LAUNCH_CANON && !out.contains(&token) {
+                out.push(token);
+            }
+        }
+        out
+    }
+
+    /// Lê os nomes extras (variável de ambiente ou meta do catalog.db) para o processo todo.
+    fn configure_launch_tokens(conn: &Connection) {
+        let raw = std::env::var(LAUNCH_DIRS_ENV)
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .or_else(|| {
+                conn.query_row(
+                    "SELECT value FROM meta WHERE key = ?1",
+                    params![META_LAUNCH_DIRS_KEY],
+                    |row| row.get::<_, String>(0),
+                )
+                .ok()
+            })
+            .unwrap_or_default();
+        if let Ok(mut extra) = EXTRA_LAUNCH_TOKENS.write() {
+            *extra = parse_launch_tokens(&raw);
+        }
     }
 
     fn is_launch_path(path: &str) -> bool {
         path.replace('\\', "/").split('/').any(is_launch_component)
     }
 
+    /// Chave de comparação de caminhos relativos (manifest x disco): separador `/`, sem caixa e
+    /// sem acento, tanto em forma composta (NFC, "ç") quanto decomposta (NFD, "c" + U+0327).
     fn normalize_rel_path(path: &str) -> String {
         let mut cleaned = path.replace('\\', "/");
         while cleaned.starts_with("./") {
@@ -62,7 +169,12 @@ mod core {
         while cleaned.starts_with('/') {
             cleaned = cleaned.trim_start_matches('/').to_string();
         }
-        cleaned.to_ascii_lowercase()
+        cleaned
+            .chars()
+            .filter(|c| !('\u{0300}'..='\u{036f}').contains(c))
+            .map(crate::importer::fold_char)
+            .flat_map(char::to_lowercase)
+            .collect()
     }
 
     #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -71,6 +183,10 @@ mod core {
         pub images_dir: String,
         pub db_path: String,
         pub db_version: i64,
+        /// Primeira execução: o banco veio da cópia empacotada em vez de nascer vazio.
+        pub seeded_from_bundle: bool,
+        /// Primeira execução sem catalog.db empacotado: produtos importados do seed.xlsx.
+        pub seeded_from_xlsx: bool,
     }
 
     #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -90,7 +206,41 @@ mod core {
         pub code: String,
         pub description: String,
         pub brand: String,
+        pub brand_id: i64,
         pub vehicles: Option<String>,
+        pub stock: Option<i64>,
+        /// Só preenchido com `highlight`; posições em caracteres do texto original.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub highlights: Vec<MatchRange>,
+    }
+    /// Resposta do `search_products_cmd`: `limit` é o que foi aplicado de fato.
+    #[derive(Debug, Serialize, Deserialize, Clone)]
+    pub struct SearchPage {
+        pub items: Vec<ProductListItem>,
+        pub limit: i64,
+        pub max_limit: i64,
+        /// Sem `limit` no pedido (entrou o padrão) ou pedido acima de `max_limit`.
+        pub limit_clamped: bool,
+        /// Itens vindos do `fuzzy_fallback`: sugestões por código parecido, do mais próximo
+        /// ao mais distante, e não resultados da busca.
+        #[serde(default)]
+        pub suggestions: bool,
+    }
+    #[derive(Debug, Serialize, Deserialize, Clone)]
+    pub struct ProductPage {
+        pub items: Vec<ProductListItem>,
+        pub total: i64,
+    }
+    #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+    pub struct SearchExplain {
+        pub plan: Vec<String>,
+        pub elapsed_ms: f64,
+    }
+    #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+    pub struct MatchRange {
+        pub field: String,
+        pub start: usize,
+        pub len: usize,
     }
     #[derive(Debug, Serialize, Deserialize, Clone)]
     pub struct ProductDetails {
@@ -98,6 +248,7 @@ mod core {
         pub code: String,
         pub description: String,
         pub brand: String,
+        pub brand_id: i64,
         pub application: Option<String>,
         pub details: Option<String>,
         pub ean_gtin: Option<String>,
@@ -106,8 +257,10 @@ mod core {
         pub comprimento: Option<String>,
         pub similar: Option<String>,
         pub images: Vec<String>,
+        /// Aplicações vinculadas em product_vehicles, por nome.
+        pub vehicles: Vec<Vehicle>,
     }
-    #[derive(Debug, Serialize, Deserialize, Clone)]
+    #[derive(Debug, Serialize, Deserialize, Clone, Default)]
     pub struct SearchParams {
         pub brand_id: Option<i64>,
         pub group: Option<String>,
@@ -115,6 +268,89 @@ mod core {
         pub vehicle_id: Option<i64>,
         pub code_query: Option<String>,
         pub limit: Option<i64>,
+        #[serde(default, alias = "inStockOnly")]
+        pub in_stock_only: Option<bool>,
+        #[serde(default, alias = "categoryId")]
+        pub category_id: Option<i64>,
+        #[serde(default, alias = "tagIds")]
+        pub tag_ids: Option<Vec<i64>>,
+        /// Trecho do nome do modelo ("GOL", "HILUX 2.8"), independente de vehicle_id.
+        #[serde(default, alias = "vehicleQuery")]
+        pub vehicle_query: Option<String>,
+        /// Código exato ou começo dele, comparado por `code_norm` (usa índice).
+        #[serde(default, alias = "codePrefix")]
+        pub code_prefix: Option<String>,
+        /// Restringe o code_query a alguns campos (ver `SEARCH_FIELDS`); vazio = todos.
+        #[serde(default, alias = "searchFields")]
+        pub search_fields: Option<Vec<String>>,
+        /// Devolve onde cada termo do code_query casou (desligado por padrão).
+        #[serde(default)]
+        pub highlight: Option<bool>,
+        #[serde(default)]
+        pub offset: Option<i64>,
+        /// Emite `search_explain` com o plano e o tempo da consulta (diagnóstico).
+        #[serde(default)]
+        pub explain: Option<bool>,
+        /// Uma das chaves de `SEARCH_SORTS`; ausente = marca e descrição.
+        #[serde(default)]
+        pub sort: Option<String>,
+        /// Inclui produtos descontinuados (`active = 0`), que ficam fora por padrão.
+        #[serde(default, alias = "includeInactive")]
+        pub include_inactive: Option<bool>,
+        /// Trecho do nome de arquivo de imagem ("7111_2", "IPS/7111.jpg"): acha a quem o
+        /// arquivo está vinculado.
+        #[serde(default, alias = "imageFilenameQuery")]
+        pub image_filename_query: Option<String>,
+        /// Se o code_query não achar nada na primeira página, tenta códigos parecidos (erro de
+        /// digitação). Páginas além da última voltam vazias, sem sugestões.
+        #[serde(default, alias = "fuzzyFallback")]
+        pub fuzzy_fallback: Option<bool>,
+        /// Uso interno: restringe aos ids dados (segunda passada do `fuzzy_fallback` e lotes
+        /// dos cursores de busca).
+        #[serde(skip)]
+        pub(crate) only_ids: Option<Vec<i64>>,
+    }
+    #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+    pub struct GroupCount {
+        pub name: String,
+        pub product_count: i64,
+    }
+    /// Só nomes (formato antigo) ou nomes com contagem, conforme `with_counts`.
+    #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+    #[serde(untagged)]
+    pub enum GroupsResult {
+        Names(Vec<String>),
+        Counts(Vec<GroupCount>),
+    }
+    #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+    pub struct TypeCount {
+        pub name: String,
+        pub product_count: i64,
+    }
+    #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+    #[serde(untagged)]
+    pub enum TypesResult {
+        Names(Vec<String>),
+        Counts(Vec<TypeCount>),
+    }
+    #[derive(Debug, Serialize, Deserialize, Clone)]
+    pub struct LaunchImage {
+        pub path: String,
+        pub size_bytes: u64,
+        pub modified_ms: i64,
+        pub width: Option<u32>,
+        pub height: Option<u32>,
+    }
+    #[derive(Debug, Serialize, Deserialize, Clone)]
+    pub struct Tag {
+        pub id: i64,
+        pub name: String,
+    }
+    #[derive(Debug, Serialize, Deserialize, Clone)]
+    pub struct CategoryNode {
+        pub id: i64,
+        pub name: String,
+        pub children: Vec<CategoryNode>,
     }
     #[derive(Debug, Serialize, Deserialize, Clone)]
     pub struct PrintCatalogParams {
@@ -153,15 +389,54 @@ mod core {
         pub url: String,
         pub sha256: Option<String>,
     }
-    #[derive(Debug, Serialize, Deserialize, Clone)]
+    #[derive(Debug, Serialize, Deserialize, Clone, Default)]
     pub struct ManifestImageItem {
         pub file: String,
         pub sha256: Option<String>,
+        /// Código do produto dono da imagem; quando presente, vale mais que o nome do arquivo.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub code: Option<String>,
+        /// Grupo de exibição (ex.: um lançamento) definido pelo servidor.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub group: Option<String>,
     }
     #[derive(Debug, Serialize, Deserialize, Clone)]
     pub struct ManifestImages {
         pub base_url: String,
         pub files: Vec<ManifestImageItem>,
+        /// Bases alternativas por prefixo do caminho (ex.: "BOSCH/" em outro bucket).
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub per_prefix_base: Vec<ImageBaseOverride>,
+    }
+    #[derive(Debug, Serialize, Deserialize, Clone)]
+    pub struct ImageBaseOverride {
+        pub prefix: String,
+        pub base_url: String,
+    }
+
+    impl ManifestImages {
+        /// URL de download do arquivo: absoluto como veio, senão o prefixo mais longo que casar
+        /// em `per_prefix_base` e, na falta, `base_url`.
+        fn url_for(&self, file: &str) -> String {
+            if file.starts_with("http://") || file.starts_with("https://") {
+                return file.to_string();
+            }
+            let rel = file.replace('\\', "/");
+            let base = self
+                .per_prefix_base
+                .iter()
+                .filter(|o| !o.prefix.is_empty() && rel.starts_with(&o.prefix.replace('\\', "/")))
+                .max_by_key(|o| o.prefix.len())
+                .map(|o| o.base_url.as_str())
+                .unwrap_or(&self.base_url);
+            match url::Url::parse(base) {
+                Ok(parsed) => parsed
+                    .join(file)
+                    .map(|u| u.to_string())
+                    .unwrap_or_else(|_| format!("{}{}", base, file)),
+                Err(_) => format!("{}{}", base, file),
+            }
+        }
     }
 
     #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -191,18 +466,65 @@ mod core {
         pub db: ManifestDb,
         pub images: Option<ManifestImages>,
     }
+    /// Resumo para o suporte; `key_configured` diz só se há chave, nunca qual.
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct SystemInfo {
+        pub app_version: String,
+        pub db_version: i64,
+        pub data_dir: String,
+        pub db_path: String,
+        pub images_dir: String,
+        pub key_configured: bool,
+        /// Última importação de planilha neste banco (None se o banco veio só da sincronização).
+        pub last_import: Option<crate::importer::ImportProvenance>,
+    }
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    pub struct UpdateInfo {
+        pub remote_version: i64,
+        pub local_version: i64,
+        pub update_available: bool,
+        pub image_count: usize,
+    }
+    /// O que `sync_from_manifest` faria agora, sem baixar nada além do manifest.
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    pub struct SyncPreview {
+        pub db_will_update: bool,
+        pub from_version: i64,
+        pub to_version: i64,
+        pub images_to_download: usize,
+        pub images_up_to_date: usize,
+        /// Banco será trocado: depois da troca vale o images_cache do banco baixado, então
+        /// as contagens de imagens acima são só estimativa.
+        pub images_approximate: bool,
+    }
     #[derive(Debug, Serialize, Deserialize)]
     pub struct SyncResult {
         pub updated_db: bool,
         pub downloaded_images: usize,
         pub db_version: i64,
     }
+    #[derive(Debug, Serialize, Deserialize, Default, PartialEq)]
+    pub struct RepairOrphansResult {
+        pub product_vehicles: usize,
+        pub images: usize,
+        pub vehicle_makes: usize,
+    }
     #[derive(Debug, Serialize, Deserialize)]
     pub struct CleanupResult {
         pub removed_files: usize,
         pub kept_files: usize,
         pub total_scanned: usize,
         pub manifest_files: usize,
+        /// Manifest sem bloco de imagens (ou sem caminhos válidos): nada foi removido.
+        #[serde(default)]
+        pub empty_manifest: bool,
+    }
+    #[derive(Debug, Serialize, Deserialize, Default, PartialEq)]
+    pub struct ExpireLaunchResult {
+        pub removed_files: usize,
+        pub kept_files: usize,
+        /// Bytes liberados pelos arquivos removidos.
+        pub removed_bytes: u64,
     }
 
     #[derive(Debug, Serialize, Deserialize)]
@@ -210,6 +532,29 @@ mod core {
         pub scanned: usize,
         pub matched: usize,
         pub inserted: usize,
+        /// Arquivos com hash de placeholder ("sem foto") que não foram vinculados.
+        #[serde(default)]
+        pub skipped_placeholders: usize,
+        /// Arquivos cujo nome/código não casou com nenhum produto.
+        #[serde(default)]
+        pub unmatched: usize,
+        /// Primeiros arquivos sem produto (até `UNMATCHED_SAMPLE_LIMIT`), para corrigir os nomes.
+        #[serde(default)]
+        pub unmatched_sample: Vec<String>,
+        /// Manifest sem bloco de imagens / lista vazia: zeros aqui não significam sucesso.
+        #[serde(default)]
+        pub empty_manifest: bool,
+        /// Arquivos cujo nome casou com mais de um produto (ex.: "7111_2" x 7111 e 71112).
+        #[serde(default)]
+        pub ambiguous: usize,
+        /// Primeiros arquivos ambíguos (até `UNMATCHED_SAMPLE_LIMIT`) e os códigos que casaram.
+        #[serde(default)]
+        pub ambiguous_sample: Vec<AmbiguousImage>,
+    }
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    pub struct AmbiguousImage {
+        pub file: String,
+        pub codes: Vec<String>,
     }
     #[derive(Debug, Serialize, Deserialize)]
     pub struct ExportResult {
@@ -234,6 +579,9 @@ mod core {
         pub public_base_url: Option<String>,
     }
 
+    /// Esquema que este `migrate` produz; suba ao mudar tabelas, colunas ou índices.
+    pub(crate) const SCHEMA_VERSION: i64 = 1;
+
     pub(crate) fn migrate(conn: &Connection) -> Result<()> {
         conn.execute_batch(
             r#"
@@ -253,9 +601,24 @@ mod core {
             CREATE TABLE IF NOT EXISTS products (
               id INTEGER PRIMARY KEY, brand_id INTEGER NOT NULL, code TEXT NOT NULL UNIQUE,
               description TEXT NOT NULL, application TEXT, details TEXT, oem TEXT, similar TEXT, pgroup TEXT,
-              ean_gtin TEXT, altura TEXT, largura TEXT, comprimento TEXT,
+              ean_gtin TEXT, altura TEXT, largura TEXT, comprimento TEXT, stock INTEGER,
+              category_id INTEGER, ptype TEXT, code_norm TEXT COLLATE NOCASE, active INTEGER DEFAULT 1,
               FOREIGN KEY(brand_id) REFERENCES brands(id)
             );
+            CREATE TABLE IF NOT EXISTS tags (
+              id INTEGER PRIMARY KEY,
+              name TEXT NOT NULL UNIQUE COLLATE NOCASE
+            );
+            CREATE TABLE IF NOT EXISTS product_tags (
+              product_id INTEGER NOT NULL, tag_id INTEGER NOT NULL,
+              PRIMARY KEY (product_id, tag_id)
+            );
+            CREATE TABLE IF NOT EXISTS categories (
+              id INTEGER PRIMARY KEY,
+              parent_id INTEGER,
+              name TEXT NOT NULL,
+              FOREIGN KEY(parent_id) REFERENCES categories(id)
+            );
             CREATE TABLE IF NOT EXISTS vehicle_makes (
               vehicle_id INTEGER NOT NULL,
               make_id INTEGER NOT NULL,
@@ -271,6 +634,12 @@ mod core {
               id INTEGER PRIMARY KEY, product_id INTEGER NOT NULL, filename TEXT NOT NULL,
               UNIQUE(product_id, filename)
             );
+            CREATE TABLE IF NOT EXISTS image_overrides (
+              product_code TEXT NOT NULL,
+              filename TEXT NOT NULL,
+              action TEXT NOT NULL CHECK(action IN ('link', 'exclude')),
+              PRIMARY KEY(product_code, filename)
+            );
             CREATE TABLE IF NOT EXISTS images_cache (
               filename TEXT PRIMARY KEY,
               sha256 TEXT
@@ -306,39 +675,347 @@ mod core {
         let _ = conn.execute("ALTER TABLE products ADD COLUMN altura TEXT", []);
         let _ = conn.execute("ALTER TABLE products ADD COLUMN largura TEXT", []);
         let _ = conn.execute("ALTER TABLE products ADD COLUMN comprimento TEXT", []);
-        let _ = conn.execute("ALTER TABLE vehicles ADD COLUMN make TEXT", []);
-        let _ = conn.execute("ALTER TABLE vehicles ADD COLUMN make_id INTEGER", []);
-        let _ = conn.execute("ALTER TABLE vehicles ADD COLUMN category TEXT", []);
-        let _ = conn.execute("ALTER TABLE vehicles ADD COLUMN years TEXT", []);
+        let _ = conn.execute("ALTER TABLE products ADD COLUMN stock INTEGER", []);
+        let _ = conn.execute("ALTER TABLE products ADD COLUMN category_id INTEGER", []);
+        let _ = conn.execute("ALTER TABLE products ADD COLUMN ptype TEXT", []);
         let _ = conn.execute(
-            "CREATE TABLE IF NOT EXISTS vehicle_makes (vehicle_id INTEGER NOT NULL, make_id INTEGER NOT NULL, PRIMARY KEY(vehicle_id, make_id))",
+            "ALTER TABLE products ADD COLUMN active INTEGER DEFAULT 1",
             [],
         );
+        // NOCASE para o LIKE 'X%' poder usar o índice (case_sensitive_like desligado).
         let _ = conn.execute(
-            "CREATE TABLE IF NOT EXISTS makes (id INTEGER PRIMARY KEY, name TEXT NOT NULL UNIQUE)",
+            "ALTER TABLE products ADD COLUMN code_norm TEXT COLLATE NOCASE",
             [],
         );
-        let _ = conn.execute(
+        let _ = conn.execute("ALTER TABLE vehicles ADD COLUMN make TEXT", []);
+        let _ = conn.execute("ALTER TABLE vehicles ADD COLUMN make_id INTEGER", []);
+        let _ = conn.execute("ALTER TABLE vehicles ADD COLUMN category TEXT", []);
+        let _ = conn.execute("ALTER TABLE vehicles ADD COLUMN years TEXT", []);
+        // Índices depois dos ALTERs: bancos antigos só ganham vehicles.make/pgroup acima.
+        conn.execute_batch(
+            r#"
+            CREATE INDEX IF NOT EXISTS idx_products_brand ON products(brand_id);
+            CREATE INDEX IF NOT EXISTS idx_products_pgroup ON products(pgroup);
+            CREATE INDEX IF NOT EXISTS idx_product_vehicles_vehicle ON product_vehicles(vehicle_id);
+            CREATE INDEX IF NOT EXISTS idx_product_vehicles_product ON product_vehicles(product_id);
+            CREATE INDEX IF NOT EXISTS idx_images_product ON images(product_id);
+            CREATE INDEX IF NOT EXISTS idx_vehicles_make ON vehicles(make);
+            CREATE INDEX IF NOT EXISTS idx_products_code_norm ON products(code_norm);
+        "#,
+        )?;
+        let _ = normalize_vehicles_makes(conn);
+        let _ = backfill_product_types(conn);
+        let _ = backfill_vehicle_years(conn);
+        let mut merged = merge_near_duplicate_codes(conn).unwrap_or(0);
+        let _ = backfill_code_norm(conn);
+        merged += merge_accent_variant_brands(conn).unwrap_or(0);
+        merged += merge_vehicle_name_variants(conn).unwrap_or(0);
+        if merged > 0 {
+            let _ = forget_import_fingerprint(conn);
+        }
+        let _ = seed_brand_groups(conn);
+        ensure_unknown_brand(conn)?;
+        // Banco gerado por versão mais nova do app mantém o número dele.
+        if get_schema_version(conn)? < SCHEMA_VERSION {
+            conn.execute(
+                "INSERT OR REPLACE INTO meta(key,value) VALUES(?1, ?2)",
+                params![META_SCHEMA_VERSION_KEY, SCHEMA_VERSION.to_string()],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Migra só quando o esquema gravado no banco é mais antigo que o do app; `true` se migrou.
+    /// Banco sem tabela meta conta como esquema 0.
+    pub(crate) fn ensure_schema(conn: &Connection) -> Result<bool> {
+        if get_schema_version(conn).unwrap_or(0) >= SCHEMA_VERSION {
+            return Ok(false);
+        }
+        migrate(conn)?;
+        Ok(true)
+    }
+
+    /// Deriva a montadora do primeiro token do nome quando vazia e mantém makes,
+    /// vehicles.make_id e vehicle_makes coerentes. Idempotente.
+    fn normalize_vehicles_makes(conn: &Connection) -> Result<()> {
+        conn.execute(
             "UPDATE vehicles SET make = UPPER(TRIM(CASE WHEN INSTR(name,' ')>0 THEN SUBSTR(name,1,INSTR(name,' ')-1) ELSE name END)) WHERE make IS NULL OR TRIM(COALESCE(make,''))=''",
             [],
-        );
-        let _ = conn.execute(
+        )?;
+        conn.execute(
             "INSERT OR IGNORE INTO makes(name) SELECT DISTINCT UPPER(TRIM(COALESCE(make,''))) FROM vehicles WHERE TRIM(COALESCE(make,'')) <> ''",
             [],
-        );
-        let _ = conn.execute(
+        )?;
+        conn.execute(
             "UPDATE vehicles SET make_id = (SELECT id FROM makes m WHERE UPPER(TRIM(m.name)) = UPPER(TRIM(COALESCE(vehicles.make,'')))) WHERE make_id IS NULL AND TRIM(COALESCE(make,'')) <> ''",
             [],
-        );
-        let _ = conn.execute(
+        )?;
+        conn.execute(
             "INSERT OR IGNORE INTO vehicle_makes(vehicle_id, make_id) SELECT v.id, m.id FROM vehicles v JOIN makes m ON UPPER(TRIM(m.name)) = UPPER(TRIM(COALESCE(v.make,''))) WHERE TRIM(COALESCE(v.make,'')) <> ''",
             [],
-        );
-        let _ = backfill_vehicle_years(conn);
-        let _ = seed_brand_groups(conn);
+        )?;
         Ok(())
     }
 
+    /// Roda `f` num SAVEPOINT: RELEASE se der certo, ROLLBACK TO + RELEASE se falhar, para
+    /// um erro (ignorado pelo `migrate`, por exemplo) não deixar transação aberta na conexão.
+    fn in_savepoint<T>(conn: &Connection, name: &str, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        conn.execute_batch(&format!("SAVEPOINT {name}"))?;
+        match f() {
+            Ok(v) => {
+                conn.execute_batch(&format!("RELEASE {name}"))?;
+                Ok(v)
+            }
+            Err(e) => {
+                let _ = conn.execute_batch(&format!("ROLLBACK TO {name}; RELEASE {name}"));
+                Err(e)
+            }
+        }
+    }
+
+    /// Preenche products.ptype nos bancos anteriores à coluna (ou vindos de fora).
+    fn backfill_product_types(conn: &Connection) -> Result<usize> {
+        let pending: Vec<(i64, String)> = {
+            let mut stmt = conn.prepare(
+                "SELECT id, COALESCE(description, '') FROM products WHERE ptype IS NULL",
+            )?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+        if pending.is_empty() {
+            return Ok(0);
+        }
+        in_savepoint(conn, "backfill_ptype", || {
+            let mut update = conn.prepare("UPDATE products SET ptype = ?1 WHERE id = ?2")?;
+            for (id, description) in pending.iter() {
+                update.execute(params![crate::importer::product_type(description), id])?;
+            }
+            Ok(pending.len())
+        })
+    }
+
+    /// Preenche products.code_norm onde ainda está vazio (bancos antigos, código recém-trocado).
+    fn backfill_code_norm(conn: &Connection) -> Result<usize> {
+        let pending: Vec<(i64, String)> = {
+            let mut stmt = conn.prepare("SELECT id, code FROM products WHERE code_norm IS NULL")?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+        if pending.is_empty() {
+            return Ok(0);
+        }
+        in_savepoint(conn, "backfill_code_norm", || {
+            let mut update = conn.prepare("UPDATE products SET code_norm = ?1 WHERE id = ?2")?;
+            for (id, code) in pending.iter() {
+                update.execute(params![crate::importer::code_search_key(code), id])?;
+            }
+            Ok(pending.len())
+        })
+    }
+
+    /// Une produtos cujo código só difere por espaços/caixa (ex.: "7111 " e "7111"),
+    /// repontando veículos, imagens e tags para o sobrevivente.
+    fn merge_near_duplicate_codes(conn: &Connection) -> Result<usize> {
+        let dirty: Option<i64> = conn
+            .query_row(
+                "SELECT 1 FROM products WHERE code <> UPPER(TRIM(code)) OR code LIKE '%  %' OR code GLOB ('*[' || char(9, 10, 13) || ']*') LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if dirty.is_none() {
+            return Ok(0);
+        }
+        let mut by_code: HashMap<String, Vec<(i64, String)>> = HashMap::new();
+        {
+            let mut stmt = conn.prepare("SELECT id, code FROM products ORDER BY id")?;
+            let rows = stmt.query_map([], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })?;
+            for r in rows {
+                let (id, code) = r?;
+                by_code
+                    .entry(crate::importer::normalize_code(&code))
+                    .or_default()
+                    .push((id, code));
+            }
+        }
+        in_savepoint(conn, "merge_codes", || {
+            let mut merged = 0usize;
+            for (norm, items) in by_code {
+                let survivor = items
+                    .iter()
+                    .find(|(_, code)| *code == norm)
+                    .unwrap_or(&items[0])
+                    .0;
+                for (id, _) in items.iter().filter(|(id, _)| *id != survivor) {
+                    for table in ["product_vehicles", "images", "product_tags"] {
+                        conn.execute(
+                            &format!(
+                                "UPDATE OR IGNORE {table} SET product_id = ?1 WHERE product_id = ?2"
+                            ),
+                            params![survivor, id],
+                        )?;
+                        conn.execute(
+                            &format!("DELETE FROM {table} WHERE product_id = ?1"),
+                            params![id],
+                        )?;
+                    }
+                    conn.execute("DELETE FROM products WHERE id = ?1", params![id])?;
+                    merged += 1;
+                }
+                conn.execute(
+                    "UPDATE products SET code = ?1, code_norm = NULL WHERE id = ?2 AND code <> ?1",
+                    params![norm, survivor],
+                )?;
+            }
+            Ok(merged)
+        })
+    }
+
+    /// Busca a marca ignorando caixa, acentos e espaços (ver `importer::fold_key`).
+    pub(crate) fn find_brand_by_name(conn: &Connection, name: &str) -> Result<Option<i64>> {
+        let key = crate::importer::fold_key(name);
+        if key.is_empty() {
+            return Ok(None);
+        }
+        let mut stmt = conn.prepare("SELECT id, name FROM brands ORDER BY id")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?;
+        for r in rows {
+            let (id, existing) = r?;
+            if crate::importer::fold_key(&existing) == key {
+                return Ok(Some(id));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Une marcas que só diferem por acento/caixa ("Citroën" x "CITROEN"); fica a mais antiga.
+    fn merge_accent_variant_brands(conn: &Connection) -> Result<usize> {
+        let mut by_key: HashMap<String, Vec<i64>> = HashMap::new();
+        {
+            let mut stmt = conn.prepare("SELECT id, name FROM brands ORDER BY id")?;
+            let rows = stmt.query_map([], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })?;
+            for r in rows {
+                let (id, name) = r?;
+                by_key
+                    .entry(crate::importer::fold_key(&name))
+                    .or_default()
+                    .push(id);
+            }
+        }
+        let duplicated: Vec<Vec<i64>> = by_key.into_values().filter(|ids| ids.len() > 1).collect();
+        if duplicated.is_empty() {
+            return Ok(0);
+        }
+        in_savepoint(conn, "merge_brands", || {
+            let mut merged = 0usize;
+            for ids in duplicated {
+                let survivor = ids[0];
+                for id in &ids[1..] {
+                    conn.execute(
+                        "UPDATE products SET brand_id = ?1 WHERE brand_id = ?2",
+                        params![survivor, id],
+                    )?;
+                    conn.execute(
+                        "UPDATE OR IGNORE brand_groups SET brand_id = ?1 WHERE brand_id = ?2",
+                        params![survivor, id],
+                    )?;
+                    conn.execute("DELETE FROM brand_groups WHERE brand_id = ?1", params![id])?;
+                    conn.execute("DELETE FROM brands WHERE id = ?1", params![id])?;
+                    merged += 1;
+                }
+            }
+            Ok(merged)
+        })
+    }
+
+    /// Une veículos cujo nome só difere por espaços/caixa ("Gol  1.0" x "GOL 1.0"),
+    /// repontando produtos e montadoras para o mais antigo, que fica com o nome canônico
+    /// e herda make/category/years que ele não tinha.
+    fn merge_vehicle_name_variants(conn: &Connection) -> Result<usize> {
+        let dirty: Option<i64> = conn
+            .query_row(
+                "SELECT 1 FROM vehicles WHERE name <> UPPER(TRIM(name)) OR name LIKE '%  %' OR name GLOB ('*[' || char(9, 10, 13) || ']*') LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if dirty.is_none() {
+            return Ok(0);
+        }
+        let mut by_name: HashMap<String, Vec<(i64, String)>> = HashMap::new();
+        {
+            let mut stmt = conn.prepare("SELECT id, name FROM vehicles ORDER BY id")?;
+            let rows = stmt.query_map([], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })?;
+            for r in rows {
+                let (id, name) = r?;
+                by_name
+                    .entry(crate::importer::normalize_vehicle_name(&name))
+                    .or_default()
+                    .push((id, name));
+            }
+        }
+        by_name.retain(|norm, items| items.len() > 1 || items[0].1 != *norm);
+        if by_name.is_empty() {
+            return Ok(0);
+        }
+        in_savepoint(conn, "merge_vehicles", || {
+            let mut merged = 0usize;
+            for (norm, items) in by_name {
+                let survivor = items[0].0;
+                for (id, _) in &items[1..] {
+                    conn.execute(
+                        "UPDATE vehicles SET
+                           make = COALESCE(NULLIF(TRIM(make),''), (SELECT make FROM vehicles WHERE id = ?2)),
+                           make_id = COALESCE(make_id, (SELECT make_id FROM vehicles WHERE id = ?2)),
+                           category = COALESCE(NULLIF(TRIM(category),''), (SELECT category FROM vehicles WHERE id = ?2)),
+                           years = COALESCE(NULLIF(TRIM(years),''), (SELECT years FROM vehicles WHERE id = ?2))
+                         WHERE id = ?1",
+                        params![survivor, id],
+                    )?;
+                    for table in ["product_vehicles", "vehicle_makes"] {
+                        conn.execute(
+                            &format!(
+                                "UPDATE OR IGNORE {table} SET vehicle_id = ?1 WHERE vehicle_id = ?2"
+                            ),
+                            params![survivor, id],
+                        )?;
+                        conn.execute(
+                            &format!("DELETE FROM {table} WHERE vehicle_id = ?1"),
+                            params![id],
+                        )?;
+                    }
+                    conn.execute("DELETE FROM vehicles WHERE id = ?1", params![id])?;
+                    merged += 1;
+                }
+                conn.execute(
+                    "UPDATE vehicles SET name = ?1 WHERE id = ?2 AND name <> ?1",
+                    params![norm, survivor],
+                )?;
+            }
+            Ok(merged)
+        })
+    }
+
+    pub(crate) fn ensure_unknown_brand(conn: &Connection) -> Result<i64> {
+        conn.execute(
+            "INSERT OR IGNORE INTO brands(name) VALUES(?1)",
+            params![UNKNOWN_BRAND_NAME],
+        )?;
+        Ok(conn.query_row(
+            "SELECT id FROM brands WHERE name = ?1",
+            params![UNKNOWN_BRAND_NAME],
+            |row| row.get(0),
+        )?)
+    }
+
     fn backfill_vehicle_years(conn: &Connection) -> Result<()> {
         let current_year = crate::years::current_year();
         let mut stmt =
@@ -388,66 +1065,3076 @@ mod core {
             assert!(years.contains("2006"));
             assert!(years.contains("2015"));
         }
-    }
 
-    pub(crate) fn get_db_version(conn: &Connection) -> Result<i64> {
-        Ok(conn.query_row(
-            "SELECT CAST(value AS INTEGER) FROM meta WHERE key = ?1",
-            params![META_DB_VERSION_KEY],
-            |row| row.get(0),
-        )?)
-    }
-    pub(crate) fn set_db_version(conn: &Connection, v: i64) -> Result<()> {
-        conn.execute(
-            "INSERT OR REPLACE INTO meta(key,value) VALUES(?1, ?2)",
-            params![META_DB_VERSION_KEY, v.to_string()],
-        )?;
-        Ok(())
-    }
-    fn get_manifest_hash(conn: &Connection) -> Result<Option<String>> {
-        Ok(conn
-            .query_row(
-                "SELECT value FROM meta WHERE key = ?1",
-                params![META_MANIFEST_HASH_KEY],
-                |row| row.get(0),
+        #[test]
+        fn in_stock_only_keeps_positive_stock() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO", "ESTOQUE"],
+                    &["IPS", "A1", "PASTILHA", "5"],
+                    &["IPS", "A2", "DISCO", "0"],
+                    &["IPS", "A3", "SAPATA", ""],
+                ]),
             )
-            .optional()?)
-    }
-    fn set_manifest_hash(conn: &Connection, v: &str) -> Result<()> {
-        conn.execute(
-            "INSERT OR REPLACE INTO meta(key,value) VALUES(?1, ?2)",
-            params![META_MANIFEST_HASH_KEY, v],
-        )?;
-        Ok(())
-    }
+            .unwrap();
 
-    fn seed_catalog_db_candidates(app: &AppHandle) -> Vec<PathBuf> {
-        let mut candidates = Vec::new();
-        if let Ok(res_dir) = app.path().resource_dir() {
-            candidates.push(res_dir.join("catalog.db"));
-            candidates.push(res_dir.join("data").join("catalog.db"));
-        }
-        if let Ok(cwd) = std::env::current_dir() {
-            let app_root = if cwd.ends_with("src-tauri") {
-                cwd.parent().unwrap_or(&cwd).to_path_buf()
-            } else {
-                cwd
+            let all = search_products(&conn, &SearchParams::default()).unwrap();
+            assert_eq!(all.len(), 3);
+            let a3 = all.iter().find(|p| p.code == "A3").unwrap();
+            assert_eq!(a3.stock, None);
+
+            let params = SearchParams {
+                in_stock_only: Some(true),
+                ..Default::default()
             };
-            candidates.push(app_root.join("data").join("catalog.db"));
+            let in_stock = search_products(&conn, &params).unwrap();
+            assert_eq!(in_stock.len(), 1);
+            assert_eq!(in_stock[0].code, "A1");
+            assert_eq!(in_stock[0].stock, Some(5));
         }
-        candidates
-    }
 
-    fn copy_seed_catalog_db(app: &AppHandle, db_file: &Path) -> Result<Option<PathBuf>> {
-        for seed in seed_catalog_db_candidates(app) {
-            if !seed.exists() {
-                continue;
-            }
-            if let Some(parent) = db_file.parent() {
+        #[test]
+        fn category_tree_and_child_filter() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO", "CATEGORIA", "SUBCATEGORIA"],
+                    &["IPS", "F1", "FILTRO OLEO", "FILTROS", "OLEO"],
+                    &["IPS", "F2", "FILTRO AR", "FILTROS", "AR"],
+                    &["IPS", "F3", "FILTRO AR ESPORTIVO", "FILTROS", "AR"],
+                ]),
+            )
+            .unwrap();
+
+            let tree = fetch_category_tree(&conn).unwrap();
+            assert_eq!(tree.len(), 1);
+            assert_eq!(tree[0].name, "FILTROS");
+            let children: Vec<&str> = tree[0].children.iter().map(|c| c.name.as_str()).collect();
+            assert_eq!(children, vec!["AR", "OLEO"]);
+
+            let ar = &tree[0].children[0];
+            let by_child = search_products(
+                &conn,
+                &SearchParams {
+                    category_id: Some(ar.id),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            let mut codes: Vec<String> = by_child.into_iter().map(|p| p.code).collect();
+            codes.sort();
+            assert_eq!(codes, vec!["F2", "F3"]);
+
+            let by_parent = search_products(
+                &conn,
+                &SearchParams {
+                    category_id: Some(tree[0].id),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            assert_eq!(by_parent.len(), 3);
+        }
+
+        #[test]
+        fn tag_filter_requires_all_tags() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            let sheet = crate::importer::tests::sheet(&[
+                &["MARCA", "CODIGO", "DESCRICAO"],
+                &["IPS", "T1", "PASTILHA"],
+                &["IPS", "T2", "DISCO"],
+                &["IPS", "T3", "SAPATA"],
+            ]);
+            crate::importer::import_range(&mut conn, &sheet).unwrap();
+            let id_of = |code: &str| -> i64 {
+                conn.query_row(
+                    "SELECT id FROM products WHERE code = ?1",
+                    params![code],
+                    |r| r.get(0),
+                )
+                .unwrap()
+            };
+            let promo = add_product_tag(&conn, id_of("T1"), "promo").unwrap();
+            add_product_tag(&conn, id_of("T2"), "PROMO").unwrap();
+            let novo = add_product_tag(&conn, id_of("T2"), "novo").unwrap();
+            assert_eq!(list_tags(&conn).unwrap().len(), 2);
+
+            fn search(conn: &Connection, ids: Vec<i64>) -> Vec<String> {
+                let params = SearchParams {
+                    tag_ids: Some(ids),
+                    ..Default::default()
+                };
+                let mut codes: Vec<String> = search_products(conn, &params)
+                    .unwrap()
+                    .into_iter()
+                    .map(|p| p.code)
+                    .collect();
+                codes.sort();
+                codes
+            }
+            assert_eq!(search(&conn, vec![promo.id]), vec!["T1", "T2"]);
+            assert_eq!(search(&conn, vec![promo.id, novo.id]), vec!["T2"]);
+
+            // Sem coluna TAGS, reimportar mantém as tags manuais.
+            crate::importer::import_range(&mut conn, &sheet).unwrap();
+            assert_eq!(search(&conn, vec![promo.id]), vec!["T1", "T2"]);
+        }
+
+        #[test]
+        fn blank_brand_rows_join_sentinel_brand() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO"],
+                    &["", "X1", "PECA SEM MARCA"],
+                ]),
+            )
+            .unwrap();
+
+            let found = search_products(&conn, &SearchParams::default()).unwrap();
+            assert_eq!(found.len(), 1);
+            assert_eq!(found[0].brand, UNKNOWN_BRAND_NAME);
+        }
+
+        #[tokio::test]
+        async fn second_sync_with_same_manifest_is_noop() {
+            use std::sync::atomic::Ordering;
+            const PNG: &[u8] = b"\x89PNG\r\n\x1a\nfake";
+            let (base_url, hits) =
+                crate::test_http::serve_raw(crate::test_http::ok_response("image/png", PNG));
+            let dir =
+                std::env::temp_dir().join(format!("catalogo_ips_sync_{}", std::process::id()));
+            let imgs_dir = dir.join("images");
+            fs::create_dir_all(&imgs_dir).unwrap();
+            let dbf = dir.join("catalog.db");
+            {
+                let conn = open_db(&dbf).unwrap();
+                migrate(&conn).unwrap();
+                set_db_version(&conn, 7).unwrap();
+                set_manifest_hash(&conn, "h1").unwrap();
+            }
+            let sha: String = Sha256::digest(PNG)
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect();
+            let manifest = CatalogManifest {
+                db: ManifestDb {
+                    version: 7,
+                    url: format!("{base_url}catalog.db"),
+                    sha256: None,
+                },
+                images: Some(ManifestImages {
+                    per_prefix_base: Vec::new(),
+                    base_url,
+                    files: vec![ManifestImageItem {
+                        file: "IPS/7111.png".into(),
+                        sha256: Some(sha),
+                        ..Default::default()
+                    }],
+                }),
+            };
+
+            let conn = open_db(&dbf).unwrap();
+            assert!(!sync_is_noop(&conn, &manifest, "h1", &imgs_dir));
+            let imgs = manifest.images.as_ref().unwrap();
+            let (down, errs) =
+                download_images_sequential(&Client::new(), &imgs_dir, &dbf, imgs, false).await;
+            assert_eq!((down, errs), (1, 0));
+            assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+            // Segunda sincronização: mesmo manifest, nada para baixar.
+            assert!(sync_is_noop(&conn, &manifest, "h1", &imgs_dir));
+            assert!(!sync_is_noop(&conn, &manifest, "h2", &imgs_dir));
+            assert_eq!(hits.load(Ordering::SeqCst), 1);
+            drop(conn);
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        #[tokio::test]
+        async fn images_go_to_overridden_dir() {
+            const PNG: &[u8] = b"\x89PNG\r\n\x1a\nfake";
+            let (base_url, _hits) =
+                crate::test_http::serve_raw(crate::test_http::ok_response("image/png", PNG));
+            let base =
+                std::env::temp_dir().join(format!("catalogo_ips_imgsdir_{}", std::process::id()));
+            let _ = fs::remove_dir_all(&base);
+            let custom = base.join("outro_disco").join("imagens");
+            crate::db::set_images_dir_override(&base.join("dados"), Some(&custom)).unwrap();
+            let (data_dir, dbf, imgs_dir) = crate::db::ensure_dirs_in(base.join("dados")).unwrap();
+            assert_eq!(imgs_dir, custom);
+            {
+                let conn = open_db(&dbf).unwrap();
+                migrate(&conn).unwrap();
+            }
+            let imgs = ManifestImages {
+                per_prefix_base: Vec::new(),
+                base_url,
+                files: vec![ManifestImageItem {
+                    file: "IPS/7111.png".into(),
+                    ..Default::default()
+                }],
+            };
+            let (down, errs) =
+                download_images_sequential(&Client::new(), &imgs_dir, &dbf, &imgs, true).await;
+            assert_eq!((down, errs), (1, 0));
+            assert!(custom.join("IPS").join("7111.png").is_file());
+            assert!(!data_dir.join("images").join("IPS").exists());
+
+            let url =
+                crate::call_img::read_image_data_url(&imgs_dir, "IPS/7111.png", Vec::new).unwrap();
+            assert!(url.starts_with("data:image/png;base64,"));
+            let _ = fs::remove_dir_all(&base);
+        }
+
+        #[tokio::test]
+        async fn interrupted_image_download_leaves_no_file() {
+            // Promete 1000 bytes, manda 10 e fecha a conexão.
+            let (base_url, _hits) = crate::test_http::serve_raw(
+                b"HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nContent-Length: 1000\r\nConnection: close\r\n\r\n\x89PNG\r\n\x1a\nab".to_vec(),
+            );
+            let dir =
+                std::env::temp_dir().join(format!("catalogo_ips_part_{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            let imgs_dir = dir.join("images");
+            fs::create_dir_all(imgs_dir.join("IPS")).unwrap();
+            let dbf = dir.join("catalog.db");
+            migrate(&open_db(&dbf).unwrap()).unwrap();
+            // Sobra de um download anterior interrompido.
+            let stray = imgs_dir.join("IPS").join("7222.png.part");
+            fs::write(&stray, b"\x89PNG").unwrap();
+
+            let imgs = ManifestImages {
+                per_prefix_base: Vec::new(),
+                base_url,
+                files: vec![ManifestImageItem {
+                    file: "IPS/7111.png".into(),
+                    ..Default::default()
+                }],
+            };
+            let (down, errs) =
+                download_images_sequential(&Client::new(), &imgs_dir, &dbf, &imgs, true).await;
+            assert_eq!((down, errs), (0, 1));
+            assert!(!imgs_dir.join("IPS").join("7111.png").exists());
+            assert!(!imgs_dir.join("IPS").join("7111.png.part").exists());
+            assert!(!stray.exists());
+
+            // Falha ao gravar (destino é uma pasta): nada de arquivo final nem `.part`.
+            let dest = imgs_dir.join("IPS").join("7333.png");
+            fs::create_dir_all(dest.join("ocupado")).unwrap();
+            assert!(write_download_bytes(&dest, b"\x89PNG", None).is_err());
+            assert!(dest.is_dir());
+            assert!(!partial_download_path(&dest).exists());
+            assert!(write_download_bytes(&imgs_dir.join("x.png"), b"abc", Some("00")).is_err());
+            assert!(!imgs_dir.join("x.png").exists());
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        #[tokio::test]
+        async fn short_body_against_content_length_is_rejected_and_retried() {
+            use std::sync::atomic::Ordering;
+            let (base_url, hits) = crate::test_http::serve_raw(
+                b"HTTP/1.1 200 OK\r\nContent-Length: 64\r\nConnection: close\r\n\r\n0123456789"
+                    .to_vec(),
+            );
+            let dir = std::env::temp_dir().join(format!("catalogo_ips_len_{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            let dest = dir.join("7111.png");
+            let err = download_to_file(&Client::new(), &format!("{base_url}7111.png"), &dest, None)
+                .await
+                .unwrap_err();
+            assert!(is_truncated_download(&err), "{err}");
+            // reqwest acusa o corpo curto como erro de decode, então cada tentativa
+            // passa também pelo modo bruto.
+            assert_eq!(hits.load(Ordering::SeqCst), 2 * DOWNLOAD_ATTEMPTS);
+            assert!(!dest.exists());
+
+            assert_eq!(
+                check_content_length(Some(64), 10),
+                Err(SizeMismatch {
+                    expected: 64,
+                    received: 10
+                })
+            );
+            assert!(check_content_length(Some(10), 10).is_ok());
+            assert!(check_content_length(None, 10).is_ok());
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        #[test]
+        fn unresolved_resource_dir_is_named_in_seed_error() {
+            let err = seed_manifest_from(Err("unknown path".to_string())).unwrap_err();
+            assert_eq!(
+                err,
+                SeedManifestError::ResourceDirUnavailable("unknown path".to_string())
+            );
+            assert!(err.to_string().contains("pasta de recursos"));
+
+            let dir =
+                std::env::temp_dir().join(format!("catalogo_ips_seed_res_{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            let err = seed_manifest_from(Ok(dir.clone())).unwrap_err();
+            assert_eq!(err, SeedManifestError::Missing(dir.join("manifest.json")));
+
+            fs::write(dir.join("manifest.json"), "{ nope").unwrap();
+            let err = seed_manifest_from(Ok(dir.clone())).unwrap_err();
+            assert!(matches!(err, SeedManifestError::Invalid { .. }));
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        #[test]
+        fn preview_counts_new_and_cached_images() {
+            let dir =
+                std::env::temp_dir().join(format!("catalogo_ips_preview_{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            let imgs_dir = dir.join("images");
+            fs::create_dir_all(imgs_dir.join("IPS")).unwrap();
+            fs::write(imgs_dir.join("IPS/7111.png"), b"png").unwrap();
+            let conn = Connection::open_in_memory().unwrap();
+            migrate(&conn).unwrap();
+            set_db_version(&conn, 3).unwrap();
+            set_manifest_hash(&conn, "h1").unwrap();
+            conn.execute(
+                "INSERT INTO images_cache(filename, sha256) VALUES('IPS/7111.png', 'aa')",
+                [],
+            )
+            .unwrap();
+            let manifest = CatalogManifest {
+                db: ManifestDb {
+                    version: 4,
+                    url: "http://localhost/catalog.db".into(),
+                    sha256: None,
+                },
+                images: Some(ManifestImages {
+                    base_url: "http://localhost/".into(),
+                    files: vec![
+                        ManifestImageItem {
+                            file: "IPS/7111.png".into(),
+                            sha256: Some("aa".into()),
+                            ..Default::default()
+                        },
+                        ManifestImageItem {
+                            file: "IPS/7222.png".into(),
+                            sha256: Some("bb".into()),
+                            ..Default::default()
+                        },
+                    ],
+                    per_prefix_base: Vec::new(),
+                }),
+            };
+
+            let preview = preview_sync(Some(&conn), &manifest, "h2", &imgs_dir).unwrap();
+            assert_eq!(
+                preview,
+                SyncPreview {
+                    db_will_update: true,
+                    from_version: 3,
+                    to_version: 4,
+                    images_to_download: 1,
+                    images_up_to_date: 1,
+                    images_approximate: true,
+                }
+            );
+            set_db_version(&conn, 4).unwrap();
+            let same = preview_sync(Some(&conn), &manifest, "h2", &imgs_dir).unwrap();
+            assert!(!same.db_will_update && !same.images_approximate);
+
+            // Sem banco local tudo vem do manifest, inclusive as imagens já no disco.
+            let fresh = preview_sync(None, &manifest, "h2", &imgs_dir).unwrap();
+            assert_eq!((fresh.from_version, fresh.images_to_download), (0, 2));
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        #[test]
+        fn catalog_updated_fires_only_when_db_was_replaced() {
+            let mut result = SyncResult {
+                updated_db: false,
+                downloaded_images: 3,
+                db_version: 8,
+            };
+            assert_eq!(catalog_updated_payload(&result), None);
+            result.updated_db = true;
+            assert_eq!(
+                catalog_updated_payload(&result),
+                Some(json!({ "db_version": 8 }))
+            );
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn child_output_is_streamed_line_by_line() {
+            let mut cmd = PCommand::new("sh");
+            cmd.arg("-c")
+                .arg("echo listando; echo aviso >&2; echo pronto");
+            let mut events: Vec<(String, String)> = Vec::new();
+            let out = run_streaming(cmd, |stream, line| {
+                events.push((stream.to_string(), line.to_string()))
+            })
+            .unwrap();
+            assert!(out.status.success());
+            assert_eq!(out.stdout, "listando\npronto\n");
+            assert_eq!(out.stderr, "aviso\n");
+            let stdout_lines: Vec<&str> = events
+                .iter()
+                .filter(|(s, _)| s == "stdout")
+                .map(|(_, l)| l.as_str())
+                .collect();
+            assert_eq!(stdout_lines, vec!["listando", "pronto"]);
+            assert!(events.contains(&("stderr".into(), "aviso".into())));
+        }
+
+        #[test]
+        fn blank_r2_creds_are_rejected_by_name() {
+            let mut r2 = R2Creds {
+                account_id: " ".into(),
+                bucket: String::new(),
+                access_key_id: "AK".into(),
+                secret_access_key: "SK".into(),
+                endpoint: None,
+                public_base_url: None,
+            };
+            let none = |_: &str| None;
+            assert_eq!(
+                check_r2_creds(&r2, none).unwrap_err(),
+                "Credencial do R2 ausente: account_id (preencha na tela ou defina R2_ACCOUNT_ID)"
+            );
+            r2.account_id = "acc".into();
+            assert!(check_r2_creds(&r2, none).unwrap_err().contains("bucket"));
+            let env = |k: &str| (k == "R2_BUCKET").then(|| "catalogo".to_string());
+            assert!(check_r2_creds(&r2, env).is_ok());
+        }
+
+        #[test]
+        fn newer_manifest_reports_update_available() {
+            let manifest: CatalogManifest = serde_json::from_value(json!({
+                "db": { "version": 8, "url": "https://exemplo/catalog.db" },
+                "images": { "base_url": "https://exemplo/", "files": [
+                    { "file": "IPS/1.png" }, { "file": "IPS/2.png" }
+                ]}
+            }))
+            .unwrap();
+            let conn = Connection::open_in_memory().unwrap();
+            migrate(&conn).unwrap();
+            set_db_version(&conn, 7).unwrap();
+            let local = get_db_version(&conn).unwrap();
+            assert_eq!(
+                update_info(&manifest, Some(local)),
+                UpdateInfo {
+                    remote_version: 8,
+                    local_version: 7,
+                    update_available: true,
+                    image_count: 2,
+                }
+            );
+            assert!(!update_info(&manifest, Some(8)).update_available);
+            assert!(update_info(&manifest, None).update_available);
+        }
+
+        #[test]
+        fn atomic_write_never_exposes_partial_manifest() {
+            let dir =
+                std::env::temp_dir().join(format!("catalogo_ips_atomic_{}", std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            let path = dir.join("manifest.json");
+            let tmp = dir.join(".manifest.json.tmp");
+            write_file_atomic(&path, br#"{"db":{"version":1,"url":"a"}}"#).unwrap();
+            // Gravação interrompida: sobra só o temporário truncado, o manifest segue legível.
+            fs::write(&tmp, br#"{"db":{"vers"#).unwrap();
+            let kept: CatalogManifest = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+            assert_eq!(kept.db.version, 1);
+
+            write_file_atomic(&path, br#"{"db":{"version":2,"url":"b"}}"#).unwrap();
+            let fresh: CatalogManifest = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+            assert_eq!(fresh.db.version, 2);
+            assert!(!tmp.exists());
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        #[test]
+        fn image_prefixes_pick_their_own_base_url() {
+            let imgs: ManifestImages = serde_json::from_value(json!({
+                "base_url": "https://padrao.exemplo/imgs/",
+                "files": [],
+                "per_prefix_base": [
+                    { "prefix": "BOSCH/", "base_url": "https://bosch.exemplo/" },
+                    { "prefix": "BOSCH/LANCAMENTOS/", "base_url": "https://novos.exemplo/" }
+                ]
+            }))
+            .unwrap();
+            assert_eq!(
+                imgs.url_for("BOSCH/7111.jpg"),
+                "https://bosch.exemplo/BOSCH/7111.jpg"
+            );
+            assert_eq!(
+                imgs.url_for("IPS/7222.jpg"),
+                "https://padrao.exemplo/imgs/IPS/7222.jpg"
+            );
+            assert_eq!(
+                imgs.url_for("BOSCH/LANCAMENTOS/a.jpg"),
+                "https://novos.exemplo/BOSCH/LANCAMENTOS/a.jpg"
+            );
+            assert_eq!(
+                imgs.url_for("https://cdn.exemplo/x.jpg"),
+                "https://cdn.exemplo/x.jpg"
+            );
+        }
+
+        #[test]
+        fn system_info_reports_key_presence_only() {
+            let dir =
+                std::env::temp_dir().join(format!("catalogo_ips_sysinfo_{}", std::process::id()));
+            let imgs_dir = dir.join("images");
+            fs::create_dir_all(&imgs_dir).unwrap();
+            let dbf = dir.join("catalog.db");
+            {
+                let conn = open_db(&dbf).unwrap();
+                migrate(&conn).unwrap();
+                set_db_version(&conn, 12).unwrap();
+            }
+
+            let info = system_info(&dir, &dbf, &imgs_dir, Some("chave-super-secreta"));
+            assert_eq!(info.app_version, env!("CARGO_PKG_VERSION"));
+            assert_eq!(info.db_version, 12);
+            assert!(info.key_configured);
+            assert_eq!(info.db_path, dbf.to_string_lossy());
+            assert_eq!(info.last_import, None);
+            let json = serde_json::to_string(&info).unwrap();
+            assert!(!json.contains("chave-super-secreta"));
+
+            assert!(!system_info(&dir, &dbf, &imgs_dir, None).key_configured);
+            assert!(!system_info(&dir, &dbf, &imgs_dir, Some("  ")).key_configured);
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        #[test]
+        fn failed_migration_keeps_installed_db() {
+            let dir =
+                std::env::temp_dir().join(format!("catalogo_ips_install_{}", std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            let dest = dir.join("catalog.db");
+            let tmp = dest.with_extension("download.tmp");
+            {
+                let conn = open_db(&dest).unwrap();
+                migrate(&conn).unwrap();
+                conn.execute_batch(
+                    "INSERT INTO products(brand_id, code, description) VALUES (1, '7111', 'PASTILHA');",
+                )
+                .unwrap();
+                set_db_version(&conn, 3).unwrap();
+            }
+            {
+                // `brands` como view: passa na validação, mas o migrate não consegue gravar nela.
+                let conn = Connection::open(&tmp).unwrap();
+                conn.execute_batch(
+                    "CREATE TABLE products (id INTEGER PRIMARY KEY, code TEXT, padding BLOB);
+                     INSERT INTO products(code, padding) VALUES ('X', zeroblob(8192));
+                     CREATE VIEW brands AS SELECT 1 AS id, 'IPS' AS name;",
+                )
+                .unwrap();
+            }
+
+            assert!(install_downloaded_db(&tmp, &dest, &[]).is_err());
+            assert!(!tmp.exists());
+            assert!(catalog_db_is_usable(&dest));
+            let conn = open_db(&dest).unwrap();
+            assert_eq!(get_db_version(&conn).unwrap(), 3);
+            drop(conn);
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        #[test]
+        fn older_schema_db_is_migrated_on_install() {
+            let dir =
+                std::env::temp_dir().join(format!("catalogo_ips_schema_{}", std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            let dest = dir.join("catalog.db");
+            let tmp = dest.with_extension("download.tmp");
+            {
+                // Layout das primeiras versões: sem code_norm/active e sem schema_version.
+                let conn = Connection::open(&tmp).unwrap();
+                conn.execute_batch(
+                    "CREATE TABLE meta (key TEXT PRIMARY KEY, value TEXT);
+                     INSERT INTO meta(key, value) VALUES ('db_version', '9');
+                     CREATE TABLE brands (id INTEGER PRIMARY KEY, name TEXT NOT NULL UNIQUE);
+                     INSERT INTO brands(id, name) VALUES (1, 'IPS');
+                     CREATE TABLE products (id INTEGER PRIMARY KEY, brand_id INTEGER NOT NULL,
+                       code TEXT NOT NULL UNIQUE, description TEXT NOT NULL, padding BLOB);
+                     INSERT INTO products(brand_id, code, description, padding)
+                       VALUES (1, 'AB-7111', 'PASTILHA', zeroblob(8192));",
+                )
+                .unwrap();
+                assert_eq!(get_schema_version(&conn).unwrap(), 0);
+            }
+
+            install_downloaded_db(&tmp, &dest, &[]).unwrap();
+            let conn = open_db(&dest).unwrap();
+            assert_eq!(get_schema_version(&conn).unwrap(), SCHEMA_VERSION);
+            assert_eq!(get_db_version(&conn).unwrap(), 9);
+            let (code_norm, active): (String, i64) = conn
+                .query_row(
+                    "SELECT code_norm, COALESCE(active, 1) FROM products WHERE code = 'AB-7111'",
+                    [],
+                    |r| Ok((r.get(0)?, r.get(1)?)),
+                )
+                .unwrap();
+            assert_eq!((code_norm.as_str(), active), ("AB7111", 1));
+            assert!(!ensure_schema(&conn).unwrap());
+
+            // Esquema rebaixado (cópia antiga por cima) volta a migrar.
+            conn.execute(
+                "DELETE FROM meta WHERE key = ?1",
+                params![META_SCHEMA_VERSION_KEY],
+            )
+            .unwrap();
+            assert!(ensure_schema(&conn).unwrap());
+            assert_eq!(get_schema_version(&conn).unwrap(), SCHEMA_VERSION);
+            drop(conn);
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        #[test]
+        fn reset_keeps_unrelated_files_in_custom_images_dir() {
+            let dir = std::env::temp_dir()
+                .join(format!("catalogo_ips_reset_custom_{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            let data = dir.join("dados");
+            let shared = dir.join("fotos");
+            fs::create_dir_all(&data).unwrap();
+            crate::db::set_images_dir_override(&data, Some(&shared)).unwrap();
+            let (data_dir, dbf, imgs_dir) = crate::db::ensure_dirs_in(data).unwrap();
+            assert_eq!(imgs_dir, shared);
+            {
+                let conn = open_db(&dbf).unwrap();
+                migrate(&conn).unwrap();
+                conn.execute(
+                    "INSERT INTO images_cache(filename, sha256) VALUES ('IPS/7111.jpg', 'abc')",
+                    [],
+                )
+                .unwrap();
+            }
+            fs::create_dir_all(shared.join("IPS")).unwrap();
+            fs::write(shared.join("IPS").join("7111.jpg"), b"x").unwrap();
+            fs::write(shared.join("ferias.jpg"), b"foto").unwrap();
+
+            reset_data_dir(data_dir.clone()).unwrap();
+            assert!(!shared.join("IPS").join("7111.jpg").exists());
+            assert!(shared.join("ferias.jpg").exists());
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        #[test]
+        fn reset_leaves_empty_db_and_fresh_dirs() {
+            let dir =
+                std::env::temp_dir().join(format!("catalogo_ips_reset_{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            let (data_dir, dbf, imgs_dir) = crate::db::ensure_dirs_in(dir.clone()).unwrap();
+            {
+                let mut conn = open_db(&dbf).unwrap();
+                crate::importer::import_range(
+                    &mut conn,
+                    &crate::importer::tests::sheet(&[
+                        &["MARCA", "CODIGO", "DESCRICAO"],
+                        &["IPS", "7111", "PASTILHA"],
+                    ]),
+                )
+                .unwrap();
+                conn.execute(
+                    "INSERT INTO images_cache(filename, sha256) VALUES ('IPS/7111.jpg', 'abc')",
+                    [],
+                )
+                .unwrap();
+            }
+            fs::create_dir_all(imgs_dir.join("IPS")).unwrap();
+            fs::write(imgs_dir.join("IPS").join("7111.jpg"), b"x").unwrap();
+            fs::write(data_dir.join("manifest.json"), b"{}").unwrap();
+            fs::write(data_dir.join("descrypt.key"), b"chave").unwrap();
+
+            reset_data_dir(data_dir.clone()).unwrap();
+            assert!(imgs_dir.is_dir());
+            assert_eq!(fs::read_dir(&imgs_dir).unwrap().count(), 0);
+            assert!(!data_dir.join("manifest.json").exists());
+            assert!(data_dir.join("descrypt.key").exists());
+            let conn = open_db(&dbf).unwrap();
+            for table in ["products", "brands", "images_cache"] {
+                let n: i64 = conn
+                    .query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |r| r.get(0))
+                    .unwrap();
+                // migrate recria só a marca "sem marca".
+                let expected = if table == "brands" { 1 } else { 0 };
+                assert_eq!(n, expected, "{table}");
+            }
+            drop(conn);
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        #[test]
+        fn image_map_lists_links_and_products_without_images() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO"],
+                    &["IPS", "7111", "PASTILHA"],
+                    &["IPS", "7222", "DISCO"],
+                    &["IPS", "7333", "SAPATA"],
+                ]),
+            )
+            .unwrap();
+            conn.execute_batch(
+                "INSERT INTO images(product_id, filename)
+                   SELECT id, 'IPS/' || code || '.jpg' FROM products WHERE code IN ('7111', '7222');
+                 INSERT INTO images(product_id, filename)
+                   SELECT id, 'IPS/7111;b.jpg' FROM products WHERE code = '7111';",
+            )
+            .unwrap();
+            let mut out: Vec<u8> = Vec::new();
+            assert_eq!(write_image_map(&conn, &mut out).unwrap(), 3);
+            let text = String::from_utf8(out).unwrap();
+            assert_eq!(
+                text.lines().collect::<Vec<_>>(),
+                vec![
+                    "product_code;filename",
+                    "7111;IPS/7111.jpg",
+                    "7111;\"IPS/7111;b.jpg\"",
+                    "7222;IPS/7222.jpg",
+                    "",
+                    "product_code_sem_imagem",
+                    "7333",
+                ]
+            );
+        }
+
+        #[test]
+        fn encrypted_catalog_db_round_trips_through_install_and_export() {
+            let dir =
+                std::env::temp_dir().join(format!("catalogo_ips_encdb_{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            let plain = dir.join("origem.db");
+            {
+                let mut conn = open_db(&plain).unwrap();
+                crate::importer::import_range(
+                    &mut conn,
+                    &crate::importer::tests::sheet(&[
+                        &["MARCA", "CODIGO", "DESCRICAO"],
+                        &["IPS", "7111", "PASTILHA"],
+                    ]),
+                )
+                .unwrap();
+                conn.execute_batch("PRAGMA journal_mode=DELETE;").unwrap();
+            }
+            let sealed =
+                crate::desc::encrypt_image(&fs::read(&plain).unwrap(), "segredo", 1_000).unwrap();
+            let dest = dir.join("catalog.db");
+            let tmp = dest.with_extension("download.tmp");
+
+            fs::write(&tmp, &sealed).unwrap();
+            assert!(install_downloaded_db(&tmp, &dest, &[]).is_err());
+            assert!(!dest.exists());
+            fs::write(&tmp, &sealed).unwrap();
+            let keys = vec!["errada".to_string(), "segredo".to_string()];
+            install_downloaded_db(&tmp, &dest, &keys).unwrap();
+            let conn = open_db(&dest).unwrap();
+            let count: i64 = conn
+                .query_row("SELECT COUNT(*) FROM products", [], |r| r.get(0))
+                .unwrap();
+            assert_eq!(count, 1);
+
+            let exported = dir.join("export.db");
+            export_db_file(&conn, &exported, false, Some("segredo"), false).unwrap();
+            assert!(fs::read(&exported).unwrap().starts_with(b"CIMG"));
+            assert!(decrypt_catalog_db_file(&exported, &["segredo".to_string()]).unwrap());
+            assert!(catalog_db_is_usable(&exported));
+            // Banco em claro passa direto.
+            assert!(!decrypt_catalog_db_file(&exported, &[]).unwrap());
+            drop(conn);
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        #[test]
+        fn undecryptable_seed_is_removed_and_leftover_is_rejected() {
+            let dir =
+                std::env::temp_dir().join(format!("catalogo_ips_seed_cimg_{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            let db_file = dir.join("catalog.db");
+            let sealed =
+                crate::desc::encrypt_image(b"SQLite format 3\0", "segredo", 1_000).unwrap();
+
+            fs::write(&db_file, &sealed).unwrap();
+            assert!(db_file_is_encrypted(&db_file));
+            assert!(prepare_db_file(&db_file, true, &[]).is_err());
+            assert!(!db_file.exists());
+
+            fs::write(&db_file, &sealed).unwrap();
+            let err = prepare_db_file(&db_file, false, &["errada".to_string()]).unwrap_err();
+            assert!(err.to_string().contains("criptografado"), "{err}");
+            assert!(db_file.exists());
+            prepare_db_file(&db_file, false, &["segredo".to_string()]).unwrap();
+            assert!(!db_file_is_encrypted(&db_file));
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        #[test]
+        fn export_can_drop_launch_image_rows() {
+            let dir = std::env::temp_dir()
+                .join(format!("catalogo_ips_export_launch_{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO"],
+                    &["IPS", "7111", "PASTILHA"],
+                ]),
+            )
+            .unwrap();
+            for file in [
+                "IPS/7111.jpg",
+                "lancamentos/7111.jpg",
+                "2024\\Lançamentos\\7111_promo.jpg",
+            ] {
+                conn.execute(
+                    "INSERT INTO images(product_id, filename) SELECT id, ?1 FROM products WHERE code = '7111'",
+                    params![file],
+                )
+                .unwrap();
+            }
+            let files_in = |path: &Path| -> Vec<String> {
+                let c = open_db(path).unwrap();
+                let mut stmt = c
+                    .prepare("SELECT filename FROM images ORDER BY filename")
+                    .unwrap();
+                stmt.query_map([], |r| r.get(0))
+                    .unwrap()
+                    .collect::<rusqlite::Result<Vec<String>>>()
+                    .unwrap()
+            };
+
+            let full = dir.join("full.db");
+            export_db_file(&conn, &full, false, None, false).unwrap();
+            assert_eq!(files_in(&full).len(), 3);
+
+            let partner = dir.join("partner.db");
+            export_db_file(&conn, &partner, false, None, true).unwrap();
+            assert_eq!(files_in(&partner), vec!["IPS/7111.jpg"]);
+            // O banco de origem não muda.
+            let left: i64 = conn
+                .query_row("SELECT COUNT(*) FROM images", [], |r| r.get(0))
+                .unwrap();
+            assert_eq!(left, 3);
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        #[test]
+        fn export_rejects_missing_parent_and_existing_file() {
+            let dir =
+                std::env::temp_dir().join(format!("catalogo_ips_export_{}", std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            let conn = Connection::open_in_memory().unwrap();
+            migrate(&conn).unwrap();
+
+            let missing = dir.join("nao_existe").join("catalog.db");
+            let err = export_db_file(&conn, &missing, true, None, false).unwrap_err();
+            assert!(err.contains("Pasta de destino não existe"), "{err}");
+
+            let dest = dir.join("catalog.db");
+            fs::write(&dest, b"antigo").unwrap();
+            let err = export_db_file(&conn, &dest, false, None, false).unwrap_err();
+            assert!(err.contains("Arquivo já existe"), "{err}");
+            assert_eq!(fs::read(&dest).unwrap(), b"antigo");
+
+            export_db_file(&conn, &dest, true, None, false).unwrap();
+            assert!(fs::read(&dest).unwrap().starts_with(b"SQLite format 3\0"));
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        /// Conta os flushes para provar que as linhas saem durante a leitura.
+        #[derive(Default)]
+        struct FlushCounter {
+            bytes: Vec<u8>,
+            flushes: Vec<usize>,
+        }
+
+        impl std::io::Write for FlushCounter {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.bytes.extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                self.flushes.push(self.bytes.len());
+                Ok(())
+            }
+        }
+
+        #[test]
+        fn product_export_streams_rows() {
+            let conn = Connection::open_in_memory().unwrap();
+            migrate(&conn).unwrap();
+            conn.execute_batch(
+                "INSERT INTO brands(id, name) VALUES (10, 'IPS');
+                 WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 5000)
+                 INSERT INTO products(brand_id, code, description, stock)
+                 SELECT 10, printf('C%05d', i), 'PEÇA; \"ESPECIAL\"', i FROM n;",
+            )
+            .unwrap();
+
+            let mut csv = FlushCounter::default();
+            let count = write_products_export(&conn, &mut csv, ProductExportFormat::Csv).unwrap();
+            assert_eq!(count, 5000);
+            // Um flush a cada EXPORT_FLUSH_EVERY linhas, com o arquivo crescendo, e o final.
+            assert_eq!(csv.flushes.len(), 5000 / EXPORT_FLUSH_EVERY + 1);
+            assert!(csv.flushes.windows(2).all(|w| w[0] <= w[1]));
+            assert!(csv.flushes[0] < csv.bytes.len() / 4);
+            let text = String::from_utf8(csv.bytes).unwrap();
+            let mut lines = text.lines();
+            assert_eq!(lines.next().unwrap(), EXPORT_COLUMNS.join(";"));
+            assert_eq!(
+                lines.next().unwrap(),
+                "C00001;\"PEÇA; \"\"ESPECIAL\"\"\";IPS;;;;1;"
+            );
+
+            let mut js = FlushCounter::default();
+            write_products_export(&conn, &mut js, ProductExportFormat::Json).unwrap();
+            let parsed: Vec<serde_json::Value> = serde_json::from_slice(&js.bytes).unwrap();
+            assert_eq!(parsed.len(), 5000);
+            assert_eq!(parsed[4999]["code"], "C05000");
+            assert_eq!(parsed[0]["stock"], 1);
+        }
+
+        #[test]
+        fn failed_export_leaves_existing_destination() {
+            let dir = std::env::temp_dir()
+                .join(format!("catalogo_ips_export_fail_{}", std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            let dest = dir.join("catalog.db");
+            fs::write(&dest, b"exportacao anterior").unwrap();
+            let conn = Connection::open_in_memory().unwrap();
+            migrate(&conn).unwrap();
+            // VACUUM não roda dentro de transação: falha no meio da exportação.
+            conn.execute_batch("BEGIN").unwrap();
+
+            assert!(export_db_file(&conn, &dest, true, None, false).is_err());
+            assert_eq!(fs::read(&dest).unwrap(), b"exportacao anterior");
+            assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        #[test]
+        fn backup_export_copies_rows_and_can_be_cancelled() {
+            use std::sync::atomic::AtomicBool;
+            let dir =
+                std::env::temp_dir().join(format!("catalogo_ips_backup_{}", std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            let mut conn = Connection::open_in_memory().unwrap();
+            let mut rows: Vec<Vec<String>> = vec![vec![
+                "MARCA".into(),
+                "CODIGO".into(),
+                "DESCRICAO".into(),
+                "VEICULOS".into(),
+            ]];
+            for i in 0..300 {
+                rows.push(vec![
+                    "IPS".into(),
+                    format!("{i}"),
+                    format!("PASTILHA {}", "X".repeat(200)),
+                    format!("GOL G{i}"),
+                ]);
+            }
+            let rows: Vec<Vec<&str>> = rows
+                .iter()
+                .map(|r| r.iter().map(|c| c.as_str()).collect())
+                .collect();
+            let rows: Vec<&[&str]> = rows.iter().map(|r| r.as_slice()).collect();
+            crate::importer::import_range(&mut conn, &crate::importer::tests::sheet(&rows))
+                .unwrap();
+
+            let dest = dir.join("backup.db");
+            let mut progress = Vec::new();
+            backup_db_file(&conn, &dest, false, &AtomicBool::new(false), &mut |d, t| {
+                progress.push((d, t))
+            })
+            .unwrap();
+            let (done, total) = *progress.last().unwrap();
+            assert!(total > 0);
+            assert_eq!(done, total);
+            let copy = Connection::open(&dest).unwrap();
+            for table in ["products", "vehicles", "product_vehicles"] {
+                let count = |c: &Connection| -> i64 {
+                    c.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |r| r.get(0))
+                        .unwrap()
+                };
+                assert_eq!(count(&copy), count(&conn), "{table}");
+            }
+            drop(copy);
+
+            let cancelled = dir.join("cancelado.db");
+            assert!(backup_db_file(
+                &conn,
+                &cancelled,
+                false,
+                &AtomicBool::new(true),
+                &mut |_, _| {}
+            )
+            .is_err());
+            assert!(!cancelled.exists());
+            assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        #[test]
+        fn manifest_files_without_product_are_reported() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO"],
+                    &["IPS", "7111", "PASTILHA"],
+                ]),
+            )
+            .unwrap();
+            let files: Vec<ManifestImageItem> = ["IPS/7111.jpg", "IPS\\foto-sem-codigo.jpg"]
+                .iter()
+                .map(|f| ManifestImageItem {
+                    file: f.to_string(),
+                    ..Default::default()
+                })
+                .collect();
+            let result = index_from_file_list(&mut conn, &files, false).unwrap();
+            assert_eq!(
+                (result.scanned, result.matched, result.unmatched),
+                (2, 1, 1)
+            );
+            assert_eq!(result.unmatched_sample, vec!["IPS/foto-sem-codigo.jpg"]);
+        }
+
+        #[test]
+        fn preloaded_codes_match_like_per_file_queries() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO"],
+                    &["IPS", "7111", "PASTILHA"],
+                    &["IPS", "AB-20", "DISCO"],
+                    &["IPS", "0042", "SAPATA"],
+                    &["IPS", "X 9", "CABO"],
+                ]),
+            )
+            .unwrap();
+            let names = [
+                "IPS/7111.jpg",
+                "IPS/7111_2.jpg",
+                "IPS/ab-20.png",
+                "IPS/AB-20 (1).webp",
+                "0042.jpg",
+                "42.jpg",
+                "X 9.jpg",
+                "lancamentos/sem-produto.jpg",
+                "IPS/9999.jpg",
+            ];
+            let files: Vec<ManifestImageItem> = names
+                .iter()
+                .map(|f| ManifestImageItem {
+                    file: f.to_string(),
+                    ..Default::default()
+                })
+                .collect();
+
+            // Referência: o casamento antigo, uma consulta por candidato.
+            let mut expected: Vec<(String, i64)> = Vec::new();
+            for item in &files {
+                let rel = item.file.replace('\\', "/");
+                let last = rel.rsplit('/').next().unwrap_or(&rel);
+                let stem = last.split('.').next().unwrap_or(last);
+                let found = candidate_codes(stem).into_iter().find_map(|c| {
+                    conn.query_row("SELECT id FROM products WHERE code=?1", params![c], |r| {
+                        r.get::<_, i64>(0)
+                    })
+                    .ok()
+                });
+                if let Some(pid) = found {
+                    expected.push((rel, pid));
+                }
+            }
+            expected.sort();
+            assert!(
+                expected.len() >= 4,
+                "fixture precisa casar arquivos: {expected:?}"
+            );
+
+            let result = index_from_file_list(&mut conn, &files, false).unwrap();
+            let mut actual: Vec<(String, i64)> = conn
+                .prepare("SELECT filename, product_id FROM images")
+                .unwrap()
+                .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))
+                .unwrap()
+                .map(|r| r.unwrap())
+                .collect();
+            actual.sort();
+            assert_eq!(actual, expected);
+            assert_eq!(result.matched, expected.len());
+            assert_eq!(result.unmatched, files.len() - expected.len());
+        }
+
+        #[test]
+        fn file_matching_two_products_is_reported_as_ambiguous() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO"],
+                    &["IPS", "7111", "PASTILHA"],
+                    &["IPS", "71112", "DISCO"],
+                ]),
+            )
+            .unwrap();
+            let files = vec![
+                ManifestImageItem {
+                    file: "IPS/7111_2.jpg".into(),
+                    ..Default::default()
+                },
+                ManifestImageItem {
+                    file: "IPS/71112.jpg".into(),
+                    ..Default::default()
+                },
+            ];
+            let linked = |conn: &Connection| -> i64 {
+                conn.query_row(
+                    "SELECT COUNT(*) FROM images WHERE filename = 'IPS/7111_2.jpg'",
+                    [],
+                    |r| r.get(0),
+                )
+                .unwrap()
+            };
+
+            let result = index_from_file_list(&mut conn, &files, false).unwrap();
+            assert_eq!((result.matched, result.ambiguous), (1, 1));
+            assert_eq!(
+                result.ambiguous_sample,
+                vec![AmbiguousImage {
+                    file: "IPS/7111_2.jpg".into(),
+                    codes: vec!["7111".into(), "71112".into()],
+                }]
+            );
+            assert_eq!(linked(&conn), 0);
+
+            let result = index_from_file_list(&mut conn, &files, true).unwrap();
+            assert_eq!((result.matched, result.ambiguous), (2, 1));
+            assert_eq!(linked(&conn), 2);
+        }
+
+        #[test]
+        fn exact_stem_wins_over_prefix_product() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO"],
+                    &["IPS", "7111043002LE", "PASTILHA"],
+                    &["IPS", "7111043002", "DISCO"],
+                    &["IPS", "7111-2", "SAPATA"],
+                    &["IPS", "7111", "CABO"],
+                ]),
+            )
+            .unwrap();
+            let files: Vec<ManifestImageItem> = ["IPS/7111043002LE.jpg", "IPS/7111-2.jpg"]
+                .iter()
+                .map(|f| ManifestImageItem {
+                    file: f.to_string(),
+                    ..Default::default()
+                })
+                .collect();
+            let result = index_from_file_list(&mut conn, &files, false).unwrap();
+            assert_eq!((result.matched, result.ambiguous), (2, 0));
+            let links: Vec<(String, String)> = conn
+                .prepare(
+                    "SELECT p.code, i.filename FROM images i JOIN products p ON p.id = i.product_id ORDER BY p.code",
+                )
+                .unwrap()
+                .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))
+                .unwrap()
+                .map(|r| r.unwrap())
+                .collect();
+            assert_eq!(
+                links,
+                vec![
+                    ("7111-2".to_string(), "IPS/7111-2.jpg".to_string()),
+                    (
+                        "7111043002LE".to_string(),
+                        "IPS/7111043002LE.jpg".to_string()
+                    ),
+                ]
+            );
+
+            let dir = std::env::temp_dir()
+                .join(format!("catalogo_ips_exact_stem_{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("7111-2.jpg"), b"x").unwrap();
+            let allow = extension_allowlist(None, DEFAULT_IMAGE_EXTENSIONS);
+            let res =
+                index_images_in_dir(&mut conn, &dir, &allow, None, false, &mut |_, _| {}).unwrap();
+            let _ = fs::remove_dir_all(&dir);
+            assert_eq!((res.matched, res.ambiguous), (1, 0));
+        }
+
+        #[test]
+        fn manifest_code_links_oddly_named_file() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO"],
+                    &["IPS", "7111", "PASTILHA"],
+                    &["IPS", "7222", "DISCO"],
+                ]),
+            )
+            .unwrap();
+            let files = vec![
+                ManifestImageItem {
+                    file: "lancamentos/foto final (2).jpg".into(),
+                    code: Some(" 7111".into()),
+                    group: Some("LANCAMENTOS 2024".into()),
+                    ..Default::default()
+                },
+                // Código explícito inexistente cai no casamento pelo nome.
+                ManifestImageItem {
+                    file: "IPS/7222.jpg".into(),
+                    code: Some("9999".into()),
+                    ..Default::default()
+                },
+            ];
+            let result = index_from_file_list(&mut conn, &files, false).unwrap();
+            assert_eq!(result.matched, 2);
+            assert_eq!((result.unmatched, result.unmatched_sample.len()), (0, 0));
+            let links: Vec<(String, String)> = conn
+                .prepare(
+                    "SELECT p.code, i.filename FROM images i JOIN products p ON p.id = i.product_id ORDER BY p.code",
+                )
+                .unwrap()
+                .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))
+                .unwrap()
+                .map(|r| r.unwrap())
+                .collect();
+            assert_eq!(
+                links,
+                vec![
+                    (
+                        "7111".to_string(),
+                        "lancamentos/foto final (2).jpg".to_string()
+                    ),
+                    ("7222".to_string(), "IPS/7222.jpg".to_string()),
+                ]
+            );
+        }
+
+        #[test]
+        fn image_overrides_survive_reindex() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO"],
+                    &["IPS", "7111", "PASTILHA"],
+                    &["IPS", "7222", "DISCO"],
+                ]),
+            )
+            .unwrap();
+            set_image_override(&conn, "7111", "fotos\\foto_kit.jpg", "link").unwrap();
+            set_image_override(&conn, "7222", "IPS/7222.jpg", "exclude").unwrap();
+
+            let files: Vec<ManifestImageItem> = ["fotos/foto_kit.jpg", "IPS/7222.jpg"]
+                .into_iter()
+                .map(|f| ManifestImageItem {
+                    file: f.into(),
+                    ..Default::default()
+                })
+                .collect();
+            index_from_file_list(&mut conn, &files, false).unwrap();
+
+            let links: Vec<(String, String)> = conn
+                .prepare(
+                    "SELECT p.code, i.filename FROM images i JOIN products p ON p.id = i.product_id ORDER BY p.code",
+                )
+                .unwrap()
+                .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))
+                .unwrap()
+                .map(|r| r.unwrap())
+                .collect();
+            assert_eq!(
+                links,
+                vec![("7111".to_string(), "fotos/foto_kit.jpg".to_string())]
+            );
+        }
+
+        #[test]
+        fn index_images_honours_extension_allowlist() {
+            let dir =
+                std::env::temp_dir().join(format!("catalogo_ips_exts_{}", std::process::id()));
+            fs::create_dir_all(dir.join("IPS")).unwrap();
+            fs::write(dir.join("IPS").join("7111.tiff"), b"II*\0").unwrap();
+            fs::write(dir.join("IPS").join("7222.jpg"), b"\xFF\xD8\xFF").unwrap();
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO"],
+                    &["IPS", "7111", "PASTILHA"],
+                    &["IPS", "7222", "DISCO"],
+                ]),
+            )
+            .unwrap();
+
+            let default = extension_allowlist(None, DEFAULT_IMAGE_EXTENSIONS);
+            let res = index_images_in_dir(&mut conn, &dir, &default, None, false, &mut |_, _| {})
+                .unwrap();
+            assert_eq!((res.scanned, res.matched), (1, 1));
+
+            let custom = extension_allowlist(
+                Some(&[" .TIFF".to_string(), "tiff".to_string(), "jpg".to_string()]),
+                DEFAULT_IMAGE_EXTENSIONS,
+            );
+            assert_eq!(custom, vec!["tiff", "jpg"]);
+            let res =
+                index_images_in_dir(&mut conn, &dir, &custom, None, false, &mut |_, _| {}).unwrap();
+            assert_eq!((res.scanned, res.matched), (2, 2));
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        #[test]
+        fn batched_indexing_keeps_every_file() {
+            let dir =
+                std::env::temp_dir().join(format!("catalogo_ips_batch_{}", std::process::id()));
+            fs::create_dir_all(dir.join("IPS")).unwrap();
+            let mut rows: Vec<Vec<String>> =
+                vec![vec!["MARCA".into(), "CODIGO".into(), "DESCRICAO".into()]];
+            for i in 0..7 {
+                fs::write(dir.join("IPS").join(format!("71{i}.png")), b"x").unwrap();
+                rows.push(vec!["IPS".into(), format!("71{i}"), "PASTILHA".into()]);
+            }
+            let rows: Vec<Vec<&str>> = rows
+                .iter()
+                .map(|r| r.iter().map(|c| c.as_str()).collect())
+                .collect();
+            let rows: Vec<&[&str]> = rows.iter().map(|r| r.as_slice()).collect();
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(&mut conn, &crate::importer::tests::sheet(&rows))
+                .unwrap();
+
+            let allow = extension_allowlist(None, DEFAULT_IMAGE_EXTENSIONS);
+            let mut progress = Vec::new();
+            let res = index_images_in_dir(&mut conn, &dir, &allow, Some(3), false, &mut |s, m| {
+                progress.push(index_progress_payload(s, m))
+            })
+            .unwrap();
+            assert_eq!((res.scanned, res.matched, res.inserted), (7, 7, 7));
+            assert_eq!(
+                progress.last(),
+                Some(&json!({ "scanned": 7, "matched": 7 }))
+            );
+            assert!(conn.is_autocommit());
+            let stored: i64 = conn
+                .query_row("SELECT COUNT(*) FROM images", [], |r| r.get(0))
+                .unwrap();
+            assert_eq!(stored, 7);
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        #[test]
+        fn cleanup_matches_paths_ignoring_case_and_accents() {
+            let dir =
+                std::env::temp_dir().join(format!("catalogo_ips_cleanup_{}", std::process::id()));
+            // Disco em NFD e minúsculas; manifest em NFC e com caixa diferente.
+            let on_disk = dir.join("lanc\u{0327}amentos").join("freio.jpg");
+            fs::create_dir_all(on_disk.parent().unwrap()).unwrap();
+            fs::write(&on_disk, b"x").unwrap();
+            fs::write(dir.join("orfao.jpg"), b"x").unwrap();
+            let manifest_files: HashSet<String> = ["Lançamentos\\Freio.JPG"]
+                .iter()
+                .map(|f| normalize_rel_path(f))
+                .collect();
+
+            let res = cleanup_images_dir(&dir, &manifest_files, None);
+            assert_eq!((res.kept_files, res.removed_files), (1, 1));
+            assert!(on_disk.exists());
+            assert!(!dir.join("orfao.jpg").exists());
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        #[test]
+        fn sql_launch_filter_follows_configured_dirs() {
+            let conn = Connection::open_in_memory().unwrap();
+            register_launch_sql_fn(&conn, vec!["novidades".to_string()]).unwrap();
+            let check = |path: &str| -> bool {
+                conn.query_row("SELECT is_launch_path(?1)", params![path], |r| r.get(0))
+                    .unwrap()
+            };
+            assert!(check("Novidades/promo.jpg"));
+            assert!(check("IPS\\lançamentos\\7111.jpg"));
+            assert!(!check("IPS/7111.jpg"));
+            let null: bool = conn
+                .query_row("SELECT is_launch_path(NULL)", [], |r| r.get(0))
+                .unwrap();
+            assert!(!null);
+        }
+
+        #[test]
+        fn configured_launch_dir_is_listed_and_kept_by_cleanup() {
+            let root =
+                std::env::temp_dir().join(format!("catalogo_ips_novidades_{}", std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            let novidades = root.join("Novidades");
+            fs::create_dir_all(&novidades).unwrap();
+            fs::create_dir_all(root.join("IPS")).unwrap();
+            fs::write(novidades.join("promo.png"), b"x").unwrap();
+            fs::write(root.join("IPS").join("orfao.png"), b"x").unwrap();
+
+            let conn = Connection::open_in_memory().unwrap();
+            migrate(&conn).unwrap();
+            conn.execute(
+                "INSERT OR REPLACE INTO meta(key, value) VALUES (?1, ?2)",
+                params![META_LAUNCH_DIRS_KEY, " Novidades , launches"],
+            )
+            .unwrap();
+            // Os nomes extras valem para o processo todo: devolve o valor anterior ao sair,
+            // mesmo se o teste falhar, para não vazar para os outros testes.
+            struct RestoreTokens(Vec<String>);
+            impl Drop for RestoreTokens {
+                fn drop(&mut self) {
+                    if let Ok(mut extra) = EXTRA_LAUNCH_TOKENS.write() {
+                        *extra = std::mem::take(&mut self.0);
+                    }
+                }
+            }
+            let _restore = RestoreTokens(launch_tokens());
+            configure_launch_tokens(&conn);
+            if std::env::var(LAUNCH_DIRS_ENV).is_err() {
+                assert_eq!(
+                    *EXTRA_LAUNCH_TOKENS.read().unwrap(),
+                    vec!["novidades".to_string(), "launches".to_string()]
+                );
+            }
+            assert!(is_launch_component("lançamentos"));
+
+            let dirs = find_launch_dirs(&root);
+            assert_eq!(dirs, vec![novidades.clone()]);
+            let allow = extension_allowlist(None, DEFAULT_IMAGE_EXTENSIONS);
+            let images = collect_launch_images(&dirs, &allow);
+            assert_eq!(images.len(), 1);
+            assert!(images[0].path.ends_with("promo.png"));
+
+            let res = cleanup_images_dir(&root, &HashSet::new(), None);
+            assert_eq!((res.kept_files, res.removed_files), (1, 1));
+            assert!(novidades.join("promo.png").exists());
+            assert!(!root.join("IPS").join("orfao.png").exists());
+            let _ = fs::remove_dir_all(&root);
+        }
+
+        #[test]
+        fn cleanup_keeps_encrypted_twin_of_manifest_file() {
+            let dir =
+                std::env::temp_dir().join(format!("catalogo_ips_cimg_{}", std::process::id()));
+            fs::create_dir_all(dir.join("IPS")).unwrap();
+            fs::write(dir.join("IPS").join("x.jpg.cimg"), b"CIMG").unwrap();
+            fs::write(dir.join("IPS").join("y.jpg.cimg"), b"CIMG").unwrap();
+            let manifest_files: HashSet<String> =
+                [normalize_rel_path("IPS/x.jpg")].into_iter().collect();
+
+            let res = cleanup_images_dir(&dir, &manifest_files, None);
+            assert_eq!((res.kept_files, res.removed_files), (1, 1));
+            assert!(dir.join("IPS").join("x.jpg.cimg").exists());
+            assert!(!dir.join("IPS").join("y.jpg.cimg").exists());
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        #[test]
+        fn launch_images_carry_metadata_newest_first() {
+            let dir =
+                std::env::temp_dir().join(format!("catalogo_ips_launch_{}", std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            // BMP 2x3 mínimo: só o cabeçalho importa para a leitura de dimensões.
+            let mut bmp = Vec::new();
+            bmp.extend_from_slice(b"BM");
+            bmp.extend_from_slice(&(54u32 + 24).to_le_bytes());
+            bmp.extend_from_slice(&[0; 4]);
+            bmp.extend_from_slice(&54u32.to_le_bytes());
+            bmp.extend_from_slice(&40u32.to_le_bytes());
+            bmp.extend_from_slice(&2i32.to_le_bytes());
+            bmp.extend_from_slice(&3i32.to_le_bytes());
+            bmp.extend_from_slice(&1u16.to_le_bytes());
+            bmp.extend_from_slice(&24u16.to_le_bytes());
+            bmp.extend_from_slice(&[0; 24]);
+            bmp.extend_from_slice(&[0; 24]);
+            let old = dir.join("antigo.bmp");
+            let new = dir.join("novo.bmp");
+            fs::write(&old, &bmp).unwrap();
+            fs::write(&new, &bmp).unwrap();
+            let past = std::time::SystemTime::now() - Duration::from_secs(3600);
+            fs::File::options()
+                .write(true)
+                .open(&old)
+                .unwrap()
+                .set_modified(past)
+                .unwrap();
+
+            let allow = extension_allowlist(None, DEFAULT_IMAGE_EXTENSIONS);
+            let images = collect_launch_images(std::slice::from_ref(&dir), &allow);
+            assert_eq!(images.len(), 2);
+            assert!(images[0].path.ends_with("novo.bmp"));
+            assert!(images[0].modified_ms > images[1].modified_ms);
+            assert_eq!(images[0].size_bytes, bmp.len() as u64);
+            assert_eq!((images[0].width, images[0].height), (Some(2), Some(3)));
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        #[test]
+        fn only_old_launch_images_expire() {
+            let root =
+                std::env::temp_dir().join(format!("catalogo_ips_expire_{}", std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            let launches = root.join("lancamentos");
+            fs::create_dir_all(&launches).unwrap();
+            fs::create_dir_all(root.join("IPS")).unwrap();
+            let day = Duration::from_secs(24 * 60 * 60);
+            let now = std::time::SystemTime::now();
+            for (name, age_days) in [("antiga.png", 40), ("recente.png", 2)] {
+                let path = launches.join(name);
+                fs::write(&path, b"12345").unwrap();
+                fs::File::options()
+                    .write(true)
+                    .open(&path)
+                    .unwrap()
+                    .set_modified(now - day * age_days)
+                    .unwrap();
+            }
+            // Fora das pastas de lançamento nada é removido, por mais velho que seja.
+            let library = root.join("IPS").join("7111.png");
+            fs::write(&library, b"x").unwrap();
+            fs::File::options()
+                .write(true)
+                .open(&library)
+                .unwrap()
+                .set_modified(now - day * 400)
+                .unwrap();
+
+            let result = expire_launch_images(&find_launch_dirs(&root), day * 30, now);
+            assert_eq!(
+                result,
+                ExpireLaunchResult {
+                    removed_files: 1,
+                    kept_files: 1,
+                    removed_bytes: 5,
+                }
+            );
+            assert!(!launches.join("antiga.png").exists());
+            assert!(launches.join("recente.png").exists());
+            assert!(library.exists());
+            let _ = fs::remove_dir_all(&root);
+        }
+
+        #[test]
+        fn manifest_without_images_is_flagged_by_index_and_cleanup() {
+            let dir = std::env::temp_dir().join(format!(
+                "catalogo_ips_empty_manifest_{}",
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(dir.join("IPS")).unwrap();
+            fs::write(dir.join("IPS/7111.jpg"), b"img").unwrap();
+            let mut conn = Connection::open_in_memory().unwrap();
+            migrate(&conn).unwrap();
+            let manifest = CatalogManifest {
+                db: ManifestDb {
+                    version: 1,
+                    url: "http://localhost/catalog.db".into(),
+                    sha256: None,
+                },
+                images: None,
+            };
+
+            let idx = index_manifest_images(&mut conn, &manifest, false).unwrap();
+            assert!(idx.empty_manifest);
+            assert_eq!((idx.scanned, idx.matched), (0, 0));
+
+            let res = cleanup_from_manifest(&dir, &manifest, None);
+            assert!(res.empty_manifest);
+            assert_eq!(res.removed_files, 0);
+            assert!(dir.join("IPS/7111.jpg").exists());
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        #[test]
+        fn nested_launch_dirs_are_found_once() {
+            let root =
+                std::env::temp_dir().join(format!("catalogo_ips_nested_{}", std::process::id()));
+            let nested = root.join("2024").join("Lançamentos");
+            fs::create_dir_all(nested.join("lancamentos")).unwrap();
+            fs::create_dir_all(root.join("IPS")).unwrap();
+            fs::write(nested.join("a.png"), b"x").unwrap();
+            fs::write(nested.join("lancamentos").join("b.png"), b"x").unwrap();
+            fs::write(root.join("IPS").join("7111.png"), b"x").unwrap();
+
+            let dirs = find_launch_dirs(&root);
+            assert_eq!(dirs, vec![nested.clone()]);
+            let allow = extension_allowlist(None, DEFAULT_IMAGE_EXTENSIONS);
+            let mut names: Vec<String> = collect_launch_images(&dirs, &allow)
+                .into_iter()
+                .map(|img| img.path)
+                .collect();
+            names.sort();
+            assert_eq!(names.len(), 2);
+            assert!(names[0].ends_with("a.png"));
+
+            clear_launches_dir(&root).unwrap();
+            assert!(!nested.exists());
+            assert!(root.join("IPS").join("7111.png").exists());
+            let _ = fs::remove_dir_all(&root);
+        }
+
+        #[test]
+        fn placeholder_images_are_not_indexed() {
+            let dir = std::env::temp_dir()
+                .join(format!("catalogo_ips_placeholder_{}", std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("sem-foto.jpg"), b"SEM FOTO").unwrap();
+            fs::write(dir.join("7111.jpg"), b"SEM FOTO").unwrap();
+            fs::write(dir.join("7222.jpg"), b"foto real").unwrap();
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO"],
+                    &["IPS", "7111", "PASTILHA"],
+                    &["IPS", "7222", "DISCO"],
+                ]),
+            )
+            .unwrap();
+            register_placeholder(&conn, &dir.join("sem-foto.jpg")).unwrap();
+
+            let allow = extension_allowlist(None, DEFAULT_IMAGE_EXTENSIONS);
+            let res =
+                index_images_in_dir(&mut conn, &dir, &allow, None, false, &mut |_, _| {}).unwrap();
+            assert_eq!((res.matched, res.skipped_placeholders), (1, 1));
+            let files: Vec<String> = conn
+                .prepare("SELECT filename FROM images")
+                .unwrap()
+                .query_map([], |r| r.get(0))
+                .unwrap()
+                .map(|r| r.unwrap())
+                .collect();
+            assert_eq!(files, vec!["7222.jpg"]);
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        #[test]
+        fn reveal_dir_requires_existing_path() {
+            let dir =
+                std::env::temp_dir().join(format!("catalogo_ips_reveal_{}", std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            let file = dir.join("7111.png");
+            fs::write(&file, b"x").unwrap();
+
+            assert_eq!(reveal_dir(&file).unwrap(), dir.as_path());
+            assert_eq!(reveal_dir(&dir).unwrap(), dir.as_path());
+            assert!(reveal_dir(&dir.join("nao_existe.png")).is_err());
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        #[test]
+        fn seed_copy_reports_whether_bundle_was_used() {
+            let dir =
+                std::env::temp_dir().join(format!("catalogo_ips_seed_{}", std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            let bundled = dir.join("bundle.db");
+            fs::write(&bundled, b"seed").unwrap();
+            let db_file = dir.join("data").join("catalog.db");
+
+            let missing = vec![dir.join("nao_existe.db")];
+            assert_eq!(copy_first_seed(&missing, &db_file).unwrap(), None);
+            assert!(!db_file.exists());
+
+            let candidates = vec![dir.join("nao_existe.db"), bundled.clone()];
+            assert_eq!(
+                copy_first_seed(&candidates, &db_file).unwrap(),
+                Some(bundled)
+            );
+            assert_eq!(fs::read(&db_file).unwrap(), b"seed");
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        #[test]
+        fn repeated_migrate_keeps_make_counts_stable() {
+            let conn = Connection::open_in_memory().unwrap();
+            migrate(&conn).unwrap();
+            conn.execute_batch(
+                "INSERT INTO vehicles(name) VALUES ('GOL 1.0'), ('UNO MILLE'), ('GOL G5');",
+            )
+            .unwrap();
+            let counts = |conn: &Connection| -> (i64, i64) {
+                conn.query_row(
+                    "SELECT (SELECT COUNT(*) FROM makes), (SELECT COUNT(*) FROM vehicle_makes)",
+                    [],
+                    |r| Ok((r.get(0)?, r.get(1)?)),
+                )
+                .unwrap()
+            };
+
+            // init_app só chama migrate(); rodar de novo não pode duplicar nada.
+            migrate(&conn).unwrap();
+            let first = counts(&conn);
+            migrate(&conn).unwrap();
+            assert_eq!(first, (2, 3));
+            assert_eq!(counts(&conn), first);
+        }
+
+        #[test]
+        fn reseed_picks_up_manual_group_edits() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO", "GRUPO"],
+                    &["IPS", "1", "A", "Freio"],
+                    &["IPS", "2", "B", "Motor"],
+                ]),
+            )
+            .unwrap();
+            conn.execute(
+                "UPDATE products SET pgroup = 'Embreagem' WHERE code = '2'",
+                [],
+            )
+            .unwrap();
+            assert_eq!(reseed_groups(&conn).unwrap(), 2);
+            assert_eq!(
+                list_groups(&conn, None, false, 0).unwrap(),
+                GroupsResult::Names(vec!["EMBREAGEM".into(), "FREIO".into()])
+            );
+        }
+
+        #[test]
+        fn vehicle_query_has_default_page_size() {
+            let conn = Connection::open_in_memory().unwrap();
+            migrate(&conn).unwrap();
+            for i in 0..(VEHICLE_QUERY_DEFAULT_LIMIT + 5) {
+                conn.execute(
+                    "INSERT INTO vehicles(name) VALUES (?1)",
+                    params![format!("GOL {i:03}")],
+                )
+                .unwrap();
+            }
+            let found = list_vehicles(&conn, Some("gol"), None, None).unwrap();
+            assert_eq!(found.len() as i64, VEHICLE_QUERY_DEFAULT_LIMIT);
+            let all = list_vehicles(&conn, None, None, None).unwrap();
+            assert_eq!(all.len() as i64, VEHICLE_QUERY_DEFAULT_LIMIT + 5);
+        }
+
+        #[test]
+        fn vehicle_query_narrows_and_pages() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO", "VEICULOS"],
+                    &["IPS", "1", "A", "GOL G5; GOL G6; PALIO"],
+                    &["IPS", "2", "B", "GOL G4; SAVEIRO"],
+                ]),
+            )
+            .unwrap();
+            let names = |query: Option<&str>, limit, offset| -> Vec<String> {
+                list_vehicles(&conn, query, limit, offset)
+                    .unwrap()
+                    .into_iter()
+                    .map(|v| v.name)
+                    .collect()
+            };
+            assert_eq!(names(None, None, None).len(), 5);
+            assert_eq!(
+                names(Some(" gol "), None, None),
+                vec!["GOL G4", "GOL G5", "GOL G6"]
+            );
+            assert_eq!(names(Some("gól"), Some(2), None), vec!["GOL G4", "GOL G5"]);
+            assert_eq!(names(Some("gol"), Some(2), Some(2)), vec!["GOL G6"]);
+            assert_eq!(names(None, Some(1), Some(4)), vec!["SAVEIRO"]);
+            assert!(names(Some("100%"), None, None).is_empty());
+        }
+
+        #[test]
+        fn rebuild_makes_follows_renamed_vehicle_make() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO", "VEICULOS", "MONTADORA"],
+                    &["IPS", "1", "A", "GOL", "VW/FIAT"],
+                    &["IPS", "2", "B", "UNO", "FIAT"],
+                ]),
+            )
+            .unwrap();
+            conn.execute(
+                "UPDATE vehicles SET make = 'VOLKSWAGEN' WHERE name = 'GOL'",
+                [],
+            )
+            .unwrap();
+            assert_eq!(rebuild_makes(&conn).unwrap(), 2);
+            assert_eq!(list_makes(&conn).unwrap(), vec!["FIAT", "VOLKSWAGEN"]);
+            let gol_makes: Vec<String> = conn
+                .prepare(
+                    "SELECT m.name FROM vehicle_makes vm JOIN makes m ON m.id = vm.make_id
+                     JOIN vehicles v ON v.id = vm.vehicle_id WHERE v.name = 'GOL' ORDER BY m.name",
+                )
+                .unwrap()
+                .query_map([], |r| r.get(0))
+                .unwrap()
+                .collect::<rusqlite::Result<_>>()
+                .unwrap();
+            assert_eq!(gol_makes, vec!["FIAT", "VOLKSWAGEN"]);
+        }
+
+        #[test]
+        fn repair_orphans_removes_dangling_links() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO", "VEICULOS", "MONTADORA"],
+                    &["IPS", "1", "A", "GOL", "VW"],
+                ]),
+            )
+            .unwrap();
+            let counts = |conn: &Connection| -> (i64, i64, i64) {
+                let n = |t: &str| -> i64 {
+                    conn.query_row(&format!("SELECT COUNT(*) FROM {t}"), [], |r| r.get(0))
+                        .unwrap()
+                };
+                (n("product_vehicles"), n("images"), n("vehicle_makes"))
+            };
+            let before = counts(&conn);
+            // Bancos antigos foram gravados sem foreign keys; simula as sobras deles.
+            conn.execute_batch(
+                "PRAGMA foreign_keys=OFF;
+                 INSERT INTO product_vehicles(product_id, vehicle_id) VALUES (999, 1);
+                 INSERT INTO images(product_id, filename) VALUES (999, 'x.jpg');
+                 INSERT INTO vehicle_makes(vehicle_id, make_id) VALUES (1, 999);
+                 PRAGMA foreign_keys=ON;",
+            )
+            .unwrap();
+            let fixed = repair_orphans(&conn).unwrap();
+            assert_eq!(
+                fixed,
+                RepairOrphansResult {
+                    product_vehicles: 1,
+                    images: 1,
+                    vehicle_makes: 1,
+                }
+            );
+            assert_eq!(counts(&conn), before);
+            assert_eq!(
+                repair_orphans(&conn).unwrap(),
+                RepairOrphansResult::default()
+            );
+        }
+
+        #[test]
+        fn export_cancel_belongs_to_the_running_export() {
+            use std::sync::atomic::Ordering;
+            let state = ExportState::default();
+            assert!(!state.cancel());
+            let (guard, cancel) = state.try_begin().unwrap();
+            assert!(state.try_begin().is_err());
+            assert!(state.cancel());
+            assert!(cancel.load(Ordering::SeqCst));
+            drop(guard);
+
+            // Um cancelamento antigo não interrompe a próxima exportação.
+            let (_guard, next) = state.try_begin().unwrap();
+            assert!(!next.load(Ordering::SeqCst));
+        }
+
+        #[test]
+        fn second_sync_is_rejected_while_first_runs() {
+            use std::sync::{mpsc, Arc};
+            let state = Arc::new(SyncState::default());
+            let (started_tx, started_rx) = mpsc::channel();
+            let (finish_tx, finish_rx) = mpsc::channel::<()>();
+            let first = {
+                let state = Arc::clone(&state);
+                std::thread::spawn(move || {
+                    let _guard = state.try_begin().unwrap();
+                    started_tx.send(()).unwrap();
+                    finish_rx.recv().unwrap();
+                })
+            };
+            started_rx.recv().unwrap();
+            let second = {
+                let state = Arc::clone(&state);
+                std::thread::spawn(move || state.try_begin().map(|_| ()))
+            };
+            assert!(second.join().unwrap().is_err());
+            finish_tx.send(()).unwrap();
+            first.join().unwrap();
+
+            // Guard liberado também quando a sincronização termina com erro.
+            let failing = || -> Result<(), String> {
+                let _guard = state.try_begin()?;
+                Err("falha de rede".into())
+            };
+            assert_eq!(failing(), Err("falha de rede".to_string()));
+            assert!(state.try_begin().is_ok());
+        }
+
+        #[test]
+        fn explain_reports_query_plan_when_requested() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO"],
+                    &["IPS", "1", "PASTILHA"],
+                ]),
+            )
+            .unwrap();
+            let mut params = SearchParams {
+                code_query: Some("past".into()),
+                limit: Some(10),
+                ..Default::default()
+            };
+            let (items, explain) = search_products_explained(&conn, &params).unwrap();
+            assert_eq!(items.len(), 1);
+            assert!(explain.is_none());
+
+            params.explain = Some(true);
+            let (items, explain) = search_products_explained(&conn, &params).unwrap();
+            assert_eq!(items.len(), 1);
+            let explain = explain.unwrap();
+            assert!(!explain.plan.is_empty());
+            assert!(explain.plan.iter().all(|line| !line.trim().is_empty()));
+            assert!(explain.elapsed_ms >= 0.0);
+        }
+
+        #[test]
+        fn migrate_creates_search_indexes() {
+            let conn = Connection::open_in_memory().unwrap();
+            migrate(&conn).unwrap();
+            migrate(&conn).unwrap();
+            let indexes: HashSet<String> = conn
+                .prepare("SELECT name FROM sqlite_master WHERE type = 'index'")
+                .unwrap()
+                .query_map([], |r| r.get(0))
+                .unwrap()
+                .collect::<rusqlite::Result<_>>()
+                .unwrap();
+            for name in [
+                "idx_products_brand",
+                "idx_products_pgroup",
+                "idx_product_vehicles_vehicle",
+                "idx_product_vehicles_product",
+                "idx_images_product",
+                "idx_vehicles_make",
+            ] {
+                assert!(indexes.contains(name), "índice ausente: {name}");
+            }
+        }
+
+        #[test]
+        fn image_filename_query_finds_owning_product() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO"],
+                    &["IPS", "7111", "PASTILHA"],
+                    &["IPS", "7222", "DISCO"],
+                ]),
+            )
+            .unwrap();
+            for (code, file) in [("7111", "IPS/7111_2.jpg"), ("7222", "IPS/7222x2.jpg")] {
+                conn.execute(
+                    "INSERT INTO images(product_id, filename) SELECT id, ?2 FROM products WHERE code = ?1",
+                    params![code, file],
+                )
+                .unwrap();
+            }
+            let codes = |q: &str| -> Vec<String> {
+                let params = SearchParams {
+                    image_filename_query: Some(q.into()),
+                    ..Default::default()
+                };
+                search_products(&conn, &params)
+                    .unwrap()
+                    .into_iter()
+                    .map(|p| p.code)
+                    .collect()
+            };
+            assert_eq!(codes("7111_2"), vec!["7111"]);
+            assert_eq!(codes("ips\\7222"), vec!["7222"]);
+            // "_" não vale como curinga: "7222x2" não casa com "7222_2".
+            assert!(codes("7222_2").is_empty());
+            assert_eq!(codes("  ").len(), 2);
+        }
+
+        #[test]
+        fn cursor_batches_add_up_to_full_result() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO"],
+                    &["IPS", "5", "E"],
+                    &["IPS", "1", "A"],
+                    &["IPS", "4", "D"],
+                    &["IPS", "2", "B"],
+                    &["IPS", "3", "C"],
+                ]),
+            )
+            .unwrap();
+            let params = SearchParams {
+                sort: Some("code".into()),
+                ..Default::default()
+            };
+            let codes = |items: Vec<ProductListItem>| -> Vec<String> {
+                items.into_iter().map(|p| p.code).collect()
+            };
+            let full = codes(search_products(&conn, &params).unwrap());
+
+            let cursors = SearchCursors::default();
+            let id = cursors.open(&conn, &params).unwrap();
+            let mut streamed = codes(cursors.fetch(&conn, id, 3).unwrap());
+            assert_eq!(streamed.len(), 3);
+            streamed.extend(codes(cursors.fetch(&conn, id, 3).unwrap()));
+            assert_eq!(streamed, full);
+            assert!(cursors.fetch(&conn, id, 3).unwrap().is_empty());
+
+            assert!(cursors.close(id));
+            assert!(!cursors.close(id));
+            assert!(cursors.fetch(&conn, id, 3).is_err());
+        }
+
+        #[test]
+        fn listed_codes_are_sorted_and_paged() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO", "GRUPO"],
+                    &["IPS", "C3", "PASTILHA", "FREIO"],
+                    &["IPS", "A1", "DISCO", "FREIO"],
+                    &["IPS", "B2", "SAPATA", "FREIO"],
+                    &["IPS", "Z9", "AMORTECEDOR", "SUSPENSAO"],
+                ]),
+            )
+            .unwrap();
+            let params = SearchParams {
+                group: Some("freio".into()),
+                ..Default::default()
+            };
+            assert_eq!(
+                list_codes(&conn, &params, None, None).unwrap(),
+                vec!["A1", "B2", "C3"]
+            );
+            assert_eq!(
+                list_codes(&conn, &params, Some(2), None).unwrap(),
+                vec!["A1", "B2"]
+            );
+            assert_eq!(
+                list_codes(&conn, &params, Some(2), Some(2)).unwrap(),
+                vec!["C3"]
+            );
+        }
+
+        #[test]
+        fn search_limit_defaults_and_is_clamped() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO"],
+                    &["IPS", "1", "A"],
+                    &["IPS", "2", "B"],
+                    &["IPS", "3", "C"],
+                    &["IPS", "4", "D"],
+                ]),
+            )
+            .unwrap();
+            let page = |limit: Option<i64>| {
+                search_page(
+                    &conn,
+                    &SearchParams {
+                        limit,
+                        ..Default::default()
+                    },
+                    3,
+                )
+                .unwrap()
+                .0
+            };
+
+            let unlimited = page(None);
+            assert_eq!(unlimited.items.len(), 3);
+            assert_eq!((unlimited.limit, unlimited.max_limit), (3, 3));
+            assert!(unlimited.limit_clamped);
+            assert!(page(Some(50)).limit_clamped);
+            let small = page(Some(2));
+            assert_eq!((small.items.len(), small.limit), (2, 2));
+            assert!(!small.limit_clamped);
+
+            assert_eq!(search_max_limit(None), SEARCH_MAX_LIMIT);
+            assert_eq!(search_max_limit(Some("500".into())), 500);
+            assert_eq!(search_max_limit(Some("0".into())), SEARCH_MAX_LIMIT);
+        }
+
+        #[test]
+        fn fuzzy_fallback_suggests_close_codes() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO"],
+                    &["IPS", "7111", "PASTILHA"],
+                    &["IPS", "7311", "DISCO"],
+                    &["IPS", "7999", "SAPATA"],
+                    &["IPS", "8112", "CUBO"],
+                ]),
+            )
+            .unwrap();
+            let mut params = SearchParams {
+                code_query: Some("7112".into()),
+                search_fields: Some(vec!["code".into()]),
+                ..Default::default()
+            };
+            let (page, _) = search_page(&conn, &params, 50).unwrap();
+            assert!(page.items.is_empty());
+            assert!(!page.suggestions);
+
+            params.fuzzy_fallback = Some(true);
+            let (page, _) = search_page(&conn, &params, 50).unwrap();
+            assert!(page.suggestions);
+            let codes: Vec<String> = page.items.into_iter().map(|p| p.code).collect();
+            // "8112" também está a 1 edição, mas começa por outro caractere.
+            assert_eq!(codes, vec!["7111"]);
+            assert_eq!(levenshtein("7112", "7311"), 2);
+
+            // Passou da última página: fim dos resultados, não sugestões.
+            params.offset = Some(50);
+            let (page, _) = search_page(&conn, &params, 50).unwrap();
+            assert!(page.items.is_empty());
+            assert!(!page.suggestions);
+            params.offset = None;
+
+            params.code_query = Some("7111".into());
+            let (page, _) = search_page(&conn, &params, 50).unwrap();
+            assert!(!page.suggestions);
+            assert_eq!(page.items.len(), 1);
+        }
+
+        #[test]
+        fn code_prefix_search_uses_code_norm_index() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO"],
+                    &["IPS", "AB-7111", "PASTILHA"],
+                    &["IPS", "ab 7112", "DISCO"],
+                    &["IPS", "XAB7111", "SAPATA"],
+                ]),
+            )
+            .unwrap();
+            // Linha gravada por fora do importador ganha code_norm na próxima migração.
+            conn.execute(
+                "INSERT INTO products(brand_id, code, description) VALUES (1, 'AB.80', 'CUBO')",
+                [],
+            )
+            .unwrap();
+            migrate(&conn).unwrap();
+
+            let mut params = SearchParams {
+                code_prefix: Some("ab71".into()),
+                sort: Some("code".into()),
+                limit: Some(10),
+                explain: Some(true),
+                ..Default::default()
+            };
+            let (items, explain) = search_products_explained(&conn, &params).unwrap();
+            let codes: Vec<String> = items.into_iter().map(|p| p.code).collect();
+            assert_eq!(codes, vec!["AB 7112", "AB-7111"]);
+            let plan = explain.unwrap().plan.join("\n");
+            assert!(plan.contains("idx_products_code_norm"), "{plan}");
+
+            params.code_prefix = Some("ab.8".into());
+            let (items, _) = search_products_explained(&conn, &params).unwrap();
+            assert_eq!(items.len(), 1);
+            assert_eq!(items[0].code, "AB.80");
+        }
+
+        #[test]
+        fn clearing_image_cache_forces_redownload() {
+            let dir =
+                std::env::temp_dir().join(format!("catalogo_ips_img_cache_{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(dir.join("IPS")).unwrap();
+            let file = dir.join("IPS").join("7111.jpg");
+            std::fs::write(&file, b"jpeg").unwrap();
+            let item = ManifestImageItem {
+                file: "IPS/7111.jpg".into(),
+                sha256: Some(sha256_file(&file).unwrap()),
+                ..Default::default()
+            };
+            let conn = Connection::open_in_memory().unwrap();
+            migrate(&conn).unwrap();
+            assert!(image_needs_download(&conn, &file, &item, false));
+
+            assert_eq!(rebuild_image_cache(&conn, &dir).unwrap(), 1);
+            assert!(!image_needs_download(&conn, &file, &item, false));
+
+            // Arquivo trocado fora da sync: o rebuild grava o hash real e a sync rebaixa.
+            std::fs::write(&file, b"editado").unwrap();
+            rebuild_image_cache(&conn, &dir).unwrap();
+            assert!(image_needs_download(&conn, &file, &item, false));
+
+            std::fs::write(&file, b"jpeg").unwrap();
+            rebuild_image_cache(&conn, &dir).unwrap();
+            assert_eq!(conn.execute("DELETE FROM images_cache", []).unwrap(), 1);
+            assert!(image_needs_download(&conn, &file, &item, false));
+            let _ = std::fs::remove_dir_all(&dir);
+        }
+
+        #[test]
+        fn products_by_vehicle_pages_direct_links() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO", "VEICULOS"],
+                    &["IPS", "1", "A PASTILHA", "GOL"],
+                    &["IPS", "2", "B DISCO", "GOL, UNO"],
+                    &["IPS", "3", "C CUBO", "GOL"],
+                    &["IPS", "4", "D SAPATA", "GOL G5"],
+                ]),
+            )
+            .unwrap();
+            let gol: i64 = conn
+                .query_row("SELECT id FROM vehicles WHERE name = 'GOL'", [], |r| {
+                    r.get(0)
+                })
+                .unwrap();
+            let codes = |limit, offset| -> (Vec<String>, i64) {
+                let page = products_by_vehicle(&conn, gol, limit, offset).unwrap();
+                (page.items.into_iter().map(|p| p.code).collect(), page.total)
+            };
+            // "GOL G5" não entra: sem casamento por nome.
+            assert_eq!(
+                codes(None, None),
+                (vec!["1".into(), "2".into(), "3".into()], 3)
+            );
+            assert_eq!(codes(Some(2), None), (vec!["1".into(), "2".into()], 3));
+            assert_eq!(codes(Some(2), Some(2)), (vec!["3".into()], 3));
+            assert_eq!(codes(Some(2), Some(4)), (vec![], 3));
+        }
+
+        #[test]
+        fn busy_reads_retry_until_lock_is_released() {
+            let dir =
+                std::env::temp_dir().join(format!("catalogo_ips_busy_{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            let dbf = dir.join("catalog.db");
+            let writer = open_db(&dbf).unwrap();
+            migrate(&writer).unwrap();
+            writer
+                .execute("INSERT INTO makes(name) VALUES ('FIAT')", [])
+                .unwrap();
+            let reader = Connection::open(&dbf).unwrap();
+            reader.busy_timeout(Duration::from_millis(0)).unwrap();
+            // Sem WAL para a leitura também esbarrar no lock exclusivo.
+            writer.execute_batch("PRAGMA journal_mode=DELETE").unwrap();
+
+            writer.execute_batch("BEGIN EXCLUSIVE").unwrap();
+            let still_locked =
+                retry_busy_with(2, Duration::from_millis(10), || list_makes(&reader));
+            assert_eq!(still_locked, Err(DB_BUSY_ERROR.to_string()));
+
+            let (locked_tx, locked_rx) = std::sync::mpsc::channel();
+            let releaser = std::thread::spawn(move || {
+                locked_rx.recv().unwrap();
+                std::thread::sleep(Duration::from_millis(100));
+                writer.execute_batch("COMMIT").unwrap();
+            });
+            locked_tx.send(()).unwrap();
+            let mut attempts = 0;
+            let makes = retry_busy_with(50, Duration::from_millis(20), || {
+                attempts += 1;
+                list_makes(&reader)
+            })
+            .unwrap();
+            releaser.join().unwrap();
+            assert_eq!(makes, vec!["FIAT"]);
+            assert!(attempts > 1);
+            drop(reader);
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        #[test]
+        fn product_details_list_linked_vehicles() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO", "VEICULOS"],
+                    &["IPS", "7111", "PASTILHA", "UNO, GOL"],
+                    &["IPS", "7222", "DISCO", "PALIO"],
+                ]),
+            )
+            .unwrap();
+            let id: i64 = conn
+                .query_row("SELECT id FROM products WHERE code = '7111'", [], |r| {
+                    r.get(0)
+                })
+                .unwrap();
+            let details = product_details(&conn, id).unwrap();
+            let names: Vec<&str> = details.vehicles.iter().map(|v| v.name.as_str()).collect();
+            assert_eq!(names, vec!["GOL", "UNO"]);
+        }
+
+        #[test]
+        fn items_and_details_carry_brand_id() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO", "VEICULOS"],
+                    &["IPS", "7111", "PASTILHA", "GOL"],
+                    &["BOSCH", "B1", "VELA", "GOL"],
+                ]),
+            )
+            .unwrap();
+            let stored: HashMap<String, i64> = conn
+                .prepare("SELECT code, brand_id FROM products")
+                .unwrap()
+                .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))
+                .unwrap()
+                .collect::<rusqlite::Result<_>>()
+                .unwrap();
+            let items = search_products(&conn, &SearchParams::default()).unwrap();
+            assert_eq!(items.len(), 2);
+            for item in &items {
+                assert_eq!(item.brand_id, stored[&item.code]);
+                assert_eq!(
+                    product_details(&conn, item.id).unwrap().brand_id,
+                    item.brand_id
+                );
+            }
+            let gol: i64 = conn
+                .query_row("SELECT id FROM vehicles WHERE name = 'GOL'", [], |r| {
+                    r.get(0)
+                })
+                .unwrap();
+            for item in products_by_vehicle(&conn, gol, None, None).unwrap().items {
+                assert_eq!(item.brand_id, stored[&item.code]);
+            }
+        }
+
+        #[test]
+        fn aggregated_vehicles_are_sorted_and_unique() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO", "VEICULOS"],
+                    &["IPS", "7111", "PASTILHA", "UNO, CELTA, PALIO, GOL, AGILE"],
+                ]),
+            )
+            .unwrap();
+            let expected = Some("AGILE,CELTA,GOL,PALIO,UNO".to_string());
+            let item = search_products(&conn, &SearchParams::default())
+                .unwrap()
+                .remove(0);
+            assert_eq!(item.vehicles, expected);
+            let gol: i64 = conn
+                .query_row("SELECT id FROM vehicles WHERE name = 'GOL'", [], |r| {
+                    r.get(0)
+                })
+                .unwrap();
+            let page = products_by_vehicle(&conn, gol, None, None).unwrap();
+            assert_eq!(page.items[0].vehicles, expected);
+        }
+
+        #[test]
+        fn product_count_respects_brand_filter() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO"],
+                    &["IPS", "1", "A"],
+                    &["IPS", "2", "B"],
+                    &["BOSCH", "3", "C"],
+                ]),
+            )
+            .unwrap();
+            let bosch = find_brand_by_name(&conn, "BOSCH").unwrap().unwrap();
+            assert_eq!(product_count(&conn, None).unwrap(), 3);
+            assert_eq!(product_count(&conn, Some(bosch)).unwrap(), 1);
+            assert_eq!(product_count(&conn, Some(-1)).unwrap(), 0);
+        }
+
+        #[test]
+        fn inactive_products_are_hidden_unless_requested() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO", "STATUS"],
+                    &["IPS", "1", "PASTILHA", "ATIVO"],
+                    &["IPS", "2", "PASTILHA ANTIGA", "DESCONTINUADO"],
+                    &["IPS", "3", "PASTILHA SEM STATUS", ""],
+                ]),
+            )
+            .unwrap();
+            let mut params = SearchParams {
+                code_query: Some("pastilha".into()),
+                sort: Some("code".into()),
+                ..Default::default()
+            };
+            let codes = |params: &SearchParams| -> Vec<String> {
+                search_products(&conn, params)
+                    .unwrap()
+                    .into_iter()
+                    .map(|p| p.code)
+                    .collect()
+            };
+            assert_eq!(codes(&params), vec!["1", "3"]);
+
+            params.include_inactive = Some(true);
+            assert_eq!(codes(&params), vec!["1", "2", "3"]);
+        }
+
+        #[test]
+        fn product_lookup_by_code_normalizes_input() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO"],
+                    &["IPS", "AB 7111", "PASTILHA"],
+                ]),
+            )
+            .unwrap();
+            let found = product_by_code(&conn, "  ab   7111 ").unwrap();
+            assert_eq!(
+                (found.code.as_str(), found.description.as_str()),
+                ("AB 7111", "PASTILHA")
+            );
+            assert_eq!(product_by_code(&conn, "AB 7111").unwrap().id, found.id);
+            assert_eq!(
+                product_by_code(&conn, "7111").unwrap_err(),
+                PRODUCT_NOT_FOUND_ERROR
+            );
+        }
+
+        #[test]
+        fn images_for_products_groups_filenames_by_id() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO"],
+                    &["IPS", "7111", "PASTILHA"],
+                    &["IPS", "7222", "DISCO"],
+                    &["IPS", "7333", "SAPATA"],
+                ]),
+            )
+            .unwrap();
+            let id = |code: &str| -> i64 {
+                conn.query_row("SELECT id FROM products WHERE code = ?1", [code], |r| {
+                    r.get(0)
+                })
+                .unwrap()
+            };
+            let (a, b, c) = (id("7111"), id("7222"), id("7333"));
+            for (pid, file) in [
+                (a, "IPS/7111_2.jpg"),
+                (a, "IPS/7111.jpg"),
+                (b, "IPS/7222.jpg"),
+                (b, "IPS/7222_b.jpg"),
+            ] {
+                conn.execute(
+                    "INSERT INTO images(product_id, filename) VALUES (?1, ?2)",
+                    params![pid, file],
+                )
+                .unwrap();
+            }
+
+            let map = images_for_products(&conn, &[a, b, c, a]).unwrap();
+            assert_eq!(map.len(), 3);
+            assert_eq!(map[&a], vec!["IPS/7111.jpg", "IPS/7111_2.jpg"]);
+            assert_eq!(map[&b], vec!["IPS/7222.jpg", "IPS/7222_b.jpg"]);
+            assert!(map[&c].is_empty());
+            assert!(images_for_products(&conn, &[]).unwrap().is_empty());
+        }
+
+        #[test]
+        fn images_for_more_ids_than_one_query_allows_are_complete() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            migrate(&conn).unwrap();
+            let total = 2 * MAX_IN_PARAMS as i64 + 5;
+            {
+                let tx = conn.transaction().unwrap();
+                tx.execute("INSERT OR IGNORE INTO brands(name) VALUES ('IPS')", [])
+                    .unwrap();
+                let brand: i64 = tx
+                    .query_row("SELECT id FROM brands WHERE name = 'IPS'", [], |r| r.get(0))
+                    .unwrap();
+                for id in 1..=total {
+                    tx.execute(
+                        "INSERT INTO products(id, brand_id, code, description) VALUES (?1, ?2, ?3, 'X')",
+                        params![id, brand, format!("C{id}")],
+                    )
+                    .unwrap();
+                    tx.execute(
+                        "INSERT INTO images(product_id, filename) VALUES (?1, ?2)",
+                        params![id, format!("IPS/C{id}.jpg")],
+                    )
+                    .unwrap();
+                }
+                tx.commit().unwrap();
+            }
+            let mut ids: Vec<i64> = (1..=total).rev().collect();
+            ids.extend([1, 2, 3]);
+
+            let map = images_for_products(&conn, &ids).unwrap();
+            assert_eq!(map.len(), total as usize);
+            assert!((1..=total).all(|id| map[&id] == vec![format!("IPS/C{id}.jpg")]));
+
+            let codes = query_in_chunks(
+                &conn,
+                "SELECT code FROM products WHERE id IN ({in}) ORDER BY id DESC",
+                &ids,
+                |row| row.get::<_, String>(0),
+            )
+            .unwrap();
+            assert_eq!(codes.len(), total as usize);
+            assert_eq!(
+                codes.first().map(String::as_str),
+                Some(format!("C{total}").as_str())
+            );
+        }
+
+        #[test]
+        fn renaming_into_existing_brand_merges_products() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            migrate(&conn).unwrap();
+            conn.execute_batch(
+                "INSERT INTO brands(id, name) VALUES (10, 'FORD'), (11, 'FORDE');
+                 INSERT INTO products(brand_id, code, description, pgroup) VALUES (10, 'A1', 'X', 'FREIO'), (11, 'A2', 'Y', 'SUSPENSAO'), (11, 'A3', 'Z', 'FREIO');",
+            )
+            .unwrap();
+            set_db_version(&conn, 4).unwrap();
+
+            assert!(rename_brand(&mut conn, 11, " ford ", false).is_err());
+            let res = rename_brand(&mut conn, 11, " ford ", true).unwrap();
+            assert_eq!(
+                (res.brand_id, res.merged, res.moved_products, res.db_version),
+                (10, true, 2, 5)
+            );
+            let products: i64 = conn
+                .query_row(
+                    "SELECT COUNT(*) FROM products WHERE brand_id = 10",
+                    [],
+                    |r| r.get(0),
+                )
+                .unwrap();
+            assert_eq!(products, 3);
+            assert_eq!(find_brand_by_name(&conn, "FORDE").unwrap(), None);
+            assert_eq!(
+                fetch_brand_groups(&conn, Some(10)).unwrap(),
+                vec!["FREIO", "SUSPENSAO"]
+            );
+
+            let res = rename_brand(&mut conn, 10, "Ford Motor", false).unwrap();
+            assert!(!res.merged);
+            assert_eq!(find_brand_by_name(&conn, "FORD MOTOR").unwrap(), Some(10));
+        }
+
+        #[test]
+        fn reimport_after_brand_rename_bumps_db_version() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            let rows = crate::importer::tests::sheet(&[
+                &["MARCA", "CODIGO", "DESCRICAO"],
+                &["FORD ", "A1", "PASTILHA"],
+            ]);
+            crate::importer::import_range(&mut conn, &rows).unwrap();
+            let brand = find_brand_by_name(&conn, "FORD").unwrap().unwrap();
+            let renamed = rename_brand(&mut conn, brand, "FORD MOTOR", false)
+                .unwrap()
+                .db_version;
+
+            crate::importer::import_range(&mut conn, &rows).unwrap();
+            assert_eq!(get_db_version(&conn).unwrap(), renamed + 1);
+            // Sem edição no meio, a mesma planilha continua sem gerar versão.
+            crate::importer::import_range(&mut conn, &rows).unwrap();
+            assert_eq!(get_db_version(&conn).unwrap(), renamed + 1);
+        }
+
+        #[test]
+        fn group_counts_are_scoped_to_brand() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO", "GRUPO"],
+                    &["IPS", "1", "A", "Freio"],
+                    &["IPS", "2", "B", "FREIO "],
+                    &["IPS", "3", "C", "Motor"],
+                    &["OUTRA", "4", "D", "Freio"],
+                ]),
+            )
+            .unwrap();
+            let ips: i64 = conn
+                .query_row("SELECT id FROM brands WHERE name = 'IPS'", [], |r| r.get(0))
+                .unwrap();
+            let group = |name: &str, product_count| GroupCount {
+                name: name.to_string(),
+                product_count,
+            };
+
+            assert_eq!(
+                list_groups(&conn, Some(ips), true, 0).unwrap(),
+                GroupsResult::Counts(vec![group("FREIO", 2), group("MOTOR", 1)])
+            );
+            assert_eq!(
+                list_groups(&conn, None, true, 2).unwrap(),
+                GroupsResult::Counts(vec![group("FREIO", 3)])
+            );
+            // Fallback por products (brand_groups vazio) dá as mesmas contagens.
+            conn.execute("DELETE FROM brand_groups", []).unwrap();
+            assert_eq!(
+                list_groups(&conn, Some(ips), false, 2).unwrap(),
+                GroupsResult::Names(vec!["FREIO".to_string()])
+            );
+        }
+
+        #[test]
+        fn types_ignore_leading_spaces_and_punctuation() {
+            let conn = Connection::open_in_memory().unwrap();
+            migrate(&conn).unwrap();
+            // Linhas "antigas", sem ptype: o migrate preenche.
+            conn.execute_batch(
+                "INSERT INTO products(brand_id, code, description) VALUES
+                   (1, '1', '  Pastilha  dianteira'), (1, '2', '- PASTILHA, traseira'),
+                   (1, '3', 'Disco ventilado'), (1, '4', '  ');",
+            )
+            .unwrap();
+            migrate(&conn).unwrap();
+            let count = |name: &str, product_count| TypeCount {
+                name: name.to_string(),
+                product_count,
+            };
+            assert_eq!(
+                fetch_types(&conn, None).unwrap(),
+                vec![count("DISCO", 1), count("PASTILHA", 2)]
+            );
+            assert!(fetch_types(&conn, Some(99)).unwrap().is_empty());
+        }
+
+        #[test]
+        fn search_fields_limit_where_terms_match() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO"],
+                    &["IPS", "7111", "PASTILHA DIANTEIRA"],
+                    &["IPS", "PAST-9", "DISCO"],
+                ]),
+            )
+            .unwrap();
+            let codes = |fields: Option<Vec<&str>>| -> Result<Vec<String>, String> {
+                let params = SearchParams {
+                    code_query: Some("past".into()),
+                    search_fields: fields.map(|f| f.into_iter().map(String::from).collect()),
+                    ..Default::default()
+                };
+                let mut out: Vec<String> = search_products(&conn, &params)?
+                    .into_iter()
+                    .map(|p| p.code)
+                    .collect();
+                out.sort();
+                Ok(out)
+            };
+            assert_eq!(codes(None).unwrap(), vec!["7111", "PAST-9"]);
+            assert_eq!(codes(Some(vec!["code"])).unwrap(), vec!["PAST-9"]);
+            assert_eq!(codes(Some(vec!["Description"])).unwrap(), vec!["7111"]);
+            assert!(codes(Some(vec!["code", "stock"])).is_err());
+        }
+
+        #[test]
+        fn highlight_points_at_matched_substring() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO"],
+                    &["IPS", "AB-7111", "PASTILHA ÓLEO"],
+                ]),
+            )
+            .unwrap();
+            let mut params = SearchParams {
+                code_query: Some("7111 óleo".into()),
+                ..Default::default()
+            };
+            assert!(search_products(&conn, &params).unwrap()[0]
+                .highlights
+                .is_empty());
+
+            params.highlight = Some(true);
+            let item = search_products(&conn, &params).unwrap().remove(0);
+            let code = item.highlights.iter().find(|m| m.field == "code").unwrap();
+            let matched: String = item.code.chars().skip(code.start).take(code.len).collect();
+            assert_eq!(matched, "7111");
+            let desc = item
+                .highlights
+                .iter()
+                .find(|m| m.field == "description")
+                .unwrap();
+            let matched: String = item
+                .description
+                .chars()
+                .skip(desc.start)
+                .take(desc.len)
+                .collect();
+            assert_eq!(matched, "ÓLEO");
+        }
+
+        #[test]
+        fn sort_is_whitelisted_and_paging_is_bound() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO"],
+                    &["IPS", "C3", "ALFA"],
+                    &["IPS", "C1", "BETA"],
+                    &["IPS", "C2", "GAMA"],
+                ]),
+            )
+            .unwrap();
+            let evil = SearchParams {
+                sort: Some("p.code; DROP TABLE products".into()),
+                ..Default::default()
+            };
+            assert!(search_products(&conn, &evil).is_err());
+            let count: i64 = conn
+                .query_row("SELECT COUNT(*) FROM products", [], |r| r.get(0))
+                .unwrap();
+            assert_eq!(count, 3);
+
+            let page = |limit: Option<i64>, offset: Option<i64>| -> Vec<String> {
+                let params = SearchParams {
+                    sort: Some("CODE".into()),
+                    limit,
+                    offset,
+                    ..Default::default()
+                };
+                search_products(&conn, &params)
+                    .unwrap()
+                    .into_iter()
+                    .map(|p| p.code)
+                    .collect()
+            };
+            assert_eq!(page(None, None), vec!["C1", "C2", "C3"]);
+            assert_eq!(page(Some(1), Some(1)), vec!["C2"]);
+            assert_eq!(page(None, Some(2)), vec!["C3"]);
+            assert_eq!(page(Some(5), Some(-10)), vec!["C1", "C2", "C3"]);
+        }
+
+        #[test]
+        fn vehicle_query_matches_model_substring() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            crate::importer::import_range(
+                &mut conn,
+                &crate::importer::tests::sheet(&[
+                    &["MARCA", "CODIGO", "DESCRICAO", "VEICULOS"],
+                    &["IPS", "1", "PASTILHA", "GOL G5; UNO"],
+                    &["IPS", "2", "DISCO", "HILUX 2.8"],
+                    &["IPS", "3", "SAPATA", "UNO MILLE"],
+                ]),
+            )
+            .unwrap();
+            fn codes(conn: &Connection, params: &SearchParams) -> Vec<String> {
+                let mut out: Vec<String> = search_products(conn, params)
+                    .unwrap()
+                    .into_iter()
+                    .map(|p| p.code)
+                    .collect();
+                out.sort();
+                out
+            }
+
+            let by_query = SearchParams {
+                vehicle_query: Some("  uno ".into()),
+                ..Default::default()
+            };
+            assert_eq!(codes(&conn, &by_query), vec!["1", "3"]);
+
+            let hilux: i64 = conn
+                .query_row(
+                    "SELECT id FROM vehicles WHERE name = 'HILUX 2.8'",
+                    [],
+                    |r| r.get(0),
+                )
+                .unwrap();
+            let with_id = SearchParams {
+                vehicle_id: Some(hilux),
+                ..Default::default()
+            };
+            assert_eq!(codes(&conn, &with_id), vec!["2"]);
+            let both = SearchParams {
+                vehicle_id: Some(hilux),
+                vehicle_query: Some("uno".into()),
+                ..Default::default()
+            };
+            assert!(codes(&conn, &both).is_empty());
+        }
+
+        #[test]
+        fn migrate_merges_accent_variant_brands() {
+            let conn = Connection::open_in_memory().unwrap();
+            migrate(&conn).unwrap();
+            conn.execute_batch(
+                "INSERT INTO brands(id, name) VALUES (10, 'Citroën'), (11, 'CITROEN');
+                 INSERT INTO products(brand_id, code, description, pgroup) VALUES (10, 'A1', 'X', 'FREIO'), (11, 'A2', 'Y', 'SUSPENSAO');
+                 INSERT INTO brand_groups(brand_id, name) VALUES (10, 'FREIO'), (11, 'FREIO'), (11, 'SUSPENSAO');",
+            )
+            .unwrap();
+            migrate(&conn).unwrap();
+            let brands: i64 = conn
+                .query_row(
+                    "SELECT COUNT(*) FROM brands WHERE id IN (10, 11)",
+                    [],
+                    |r| r.get(0),
+                )
+                .unwrap();
+            assert_eq!(brands, 1);
+            let moved: i64 = conn
+                .query_row(
+                    "SELECT COUNT(*) FROM products WHERE brand_id = 10",
+                    [],
+                    |r| r.get(0),
+                )
+                .unwrap();
+            assert_eq!(moved, 2);
+            let groups: i64 = conn
+                .query_row(
+                    "SELECT COUNT(*) FROM brand_groups WHERE brand_id = 10",
+                    [],
+                    |r| r.get(0),
+                )
+                .unwrap();
+            assert_eq!(groups, 2);
+            assert_eq!(find_brand_by_name(&conn, " citroen ").unwrap(), Some(10));
+        }
+
+        #[test]
+        fn migrate_merges_vehicle_name_variants() {
+            let conn = Connection::open_in_memory().unwrap();
+            migrate(&conn).unwrap();
+            conn.execute_batch(
+                "INSERT INTO brands(id, name) VALUES (10, 'IPS');
+                 INSERT INTO products(id, brand_id, code, description) VALUES (1, 10, 'A1', 'X'), (2, 10, 'A2', 'Y');
+                 INSERT INTO vehicles(id, name) VALUES (1, 'Gol  1.0'), (3, 'uno');
+                 INSERT INTO vehicles(id, name, make, category, years) VALUES (2, 'GOL 1.0', 'GOL', 'LEVE', '2010,2011');
+                 INSERT INTO product_vehicles VALUES (1, 1), (2, 2), (1, 2);",
+            )
+            .unwrap();
+
+            migrate(&conn).unwrap();
+
+            let vehicles: Vec<(i64, String)> = conn
+                .prepare("SELECT id, name FROM vehicles ORDER BY id")
+                .unwrap()
+                .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))
+                .unwrap()
+                .map(|r| r.unwrap())
+                .collect();
+            assert_eq!(
+                vehicles,
+                vec![(1, "GOL 1.0".to_string()), (3, "UNO".to_string())]
+            );
+            let carried: (String, String) = conn
+                .query_row(
+                    "SELECT category, years FROM vehicles WHERE id = 1",
+                    [],
+                    |r| Ok((r.get(0)?, r.get(1)?)),
+                )
+                .unwrap();
+            assert_eq!(carried, ("LEVE".to_string(), "2010,2011".to_string()));
+        }
+
+        #[test]
+        fn failed_savepoint_leaves_no_open_transaction() {
+            let conn = Connection::open_in_memory().unwrap();
+            migrate(&conn).unwrap();
+            let res: Result<()> = in_savepoint(&conn, "falha", || {
+                conn.execute("INSERT INTO vehicles(name) VALUES ('GOL')", [])?;
+                Err(anyhow::anyhow!("falhou no meio"))
+            });
+            assert!(res.is_err());
+            assert!(conn.is_autocommit());
+            let vehicles: i64 = conn
+                .query_row("SELECT COUNT(*) FROM vehicles", [], |r| r.get(0))
+                .unwrap();
+            assert_eq!(vehicles, 0);
+        }
+
+        #[test]
+        fn migrate_merges_near_duplicate_codes() {
+            let conn = Connection::open_in_memory().unwrap();
+            migrate(&conn).unwrap();
+            conn.execute_batch(
+                "INSERT INTO brands(id, name) VALUES (10, 'IPS');
+                 INSERT INTO products(id, brand_id, code, description) VALUES (1, 10, '7111 ', 'A'), (2, 10, '7111', 'B'), (3, 10, 'ab 1', 'C');
+                 INSERT INTO vehicles(id, name) VALUES (1, 'GOL'), (2, 'UNO');
+                 INSERT INTO product_vehicles VALUES (1, 1), (2, 1), (1, 2);",
+            )
+            .unwrap();
+
+            migrate(&conn).unwrap();
+
+            let codes: Vec<(i64, String)> = conn
+                .prepare("SELECT id, code FROM products ORDER BY id")
+                .unwrap()
+                .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))
+                .unwrap()
+                .map(|r| r.unwrap())
+                .collect();
+            assert_eq!(
+                codes,
+                vec![(2, "7111".to_string()), (3, "AB 1".to_string())]
+            );
+            let links: i64 = conn
+                .query_row(
+                    "SELECT COUNT(*) FROM product_vehicles WHERE product_id = 2",
+                    [],
+                    |r| r.get(0),
+                )
+                .unwrap();
+            assert_eq!(links, 2);
+        }
+    }
+
+    pub(crate) fn get_db_version(conn: &Connection) -> Result<i64> {
+        Ok(conn.query_row(
+            "SELECT CAST(value AS INTEGER) FROM meta WHERE key = ?1",
+            params![META_DB_VERSION_KEY],
+            |row| row.get(0),
+        )?)
+    }
+    /// 0 para bancos anteriores à chave `schema_version`.
+    pub(crate) fn get_schema_version(conn: &Connection) -> Result<i64> {
+        let v: Option<i64> = conn
+            .query_row(
+                "SELECT CAST(value AS INTEGER) FROM meta WHERE key = ?1",
+                params![META_SCHEMA_VERSION_KEY],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(v.unwrap_or(0))
+    }
+    pub(crate) fn set_db_version(conn: &Connection, v: i64) -> Result<()> {
+        conn.execute(
+            "INSERT OR REPLACE INTO meta(key,value) VALUES(?1, ?2)",
+            params![META_DB_VERSION_KEY, v.to_string()],
+        )?;
+        Ok(())
+    }
+    /// Para quem edita o catálogo fora da planilha: sem a impressão digital, reimportar a
+    /// planilha antiga conta como mudança e gera versão nova (a sync vê a reversão).
+    pub(crate) fn forget_import_fingerprint(conn: &Connection) -> Result<()> {
+        conn.execute(
+            "DELETE FROM meta WHERE key = ?1",
+            params![META_IMPORT_FINGERPRINT_KEY],
+        )?;
+        Ok(())
+    }
+    fn get_manifest_hash(conn: &Connection) -> Result<Option<String>> {
+        Ok(conn
+            .query_row(
+                "SELECT value FROM meta WHERE key = ?1",
+                params![META_MANIFEST_HASH_KEY],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+    fn set_manifest_hash(conn: &Connection, v: &str) -> Result<()> {
+        conn.execute(
+            "INSERT OR REPLACE INTO meta(key,value) VALUES(?1, ?2)",
+            params![META_MANIFEST_HASH_KEY, v],
+        )?;
+        Ok(())
+    }
+
+    /// Pasta de recursos do bundle; quando o Tauri não a resolve, registra o motivo e
+    /// os seeds empacotados são ignorados (sobram os candidatos fora do bundle).
+    fn bundled_resource_dir(app: &AppHandle) -> Option<PathBuf> {
+        match app.path().resource_dir() {
+            Ok(dir) => Some(dir),
+            Err(e) => {
+                eprintln!(
+                    "{}; seeds empacotados ignorados",
+                    SeedManifestError::ResourceDirUnavailable(e.to_string())
+                );
+                None
+            }
+        }
+    }
+
+    /// Ordem: catalog.db no bundle, data/catalog.db no bundle, data/catalog.db na raiz do projeto.
+    fn seed_catalog_db_candidates(app: &AppHandle) -> Vec<PathBuf> {
+        let mut candidates = Vec::new();
+        if let Some(res_dir) = bundled_resource_dir(app) {
+            candidates.push(res_dir.join("catalog.db"));
+            candidates.push(res_dir.join("data").join("catalog.db"));
+        }
+        if let Ok(cwd) = std::env::current_dir() {
+            let app_root = if cwd.ends_with("src-tauri") {
+                cwd.parent().unwrap_or(&cwd).to_path_buf()
+            } else {
+                cwd
+            };
+            candidates.push(app_root.join("data").join("catalog.db"));
+        }
+        candidates
+    }
+
+    fn seed_xlsx_candidates(app: &AppHandle) -> Vec<PathBuf> {
+        let mut candidates = Vec::new();
+        if let Some(res_dir) = bundled_resource_dir(app) {
+            candidates.push(res_dir.join("seed.xlsx"));
+            candidates.push(res_dir.join("data").join("seed.xlsx"));
+        }
+        candidates
+    }
+
+    fn copy_seed_catalog_db(app: &AppHandle, db_file: &Path) -> Result<Option<PathBuf>> {
+        copy_first_seed(&seed_catalog_db_candidates(app), db_file)
+    }
+
+    fn copy_first_seed(candidates: &[PathBuf], db_file: &Path) -> Result<Option<PathBuf>> {
+        for seed in candidates {
+            if !seed.exists() {
+                continue;
+            }
+            if let Some(parent) = db_file.parent() {
                 fs::create_dir_all(parent)?;
             }
-            fs::copy(&seed, db_file)?;
-            return Ok(Some(seed));
+            fs::copy(seed, db_file)?;
+            return Ok(Some(seed.clone()));
         }
         Ok(None)
     }
@@ -458,46 +4145,46 @@ mod core {
         // se a chave vier empacotada, persiste em descrypt.key para facilitar em runtime
         if let Some(k) = load_env_key(app.path().resource_dir().ok().as_deref(), Some(&data_dir)) {
             let key_file = data_dir.join("descrypt.key");
-            if !key_file.exists() {
-                let _ = std::fs::write(&key_file, k.as_bytes());
+            if !key_file.exists() && std::fs::write(&key_file, k.as_bytes()).is_ok() {
+                crate::call_img::invalidate_key_cache();
             }
         }
         let created = !db_file.exists();
-        if created {
-            let _ = copy_seed_catalog_db(&app, &db_file);
-        }
-        let conn = open_db(&db_file).map_err(|e| e.to_string())?;
+        let seeded_from_bundle =
+            created && matches!(copy_seed_catalog_db(&app, &db_file), Ok(Some(_)));
+        if seeded_from_bundle || db_file_is_encrypted(&db_file) {
+            let keys = crate::call_img::resolve_keys(&app, &data_dir);
+            prepare_db_file(&db_file, seeded_from_bundle, &keys).map_err(|e| e.to_string())?;
+        }
+        let mut conn = open_db(&db_file).map_err(|e| e.to_string())?;
+        // migrate() já normaliza montadoras (normalize_vehicles_makes).
         migrate(&conn).map_err(|e| e.to_string())?;
-
-        // Normaliza montadoras e coluna make em vehicles
-        let _ = conn.execute("ALTER TABLE vehicles ADD COLUMN make TEXT", []);
-        let _ = conn.execute("ALTER TABLE vehicles ADD COLUMN make_id INTEGER", []);
-        let _ = conn.execute("ALTER TABLE vehicles ADD COLUMN category TEXT", []);
-        let _ = conn.execute("ALTER TABLE vehicles ADD COLUMN years TEXT", []);
-        let _ = conn.execute(
-            "CREATE TABLE IF NOT EXISTS makes (id INTEGER PRIMARY KEY, name TEXT NOT NULL UNIQUE)",
-            [],
-        );
-        let _ = conn.execute(
-            "UPDATE vehicles SET make = UPPER(TRIM(CASE WHEN INSTR(name,' ')>0 THEN SUBSTR(name,1,INSTR(name,' ')-1) ELSE name END)) WHERE make IS NULL OR TRIM(COALESCE(make,''))=''",
-            [],
-        );
-        let _ = conn.execute(
-            "INSERT OR IGNORE INTO makes(name) SELECT DISTINCT UPPER(TRIM(COALESCE(make,''))) FROM vehicles WHERE TRIM(COALESCE(make,'')) <> ''",
-            [],
-        );
-        let _ = conn.execute(
-            "UPDATE vehicles SET make_id = (SELECT id FROM makes m WHERE UPPER(TRIM(m.name)) = UPPER(TRIM(COALESCE(vehicles.make,'')))) WHERE make_id IS NULL AND TRIM(COALESCE(make,'')) <> ''",
-            [],
-        );
+        configure_launch_tokens(&conn);
+        let mut seeded_from_xlsx = false;
+        if created && !seeded_from_bundle {
+            if let Some(seed) = seed_xlsx_candidates(&app).into_iter().find(|p| p.is_file()) {
+                match crate::importer::import_seed_if_empty(&mut conn, &seed) {
+                    Ok(Some(result)) => {
+                        seeded_from_xlsx = true;
+                        let _ = app.emit("seed_imported", &result);
+                    }
+                    Ok(None) => {}
+                    Err(e) => eprintln!("Falha ao importar {}: {}", seed.display(), e),
+                }
+            }
+        }
 
         let version = get_db_version(&conn).map_err(|e| e.to_string())?;
-        Ok(InitInfo {
+        let info = InitInfo {
             data_dir: data_dir.to_string_lossy().into_owned(),
             images_dir: imgs_dir.to_string_lossy().into_owned(),
             db_path: db_file.to_string_lossy().into_owned(),
             db_version: version,
-        })
+            seeded_from_bundle,
+            seeded_from_xlsx,
+        };
+        let _ = app.emit("app_initialized", info.clone());
+        Ok(info)
     }
 
     #[tauri::command]
@@ -522,51 +4209,200 @@ mod core {
         Ok(out)
     }
 
+    /// Erro devolvido quando o banco segue travado (ex.: troca do catalog.db na sync);
+    /// o front mostra "atualização em andamento, tente novamente".
+    pub const DB_BUSY_ERROR: &str = "DB_BUSY";
+    const BUSY_RETRY_ATTEMPTS: usize = 3;
+    const BUSY_RETRY_PAUSE: Duration = Duration::from_millis(250);
+
+    fn is_busy_error(err: &anyhow::Error) -> bool {
+        if let Some(rusqlite::Error::SqliteFailure(e, _)) = err.downcast_ref::<rusqlite::Error>() {
+            return matches!(
+                e.code,
+                rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked
+            );
+        }
+        // Helpers que já converteram o erro para texto.
+        err.to_string().contains("database is locked")
+    }
+
+    /// Repete uma leitura que bateu em SQLITE_BUSY (além do busy_timeout da conexão);
+    /// se continuar travado, devolve `DB_BUSY_ERROR`.
+    fn retry_busy<T>(f: impl FnMut() -> Result<T>) -> Result<T, String> {
+        retry_busy_with(BUSY_RETRY_ATTEMPTS, BUSY_RETRY_PAUSE, f)
+    }
+
+    fn retry_busy_with<T>(
+        attempts: usize,
+        pause: Duration,
+        mut f: impl FnMut() -> Result<T>,
+    ) -> Result<T, String> {
+        for attempt in 1..=attempts.max(1) {
+            match f() {
+                Ok(v) => return Ok(v),
+                Err(e) if is_busy_error(&e) => {
+                    if attempt < attempts {
+                        std::thread::sleep(pause);
+                    }
+                }
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+        Err(DB_BUSY_ERROR.to_string())
+    }
+
     // moved lower after search_products_cmd (avoid duplicate definitions)
     #[tauri::command]
-    pub fn get_vehicles_cmd(app: AppHandle) -> Result<Vec<Vehicle>, String> {
+    pub fn get_vehicles_cmd(
+        app: AppHandle,
+        query: Option<String>,
+        limit: Option<i64>,
+        offset: Option<i64>,
+    ) -> Result<Vec<Vehicle>, String> {
         let conn =
             open_db(&db_path(&app).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
-        let mut stmt = conn
-            .prepare("SELECT id, name, category FROM vehicles ORDER BY name")
-            .map_err(|e| e.to_string())?;
-        let rows = stmt
-            .query_map([], |row| {
+        retry_busy(|| list_vehicles(&conn, query.as_deref(), limit, offset))
+    }
+
+    /// Página padrão de `list_vehicles` quando há `query` e nenhum `limit`.
+    const VEHICLE_QUERY_DEFAULT_LIMIT: i64 = 100;
+
+    /// `fold_key(texto)` no SQL, a mesma dobra de acento/caixa/espaços do importador.
+    fn register_fold_key_sql_fn(conn: &Connection) -> rusqlite::Result<()> {
+        use rusqlite::functions::FunctionFlags;
+        conn.create_scalar_function(
+            "fold_key",
+            1,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            |ctx| {
+                let text: Option<String> = ctx.get(0)?;
+                Ok(text.map(|t| crate::importer::fold_key(&t)))
+            },
+        )
+    }
+
+    /// Veículos em ordem de nome, opcionalmente filtrados por trecho do nome (sem acento/caixa)
+    /// e paginados; filtro e página rodam no SQL. Com `query` e sem `limit` devolve no máximo
+    /// `VEHICLE_QUERY_DEFAULT_LIMIT`; sem nenhum dos dois devolve todos, como antes.
+    pub(crate) fn list_vehicles(
+        conn: &Connection,
+        query: Option<&str>,
+        limit: Option<i64>,
+        offset: Option<i64>,
+    ) -> Result<Vec<Vehicle>> {
+        let needle = query.map(crate::importer::fold_key).unwrap_or_default();
+        let default_limit = if needle.is_empty() {
+            -1
+        } else {
+            VEHICLE_QUERY_DEFAULT_LIMIT
+        };
+        let limit = limit.filter(|l| *l >= 0).unwrap_or(default_limit);
+        let offset = offset.unwrap_or(0).max(0);
+        register_fold_key_sql_fn(conn)?;
+        let mut stmt = conn.prepare(
+            "SELECT id, name, category FROM vehicles
+             WHERE :needle = '' OR instr(fold_key(name), :needle) > 0
+             ORDER BY name LIMIT :limit OFFSET :offset",
+        )?;
+        let rows = stmt.query_map(
+            named_params! { ":needle": needle, ":limit": limit, ":offset": offset },
+            |row| {
                 Ok(Vehicle {
                     id: row.get(0)?,
                     name: row.get(1)?,
                     category: row.get(2)?,
                 })
-            })
-            .map_err(|e| e.to_string())?;
-        let mut out = Vec::new();
-        for r in rows {
-            out.push(r.map_err(|e| e.to_string())?);
-        }
-        Ok(out)
+            },
+        )?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
     }
 
     #[tauri::command]
     pub fn get_makes_cmd(app: AppHandle) -> Result<Vec<String>, String> {
         let conn =
             open_db(&db_path(&app).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
-        let sql = "SELECT name FROM makes ORDER BY name";
-        let mut stmt = conn.prepare(sql).map_err(|e| e.to_string())?;
-        let rows = stmt
-            .query_map([], |row| row.get::<_, String>(0))
-            .map_err(|e| e.to_string())?;
+        retry_busy(|| list_makes(&conn))
+    }
+
+    pub(crate) fn list_makes(conn: &Connection) -> Result<Vec<String>> {
+        let mut stmt = conn.prepare("SELECT name FROM makes ORDER BY name")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
         let mut out = Vec::new();
-        for r in rows {
-            if let Ok(m) = r {
-                let mm = m.trim().to_string();
-                if !mm.is_empty() {
-                    out.push(mm);
-                }
+        for m in rows.flatten() {
+            let mm = m.trim().to_string();
+            if !mm.is_empty() {
+                out.push(mm);
             }
         }
         Ok(out)
     }
 
+    /// Refaz makes/vehicle_makes a partir de vehicles.make depois de edições manuais:
+    /// troca o vínculo principal que não bate mais com o nome, mantém as montadoras
+    /// secundárias (ex.: "VW/FIAT") e apaga montadoras sem nenhum veículo.
+    pub(crate) fn rebuild_makes(conn: &Connection) -> Result<i64> {
+        in_savepoint(conn, "rebuild_makes", || {
+            let stale = "SELECT v.id FROM vehicles v JOIN makes m ON m.id = v.make_id WHERE UPPER(TRIM(m.name)) <> UPPER(TRIM(COALESCE(v.make,'')))";
+            conn.execute(
+                &format!("DELETE FROM vehicle_makes WHERE vehicle_id IN ({stale}) AND make_id = (SELECT make_id FROM vehicles WHERE id = vehicle_makes.vehicle_id)"),
+                [],
+            )?;
+            conn.execute(
+                &format!("UPDATE vehicles SET make_id = NULL WHERE id IN ({stale})"),
+                [],
+            )?;
+            normalize_vehicles_makes(conn)?;
+            conn.execute(
+                "DELETE FROM makes WHERE id NOT IN (SELECT make_id FROM vehicle_makes)
+                   AND id NOT IN (SELECT make_id FROM vehicles WHERE make_id IS NOT NULL)",
+                [],
+            )?;
+            Ok(())
+        })?;
+        Ok(conn.query_row("SELECT COUNT(*) FROM makes", [], |row| row.get(0))?)
+    }
+
+    #[tauri::command]
+    pub fn rebuild_makes_cmd(app: AppHandle) -> Result<i64, String> {
+        let conn =
+            open_db(&db_path(&app).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+        ensure_schema(&conn).map_err(|e| e.to_string())?;
+        rebuild_makes(&conn).map_err(|e| e.to_string())
+    }
+
+    /// Apaga vínculos que apontam para produto/veículo/montadora que não existe mais
+    /// (sobras de merges, exclusões ou importações interrompidas).
+    pub(crate) fn repair_orphans(conn: &Connection) -> Result<RepairOrphansResult> {
+        in_savepoint(conn, "repair_orphans", || {
+            Ok(RepairOrphansResult {
+                product_vehicles: conn.execute(
+                    "DELETE FROM product_vehicles
+                     WHERE product_id NOT IN (SELECT id FROM products)
+                        OR vehicle_id NOT IN (SELECT id FROM vehicles)",
+                    [],
+                )?,
+                images: conn.execute(
+                    "DELETE FROM images WHERE product_id NOT IN (SELECT id FROM products)",
+                    [],
+                )?,
+                vehicle_makes: conn.execute(
+                    "DELETE FROM vehicle_makes
+                     WHERE make_id NOT IN (SELECT id FROM makes)
+                        OR vehicle_id NOT IN (SELECT id FROM vehicles)",
+                    [],
+                )?,
+            })
+        })
+    }
+
+    #[tauri::command]
+    pub fn repair_orphans_cmd(app: AppHandle) -> Result<RepairOrphansResult, String> {
+        let conn =
+            open_db(&db_path(&app).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+        ensure_schema(&conn).map_err(|e| e.to_string())?;
+        repair_orphans(&conn).map_err(|e| e.to_string())
+    }
+
     #[tauri::command]
     pub fn get_vehicles_by_make_cmd(
         app: AppHandle,
@@ -609,26 +4445,77 @@ mod core {
         brand_name: Option<String>,
         brand_id_camel: Option<i64>,
         brand_name_camel: Option<String>,
-    ) -> Result<Vec<String>, String> {
+        with_counts: Option<bool>,
+        min_count: Option<i64>,
+    ) -> Result<GroupsResult, String> {
         let incoming_id = brand_id.or(brand_id_camel);
         let incoming_name = brand_name.clone().or(brand_name_camel.clone());
         let conn =
             open_db(&db_path(&app).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
         seed_brand_groups(&conn).ok();
-        if let Some(bid) = incoming_id {
-            let mut out = fetch_brand_groups(&conn, Some(bid)).map_err(|e| e.to_string())?;
-            if out.is_empty() {
-                out = fetch_groups_from_products(&conn, Some(bid)).map_err(|e| e.to_string())?;
-            }
-            return Ok(out);
-        }
-        let resolved = resolve_brand_id(&conn, incoming_id, incoming_name.clone())
-            .map_err(|e| e.to_string())?;
-        let mut out = fetch_brand_groups(&conn, resolved).map_err(|e| e.to_string())?;
-        if out.is_empty() {
-            out = fetch_groups_from_products(&conn, resolved).map_err(|e| e.to_string())?;
+        let resolved = match incoming_id {
+            Some(bid) => Some(bid),
+            None => resolve_brand_id(&conn, incoming_id, incoming_name.clone())
+                .map_err(|e| e.to_string())?,
+        };
+        list_groups(
+            &conn,
+            resolved,
+            with_counts.unwrap_or(false),
+            min_count.unwrap_or(0),
+        )
+        .map_err(|e| e.to_string())
+    }
+
+    /// Grupos da marca (ou de todas), via brand_groups com fallback para products.
+    /// As contagens saem sempre de products, então os dois caminhos concordam.
+    pub(crate) fn list_groups(
+        conn: &Connection,
+        brand_id: Option<i64>,
+        with_counts: bool,
+        min_count: i64,
+    ) -> Result<GroupsResult> {
+        let mut names = fetch_brand_groups(conn, brand_id)?;
+        if names.is_empty() {
+            names = fetch_groups_from_products(conn, brand_id)?;
+        }
+        if !with_counts && min_count <= 0 {
+            return Ok(GroupsResult::Names(names));
+        }
+        let mut sql = format!(
+            "SELECT {expr}, COUNT(*) FROM products",
+            expr = GROUP_EXPR_SQL
+        );
+        if brand_id.is_some() {
+            sql.push_str(" WHERE brand_id = ?1");
         }
-        Ok(out)
+        sql.push_str(" GROUP BY 1");
+        let mut stmt = conn.prepare(&sql)?;
+        let map_row = |r: &rusqlite::Row| Ok((r.get::<_, String>(0)?, r.get::<_, i64>(1)?));
+        let counts: HashMap<String, i64> = match brand_id {
+            Some(b) => stmt
+                .query_map(params![b], map_row)?
+                .collect::<rusqlite::Result<_>>()?,
+            None => stmt
+                .query_map([], map_row)?
+                .collect::<rusqlite::Result<_>>()?,
+        };
+        let groups: Vec<GroupCount> = names
+            .into_iter()
+            .map(|name| {
+                let product_count = counts.get(&name).copied().unwrap_or(0);
+                GroupCount {
+                    name,
+                    product_count,
+                }
+            })
+            .filter(|g| g.product_count >= min_count)
+            .collect();
+        Ok(if with_counts {
+            GroupsResult::Counts(groups)
+        } else {
+            GroupsResult::Names(groups.into_iter().map(|g| g.name).collect())
+        })
     }
 
     #[tauri::command]
@@ -715,23 +4602,134 @@ mod core {
         })
     }
 
-    fn group_expr_alias(alias: &str) -> String {
-        format!("{} AS {}", GROUP_EXPR_SQL, alias)
-    }
-
-    pub(crate) fn seed_brand_groups(conn: &Connection) -> Result<()> {
-        conn.execute("DELETE FROM brand_groups", [])?;
-        let sql = format!(
-            "INSERT INTO brand_groups(brand_id, name)
-             SELECT DISTINCT brand_id, {expr}
-             FROM products
-             WHERE TRIM({expr}) <> ''",
-            expr = GROUP_EXPR_SQL
-        );
-        conn.execute(&sql, [])?;
-        Ok(())
-    }
-
+    fn group_expr_alias(alias: &str) -> String {
+        format!("{} AS {}", GROUP_EXPR_SQL, alias)
+    }
+
+    pub(crate) fn seed_brand_groups(conn: &Connection) -> Result<()> {
+        conn.execute("DELETE FROM brand_groups", [])?;
+        let sql = format!(
+            "INSERT INTO brand_groups(brand_id, name)
+             SELECT DISTINCT brand_id, {expr}
+             FROM products
+             WHERE TRIM({expr}) <> ''",
+            expr = GROUP_EXPR_SQL
+        );
+        conn.execute(&sql, [])?;
+        Ok(())
+    }
+
+    /// Refaz o cache brand_groups e devolve quantos grupos distintos existem.
+    pub(crate) fn reseed_groups(conn: &Connection) -> Result<i64> {
+        seed_brand_groups(conn)?;
+        Ok(
+            conn.query_row("SELECT COUNT(DISTINCT name) FROM brand_groups", [], |row| {
+                row.get(0)
+            })?,
+        )
+    }
+
+    #[tauri::command]
+    pub fn reseed_groups_cmd(app: AppHandle) -> Result<i64, String> {
+        let conn =
+            open_db(&db_path(&app).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+        ensure_schema(&conn).map_err(|e| e.to_string())?;
+        reseed_groups(&conn).map_err(|e| e.to_string())
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct RenameBrandResult {
+        /// Marca que ficou: a própria ou a que já tinha o nome (quando houve união).
+        pub brand_id: i64,
+        pub merged: bool,
+        pub moved_products: usize,
+        pub db_version: i64,
+    }
+
+    /// Corrige o nome da marca ("FORD " -> "FORD"). Se outra marca já usa o nome, só une
+    /// com `merge = true`: os produtos vão para ela e esta é apagada.
+    #[tauri::command]
+    pub fn rename_brand_cmd(
+        app: AppHandle,
+        brand_id: i64,
+        new_name: String,
+        merge: Option<bool>,
+    ) -> Result<RenameBrandResult, String> {
+        let mut conn =
+            open_db(&db_path(&app).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+        ensure_schema(&conn).map_err(|e| e.to_string())?;
+        rename_brand(&mut conn, brand_id, &new_name, merge.unwrap_or(false))
+    }
+
+    pub(crate) fn rename_brand(
+        conn: &mut Connection,
+        brand_id: i64,
+        new_name: &str,
+        merge: bool,
+    ) -> Result<RenameBrandResult, String> {
+        let name = new_name.split_whitespace().collect::<Vec<_>>().join(" ");
+        if name.is_empty() {
+            return Err("Informe o novo nome da marca".to_string());
+        }
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let current: Option<String> = tx
+            .query_row(
+                "SELECT name FROM brands WHERE id = ?1",
+                params![brand_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        if current.is_none() {
+            return Err(format!("Marca não encontrada: {brand_id}"));
+        }
+        let other = find_brand_by_name(&tx, &name)
+            .map_err(|e| e.to_string())?
+            .filter(|id| *id != brand_id);
+        let (survivor, moved_products) = match other {
+            Some(_) if !merge => {
+                return Err(format!(
+                    "Já existe a marca {name}; confirme para unir as duas (produtos vão para ela)"
+                ));
+            }
+            Some(other) => {
+                let moved = tx
+                    .execute(
+                        "UPDATE products SET brand_id = ?1 WHERE brand_id = ?2",
+                        params![other, brand_id],
+                    )
+                    .map_err(|e| e.to_string())?;
+                tx.execute(
+                    "DELETE FROM brand_groups WHERE brand_id = ?1",
+                    params![brand_id],
+                )
+                .map_err(|e| e.to_string())?;
+                tx.execute("DELETE FROM brands WHERE id = ?1", params![brand_id])
+                    .map_err(|e| e.to_string())?;
+                (other, moved)
+            }
+            None => {
+                tx.execute(
+                    "UPDATE brands SET name = ?1 WHERE id = ?2",
+                    params![name, brand_id],
+                )
+                .map_err(|e| e.to_string())?;
+                (brand_id, 0)
+            }
+        };
+        seed_brand_groups(&tx).map_err(|e| e.to_string())?;
+        forget_import_fingerprint(&tx).map_err(|e| e.to_string())?;
+        let db_version = get_db_version(&tx).unwrap_or(0) + 1;
+        set_db_version(&tx, db_version).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(RenameBrandResult {
+            brand_id: survivor,
+            merged: survivor != brand_id,
+            moved_products,
+            db_version,
+        })
+    }
+
     fn fetch_brand_groups(conn: &Connection, brand_id: Option<i64>) -> Result<Vec<String>> {
         let mut out = Vec::new();
         if let Some(b) = brand_id {
@@ -807,58 +4805,163 @@ mod core {
             if trimmed.is_empty() {
                 return Ok(None);
             }
-            let found: Option<i64> = conn
-                .query_row(
-                    "SELECT id FROM brands WHERE UPPER(TRIM(name)) = UPPER(TRIM(?1))",
-                    params![trimmed],
-                    |r| r.get(0),
-                )
-                .optional()?;
-            return Ok(found);
+            return find_brand_by_name(conn, trimmed);
         }
         Ok(None)
     }
     #[tauri::command]
-    pub fn get_types_cmd(app: AppHandle, brand_id: Option<i64>) -> Result<Vec<String>, String> {
+    pub fn get_types_cmd(
+        app: AppHandle,
+        brand_id: Option<i64>,
+        with_counts: Option<bool>,
+    ) -> Result<TypesResult, String> {
         let conn =
             open_db(&db_path(&app).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
-        let expr = "UPPER(TRIM(CASE WHEN INSTR(description,' ')>0 THEN SUBSTR(description,1,INSTR(description,' ')-1) ELSE description END))";
-        let sql = if brand_id.is_some() {
-            format!(
-                "SELECT DISTINCT {} AS t FROM products WHERE brand_id = ?1 ORDER BY t",
-                expr
-            )
+        ensure_schema(&conn).map_err(|e| e.to_string())?;
+        let types = fetch_types(&conn, brand_id).map_err(|e| e.to_string())?;
+        Ok(if with_counts.unwrap_or(false) {
+            TypesResult::Counts(types)
         } else {
-            format!("SELECT DISTINCT {} AS t FROM products ORDER BY t", expr)
+            TypesResult::Names(types.into_iter().map(|t| t.name).collect())
+        })
+    }
+
+    pub(crate) fn fetch_types(conn: &Connection, brand_id: Option<i64>) -> Result<Vec<TypeCount>> {
+        let mut sql = String::from("SELECT ptype, COUNT(*) FROM products WHERE ptype <> ''");
+        if brand_id.is_some() {
+            sql.push_str(" AND brand_id = ?1");
+        }
+        sql.push_str(" GROUP BY ptype ORDER BY ptype");
+        let mut stmt = conn.prepare(&sql)?;
+        let map_row = |row: &rusqlite::Row| {
+            Ok(TypeCount {
+                name: row.get(0)?,
+                product_count: row.get(1)?,
+            })
         };
-        let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
-        if let Some(bid) = brand_id {
-            let rows = stmt
-                .query_map(params![bid], |row| row.get::<_, String>(0))
-                .map_err(|e| e.to_string())?;
-            let mut out = Vec::new();
-            for r in rows {
-                if let Ok(t) = r {
-                    if !t.trim().is_empty() {
-                        out.push(t);
-                    }
-                }
-            }
-            Ok(out)
-        } else {
-            let rows = stmt
-                .query_map([], |row| row.get::<_, String>(0))
-                .map_err(|e| e.to_string())?;
-            let mut out = Vec::new();
-            for r in rows {
-                if let Ok(t) = r {
-                    if !t.trim().is_empty() {
-                        out.push(t);
-                    }
-                }
-            }
-            Ok(out)
+        let out = match brand_id {
+            Some(b) => stmt
+                .query_map(params![b], map_row)?
+                .collect::<rusqlite::Result<_>>()?,
+            None => stmt
+                .query_map([], map_row)?
+                .collect::<rusqlite::Result<_>>()?,
+        };
+        Ok(out)
+    }
+
+    pub(crate) fn fetch_category_tree(conn: &Connection) -> Result<Vec<CategoryNode>> {
+        let mut stmt = conn.prepare("SELECT id, parent_id, name FROM categories ORDER BY name")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, Option<i64>>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?;
+        let mut by_parent: HashMap<Option<i64>, Vec<(i64, String)>> = HashMap::new();
+        for r in rows {
+            let (id, parent, name) = r?;
+            by_parent.entry(parent).or_default().push((id, name));
+        }
+        fn build(
+            parent: Option<i64>,
+            by_parent: &HashMap<Option<i64>, Vec<(i64, String)>>,
+        ) -> Vec<CategoryNode> {
+            by_parent
+                .get(&parent)
+                .map(|items| {
+                    items
+                        .iter()
+                        .map(|(id, name)| CategoryNode {
+                            id: *id,
+                            name: name.clone(),
+                            children: build(Some(*id), by_parent),
+                        })
+                        .collect()
+                })
+                .unwrap_or_default()
+        }
+        Ok(build(None, &by_parent))
+    }
+
+    #[tauri::command]
+    pub fn get_category_tree_cmd(app: AppHandle) -> Result<Vec<CategoryNode>, String> {
+        let conn =
+            open_db(&db_path(&app).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+        ensure_schema(&conn).map_err(|e| e.to_string())?;
+        fetch_category_tree(&conn).map_err(|e| e.to_string())
+    }
+
+    pub(crate) fn list_tags(conn: &Connection) -> Result<Vec<Tag>> {
+        let mut stmt = conn.prepare("SELECT id, name FROM tags ORDER BY name")?;
+        let rows = stmt.query_map([], |row| {
+            Ok(Tag {
+                id: row.get(0)?,
+                name: row.get(1)?,
+            })
+        })?;
+        let mut out = Vec::new();
+        for r in rows {
+            out.push(r?);
+        }
+        Ok(out)
+    }
+
+    pub(crate) fn add_product_tag(conn: &Connection, product_id: i64, name: &str) -> Result<Tag> {
+        let clean = name.trim();
+        if clean.is_empty() {
+            anyhow::bail!("nome da tag vazio");
         }
+        conn.execute(
+            "INSERT OR IGNORE INTO tags(name) VALUES(?1)",
+            params![clean],
+        )?;
+        let tag = conn.query_row(
+            "SELECT id, name FROM tags WHERE name = ?1",
+            params![clean],
+            |row| {
+                Ok(Tag {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                })
+            },
+        )?;
+        conn.execute(
+            "INSERT OR IGNORE INTO product_tags(product_id, tag_id) VALUES(?1, ?2)",
+            params![product_id, tag.id],
+        )?;
+        Ok(tag)
+    }
+
+    #[tauri::command]
+    pub fn list_tags_cmd(app: AppHandle) -> Result<Vec<Tag>, String> {
+        let conn =
+            open_db(&db_path(&app).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+        ensure_schema(&conn).map_err(|e| e.to_string())?;
+        list_tags(&conn).map_err(|e| e.to_string())
+    }
+
+    #[tauri::command]
+    pub fn add_tag_cmd(app: AppHandle, product_id: i64, name: String) -> Result<Tag, String> {
+        let conn =
+            open_db(&db_path(&app).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+        ensure_schema(&conn).map_err(|e| e.to_string())?;
+        add_product_tag(&conn, product_id, &name).map_err(|e| e.to_string())
+    }
+
+    #[tauri::command]
+    pub fn remove_tag_cmd(app: AppHandle, product_id: i64, tag_id: i64) -> Result<bool, String> {
+        let conn =
+            open_db(&db_path(&app).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+        ensure_schema(&conn).map_err(|e| e.to_string())?;
+        let removed = conn
+            .execute(
+                "DELETE FROM product_tags WHERE product_id = ?1 AND tag_id = ?2",
+                params![product_id, tag_id],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(removed > 0)
     }
 
     #[derive(Debug, Clone)]
@@ -907,14 +5010,504 @@ mod core {
     }
 
     #[tauri::command]
-    pub fn search_products_cmd(
+    pub fn search_products_cmd(app: AppHandle, params: SearchParams) -> Result<SearchPage, String> {
+        let conn =
+            open_db(&db_path(&app).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+        let max_limit = search_max_limit(std::env::var(SEARCH_MAX_LIMIT_ENV).ok());
+        let (page, explain) =
+            retry_busy(|| search_page(&conn, &params, max_limit).map_err(anyhow::Error::msg))?;
+        // O plano vai por evento para não mudar o formato da resposta.
+        if let Some(explain) = explain {
+            let _ = app.emit("search_explain", &explain);
+        }
+        Ok(page)
+    }
+
+    /// Lote máximo por `fetch_cursor_cmd` (e por consulta de ids).
+    const CURSOR_MAX_BATCH: usize = 500;
+    /// Cursores abertos ao mesmo tempo; abrir mais um descarta o mais antigo.
+    const MAX_OPEN_CURSORS: usize = 16;
+
+    /// Estado gerenciado pelo Tauri: buscas grandes entregues em lotes.
+    #[derive(Default)]
+    pub struct SearchCursors {
+        next_id: std::sync::atomic::AtomicU64,
+        open: Mutex<HashMap<u64, SearchCursor>>,
+    }
+
+    /// Os ids do resultado ficam guardados na abertura; cada lote busca só os seus itens,
+    /// então a ordem não muda entre lotes nem se o banco for sincronizado no meio.
+    struct SearchCursor {
+        params: SearchParams,
+        ids: Vec<i64>,
+        pos: usize,
+    }
+
+    impl SearchCursors {
+        fn open(&self, conn: &Connection, params: &SearchParams) -> Result<u64, String> {
+            let (items, _) = search_products_explained(conn, params)?;
+            let cursor = SearchCursor {
+                params: SearchParams {
+                    limit: None,
+                    offset: None,
+                    explain: None,
+                    fuzzy_fallback: None,
+                    ..params.clone()
+                },
+                ids: items.into_iter().map(|item| item.id).collect(),
+                pos: 0,
+            };
+            let id = self
+                .next_id
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst)
+                + 1;
+            let mut open = self.open.lock().map_err(|e| e.to_string())?;
+            if open.len() >= MAX_OPEN_CURSORS {
+                if let Some(oldest) = open.keys().min().copied() {
+                    open.remove(&oldest);
+                }
+            }
+            open.insert(id, cursor);
+            Ok(id)
+        }
+
+        /// Próximos `n` itens (até `CURSOR_MAX_BATCH`); lista vazia quando acabou.
+        fn fetch(
+            &self,
+            conn: &Connection,
+            id: u64,
+            n: usize,
+        ) -> Result<Vec<ProductListItem>, String> {
+            let (params, batch) = {
+                let mut open = self.open.lock().map_err(|e| e.to_string())?;
+                let cursor = open
+                    .get_mut(&id)
+                    .ok_or_else(|| "Cursor de busca inexistente ou já fechado".to_string())?;
+                let end = (cursor.pos + n.clamp(1, CURSOR_MAX_BATCH)).min(cursor.ids.len());
+                let batch = cursor.ids[cursor.pos..end].to_vec();
+                cursor.pos = end;
+                (cursor.params.clone(), batch)
+            };
+            if batch.is_empty() {
+                return Ok(Vec::new());
+            }
+            let rank: HashMap<i64, usize> =
+                batch.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+            let (mut items, _) = search_products_explained(
+                conn,
+                &SearchParams {
+                    only_ids: Some(batch),
+                    ..params
+                },
+            )?;
+            items.sort_by_key(|item| rank.get(&item.id).copied().unwrap_or(usize::MAX));
+            Ok(items)
+        }
+
+        fn close(&self, id: u64) -> bool {
+            self.open
+                .lock()
+                .map(|mut open| open.remove(&id).is_some())
+                .unwrap_or(false)
+        }
+    }
+
+    /// Abre a busca no servidor e devolve o id para `fetch_cursor_cmd`; sem teto de `limit`.
+    #[tauri::command]
+    pub fn open_search_cursor_cmd(
+        app: AppHandle,
+        state: tauri::State<'_, SearchCursors>,
+        params: SearchParams,
+    ) -> Result<u64, String> {
+        let conn =
+            open_db(&db_path(&app).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+        retry_busy(|| state.open(&conn, &params).map_err(anyhow::Error::msg))
+    }
+
+    #[tauri::command]
+    pub fn fetch_cursor_cmd(
+        app: AppHandle,
+        state: tauri::State<'_, SearchCursors>,
+        cursor_id: u64,
+        n: usize,
+    ) -> Result<Vec<ProductListItem>, String> {
+        let conn =
+            open_db(&db_path(&app).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+        retry_busy(|| state.fetch(&conn, cursor_id, n).map_err(anyhow::Error::msg))
+    }
+
+    /// `false` se o cursor já não existia.
+    #[tauri::command]
+    pub fn close_cursor_cmd(state: tauri::State<'_, SearchCursors>, cursor_id: u64) -> bool {
+        state.close(cursor_id)
+    }
+
+    /// Só os códigos que batem no filtro, em ordem de código (impressão de etiquetas).
+    #[tauri::command]
+    pub fn list_codes_cmd(
         app: AppHandle,
         params: SearchParams,
+        limit: Option<i64>,
+        offset: Option<i64>,
+    ) -> Result<Vec<String>, String> {
+        let conn =
+            open_db(&db_path(&app).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+        retry_busy(|| list_codes(&conn, &params, limit, offset).map_err(anyhow::Error::msg))
+    }
+
+    /// `sort`/`limit`/`offset` de `params` são ignorados; a página vem de `limit`/`offset`
+    /// (sem `limit` = todos).
+    pub(crate) fn list_codes(
+        conn: &Connection,
+        params: &SearchParams,
+        limit: Option<i64>,
+        offset: Option<i64>,
+    ) -> Result<Vec<String>, String> {
+        let SearchFilter {
+            where_sql,
+            mut binds,
+            ..
+        } = search_filter(conn, params)?;
+        let limit = binds.bind("limit", limit.filter(|l| *l > 0).unwrap_or(-1));
+        let offset = binds.bind("offset", offset.filter(|o| *o > 0).unwrap_or(0));
+        let sql = format!("SELECT p.code FROM products p JOIN brands b ON b.id=p.brand_id{where_sql} ORDER BY p.code LIMIT {limit} OFFSET {offset}");
+        let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+        let codes = stmt
+            .query_map(binds.as_params().as_slice(), |row| row.get::<_, String>(0))
+            .map_err(|e| e.to_string())?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(|e| e.to_string())?;
+        Ok(codes)
+    }
+
+    fn search_max_limit(env_value: Option<String>) -> i64 {
+        env_value
+            .and_then(|v| v.trim().parse::<i64>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(SEARCH_MAX_LIMIT)
+    }
+
+    /// Aplica o padrão/teto de `limit` antes de buscar; `limit <= 0` conta como ausente.
+    fn search_page(
+        conn: &Connection,
+        params: &SearchParams,
+        max_limit: i64,
+    ) -> Result<(SearchPage, Option<SearchExplain>), String> {
+        let requested = params.limit.filter(|l| *l > 0);
+        let limit = requested.unwrap_or(SEARCH_DEFAULT_LIMIT).min(max_limit);
+        let limited = SearchParams {
+            limit: Some(limit),
+            ..params.clone()
+        };
+        let (mut items, explain) = search_products_explained(conn, &limited)?;
+        let mut suggestions = false;
+        let first_page = params.offset.is_none_or(|o| o <= 0);
+        if items.is_empty() && first_page && params.fuzzy_fallback.unwrap_or(false) {
+            if let Some(query) = params.code_query.as_deref() {
+                items = fuzzy_code_suggestions(conn, &limited, query)?;
+                suggestions = !items.is_empty();
+            }
+        }
+        Ok((
+            SearchPage {
+                items,
+                limit,
+                max_limit,
+                limit_clamped: requested.is_none_or(|r| r > max_limit),
+                suggestions,
+            },
+            explain,
+        ))
+    }
+
+    /// Códigos examinados por busca aproximada (mesmo primeiro caractere, tamanho parecido).
+    const FUZZY_CANDIDATE_LIMIT: usize = 5000;
+    const FUZZY_MAX_SUGGESTIONS: usize = 20;
+
+    /// Distância aceita: 1 edição em códigos curtos, 2 a partir de 6 caracteres.
+    fn fuzzy_max_distance(key: &str) -> usize {
+        if key.chars().count() >= 6 {
+            2
+        } else {
+            1
+        }
+    }
+
+    fn levenshtein(a: &str, b: &str) -> usize {
+        let b: Vec<char> = b.chars().collect();
+        let mut prev: Vec<usize> = (0..=b.len()).collect();
+        for (i, ca) in a.chars().enumerate() {
+            let mut cur = vec![i + 1; b.len() + 1];
+            for (j, cb) in b.iter().enumerate() {
+                let cost = usize::from(ca != *cb);
+                cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+            }
+            prev = cur;
+        }
+        prev[b.len()]
+    }
+
+    /// Segunda passada: compara `code_norm` com a chave da consulta em Rust e refaz a busca
+    /// (com os demais filtros) só para os mais próximos, sem o code_query.
+    fn fuzzy_code_suggestions(
+        conn: &Connection,
+        params: &SearchParams,
+        query: &str,
     ) -> Result<Vec<ProductListItem>, String> {
+        let key = crate::importer::code_search_key(query);
+        let Some(first) = key.chars().next() else {
+            return Ok(Vec::new());
+        };
+        let max_distance = fuzzy_max_distance(&key);
+        let len = key.chars().count() as i64;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, code_norm FROM products
+                 WHERE code_norm LIKE ?1 AND length(code_norm) BETWEEN ?2 AND ?3
+                 LIMIT ?4",
+            )
+            .map_err(|e| e.to_string())?;
+        let mut scored: Vec<(usize, String, i64)> = stmt
+            .query_map(
+                params![
+                    format!("{first}%"),
+                    len - max_distance as i64,
+                    len + max_distance as i64,
+                    FUZZY_CANDIDATE_LIMIT as i64
+                ],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+            )
+            .map_err(|e| e.to_string())?
+            .filter_map(|row| row.ok())
+            .filter_map(|(id, code)| {
+                let d = levenshtein(&key, &code);
+                (d > 0 && d <= max_distance).then_some((d, code, id))
+            })
+            .collect();
+        if scored.is_empty() {
+            return Ok(Vec::new());
+        }
+        scored.sort();
+        scored.truncate(FUZZY_MAX_SUGGESTIONS);
+        let rank: HashMap<i64, usize> = scored
+            .iter()
+            .enumerate()
+            .map(|(i, (_, _, id))| (*id, i))
+            .collect();
+        let second = SearchParams {
+            code_query: None,
+            fuzzy_fallback: None,
+            only_ids: Some(scored.iter().map(|(_, _, id)| *id).collect()),
+            ..params.clone()
+        };
+        let (mut items, _) = search_products_explained(conn, &second)?;
+        items.sort_by_key(|item| rank.get(&item.id).copied().unwrap_or(usize::MAX));
+        Ok(items)
+    }
+
+    /// Contador da tela inicial, sem passar pela busca.
+    #[tauri::command]
+    pub fn get_product_count_cmd(app: AppHandle, brand_id: Option<i64>) -> Result<i64, String> {
+        let conn =
+            open_db(&db_path(&app).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+        retry_busy(|| product_count(&conn, brand_id))
+    }
+
+    pub(crate) fn product_count(conn: &Connection, brand_id: Option<i64>) -> Result<i64> {
+        Ok(conn.query_row(
+            "SELECT COUNT(*) FROM products WHERE ?1 IS NULL OR brand_id = ?1",
+            params![brand_id],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Veículos do produto `p` em ordem alfabética e sem repetição, separados por vírgula.
+    const PRODUCT_VEHICLES_AGG: &str = "(SELECT group_concat(DISTINCT v2.name ORDER BY v2.name) FROM product_vehicles pv2 JOIN vehicles v2 ON v2.id=pv2.vehicle_id WHERE pv2.product_id=p.id)";
+
+    /// "Tudo que serve neste carro": só o vínculo direto em product_vehicles, sem o
+    /// casamento por nome do `search_products`.
+    #[tauri::command]
+    pub fn get_products_by_vehicle_cmd(
+        app: AppHandle,
+        vehicle_id: i64,
+        limit: Option<i64>,
+        offset: Option<i64>,
+    ) -> Result<ProductPage, String> {
         let conn =
             open_db(&db_path(&app).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
-        // Agrega veículos sem filtrar montadora para não bagunçar a ordem de parâmetros.
-        let mut sql = String::from("SELECT p.id, p.code, p.description, b.name, (SELECT group_concat(DISTINCT v2.name) FROM product_vehicles pv2 JOIN vehicles v2 ON v2.id=pv2.vehicle_id WHERE pv2.product_id=p.id) AS vehicles FROM products p JOIN brands b ON b.id=p.brand_id");
+        retry_busy(|| products_by_vehicle(&conn, vehicle_id, limit, offset))
+    }
+
+    pub(crate) fn products_by_vehicle(
+        conn: &Connection,
+        vehicle_id: i64,
+        limit: Option<i64>,
+        offset: Option<i64>,
+    ) -> Result<ProductPage> {
+        let total: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM product_vehicles WHERE vehicle_id = ?1",
+            params![vehicle_id],
+            |row| row.get(0),
+        )?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT p.id, p.code, p.description, b.name,
+                    {PRODUCT_VEHICLES_AGG},
+                    p.stock, p.brand_id
+             FROM product_vehicles pv
+             JOIN products p ON p.id = pv.product_id
+             JOIN brands b ON b.id = p.brand_id
+             WHERE pv.vehicle_id = ?1
+             ORDER BY b.name, p.description, p.code
+             LIMIT ?2 OFFSET ?3"
+        ))?;
+        let items = stmt
+            .query_map(
+                params![
+                    vehicle_id,
+                    limit.filter(|l| *l > 0).unwrap_or(-1),
+                    offset.unwrap_or(0).max(0)
+                ],
+                |row| {
+                    Ok(ProductListItem {
+                        id: row.get(0)?,
+                        code: row.get(1)?,
+                        description: row.get(2)?,
+                        brand: row.get(3)?,
+                        brand_id: row.get(6)?,
+                        vehicles: row.get(4)?,
+                        stock: row.get(5)?,
+                        highlights: Vec::new(),
+                    })
+                },
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(ProductPage { items, total })
+    }
+
+    /// Parâmetros nomeados (`:nome`) para SQL montado dinamicamente.
+    #[derive(Default)]
+    struct NamedParams(Vec<(String, rusqlite::types::Value)>);
+
+    impl NamedParams {
+        /// Registra o valor e devolve o marcador para interpolar no SQL.
+        fn bind(&mut self, name: &str, value: impl Into<rusqlite::types::Value>) -> String {
+            let key = format!(":{name}");
+            self.0.push((key.clone(), value.into()));
+            key
+        }
+
+        fn bind_opt(&mut self, name: &str, value: Option<String>) -> String {
+            match value {
+                Some(v) => self.bind(name, v),
+                None => self.bind(name, rusqlite::types::Value::Null),
+            }
+        }
+
+        fn as_params(&self) -> Vec<(&str, &dyn rusqlite::ToSql)> {
+            self.0
+                .iter()
+                .map(|(k, v)| (k.as_str(), v as &dyn rusqlite::ToSql))
+                .collect()
+        }
+    }
+
+    /// Campos aceitos em `search_fields` e o teste SQL de cada um (`{like}` = termo).
+    const SEARCH_FIELDS: &[(&str, &str)] = &[
+        ("code", "UPPER(p.code) LIKE {like}"),
+        ("description", "UPPER(p.description) LIKE {like}"),
+        ("oem", "UPPER(COALESCE(p.oem,'')) LIKE {like}"),
+        ("similar", "UPPER(COALESCE(p.similar,'')) LIKE {like}"),
+        ("vehicle", "EXISTS (SELECT 1 FROM product_vehicles pv3 JOIN vehicles v3 ON v3.id=pv3.vehicle_id WHERE pv3.product_id=p.id AND UPPER(v3.name) LIKE {like})"),
+    ];
+
+    /// Ordenações aceitas; o texto do usuário nunca entra no SQL, só a chave.
+    const SEARCH_SORTS: &[(&str, &str)] = &[
+        ("brand", "b.name, p.description"),
+        ("code", "p.code, b.name"),
+        ("description", "p.description, b.name"),
+        ("stock", "COALESCE(p.stock, 0) DESC, b.name, p.description"),
+    ];
+
+    fn search_order_by(sort: Option<&str>) -> Result<&'static str, String> {
+        let key = sort
+            .map(|s| s.trim().to_ascii_lowercase())
+            .unwrap_or_default();
+        if key.is_empty() {
+            return Ok(SEARCH_SORTS[0].1);
+        }
+        SEARCH_SORTS
+            .iter()
+            .find(|(name, _)| *name == key)
+            .map(|(_, sql)| *sql)
+            .ok_or_else(|| format!("Ordenação inválida: {}", sort.unwrap_or_default()))
+    }
+
+    fn selected_search_fields(
+        requested: Option<&Vec<String>>,
+    ) -> Result<Vec<&'static (&'static str, &'static str)>, String> {
+        let mut out: Vec<&'static (&'static str, &'static str)> = Vec::new();
+        for name in requested.into_iter().flatten() {
+            let key = name.trim().to_ascii_lowercase();
+            let Some(field) = SEARCH_FIELDS.iter().find(|(field, _)| *field == key) else {
+                return Err(format!("Campo de busca inválido: {}", name));
+            };
+            if !out.contains(&field) {
+                out.push(field);
+            }
+        }
+        if out.is_empty() {
+            out = SEARCH_FIELDS.iter().collect();
+        }
+        Ok(out)
+    }
+
+    /// Ocorrências (sem sobreposição) de cada termo em `text`, comparando com acentos dobrados.
+    /// `fold_char` mapeia 1 caractere para 1, então as posições valem para o texto original.
+    fn match_ranges(field: &str, text: &str, terms: &[String]) -> Vec<MatchRange> {
+        let hay: Vec<char> = text.chars().map(crate::importer::fold_char).collect();
+        let mut out = Vec::new();
+        for term in terms {
+            let needle: Vec<char> = term.chars().map(crate::importer::fold_char).collect();
+            if needle.is_empty() || needle.len() > hay.len() {
+                continue;
+            }
+            let mut i = 0;
+            while i + needle.len() <= hay.len() {
+                if hay[i..i + needle.len()] == needle[..] {
+                    out.push(MatchRange {
+                        field: field.to_string(),
+                        start: i,
+                        len: needle.len(),
+                    });
+                    i += needle.len();
+                } else {
+                    i += 1;
+                }
+            }
+        }
+        out.sort_by_key(|m| m.start);
+        out
+    }
+
+    // O comando usa `search_products_explained`; atalho para quem só quer os itens.
+    #[cfg(test)]
+    pub(crate) fn search_products(
+        conn: &Connection,
+        params: &SearchParams,
+    ) -> Result<Vec<ProductListItem>, String> {
+        search_products_explained(conn, params).map(|(items, _)| items)
+    }
+
+    /// Filtros da busca já montados: `where_sql` vem com o " WHERE " (ou vazio) e os valores em `binds`.
+    struct SearchFilter {
+        where_sql: String,
+        binds: NamedParams,
+        parsed_query: Option<ParsedSearchQuery>,
+        fields: Vec<&'static (&'static str, &'static str)>,
+    }
+
+    /// Monta o WHERE de `SearchParams`; compartilhado pela listagem e por `list_codes`.
+    fn search_filter(conn: &Connection, params: &SearchParams) -> Result<SearchFilter, String> {
         // Quando filtra por veículo, precisamos do nome para permitir match parcial no texto.
         let vehicle_name: Option<String> = if let Some(vid) = params.vehicle_id {
             conn.query_row(
@@ -936,48 +5529,143 @@ mod core {
                 .map(|s| s.to_ascii_uppercase());
         }
 
+        // Cada cláusula registra os próprios valores por nome, sem depender da ordem dos `?`.
         let mut where_clauses: Vec<String> = Vec::new();
-        if params.brand_id.is_some() {
-            where_clauses.push("p.brand_id = ?".into());
+        let mut binds = NamedParams::default();
+        if let Some(b) = params.brand_id {
+            where_clauses.push(format!("p.brand_id = {}", binds.bind("brand_id", b)));
+        }
+        if let Some(g) = params.group.as_ref().filter(|s| !s.trim().is_empty()) {
+            where_clauses.push(format!(
+                "UPPER(COALESCE(p.pgroup,'')) = {}",
+                binds.bind("group", g.to_ascii_uppercase())
+            ));
+        }
+        if let Some(mk) = params.make.as_ref().filter(|s| !s.trim().is_empty()) {
+            let make = binds.bind("make", mk.to_ascii_uppercase());
+            where_clauses.push(format!("EXISTS (SELECT 1 FROM product_vehicles pvm JOIN vehicles vm ON vm.id=pvm.vehicle_id WHERE pvm.product_id=p.id AND UPPER(TRIM(COALESCE(vm.make,''))) = {make})"));
+        }
+        if let Some(v) = params.vehicle_id {
+            // Match por id e também por nome do veículo (completo ou token inicial) em qualquer posição.
+            let upper = vehicle_name.as_ref().map(|n| n.to_ascii_uppercase());
+            let vid = binds.bind("vehicle_id", v);
+            let name_like = binds.bind_opt("vehicle_name_like", upper.map(|u| format!("%{u}%")));
+            let token_like = binds.bind_opt(
+                "vehicle_token_like",
+                vehicle_token.as_ref().map(|t| format!("%{t}%")),
+            );
+            where_clauses.push(format!(
+                "EXISTS (SELECT 1 FROM product_vehicles pv JOIN vehicles v2 ON v2.id=pv.vehicle_id WHERE pv.product_id=p.id AND (pv.vehicle_id = {vid} OR ({name_like} IS NOT NULL AND UPPER(v2.name) LIKE {name_like}) OR ({token_like} IS NOT NULL AND UPPER(v2.name) LIKE {token_like})))"
+            ));
         }
-        if params
-            .group
+        if let Some(q) = params
+            .vehicle_query
             .as_ref()
-            .map(|s| !s.trim().is_empty())
-            .unwrap_or(false)
+            .map(|s| s.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|s| !s.is_empty())
+        {
+            let like = binds.bind("vehicle_query", format!("%{}%", q.to_uppercase()));
+            where_clauses.push(format!("EXISTS (SELECT 1 FROM product_vehicles pvq JOIN vehicles vq ON vq.id=pvq.vehicle_id WHERE pvq.product_id=p.id AND UPPER(vq.name) LIKE {like})"));
+        }
+        if let Some(c) = params.category_id {
+            // Categoria pai também traz os produtos das subcategorias.
+            let cat = binds.bind("category_id", c);
+            where_clauses.push(format!(
+                "p.category_id IN (SELECT id FROM categories WHERE id = {cat} OR parent_id = {cat})"
+            ));
+        }
+        let tag_ids: Vec<i64> = params
+            .tag_ids
+            .iter()
+            .flatten()
+            .copied()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        if !tag_ids.is_empty() {
+            // Produto precisa ter todas as tags pedidas.
+            let placeholders = tag_ids
+                .iter()
+                .enumerate()
+                .map(|(i, t)| binds.bind(&format!("tag{i}"), *t))
+                .collect::<Vec<_>>()
+                .join(",");
+            let count = binds.bind("tag_count", tag_ids.len() as i64);
+            where_clauses.push(format!(
+                "p.id IN (SELECT product_id FROM product_tags WHERE tag_id IN ({placeholders}) GROUP BY product_id HAVING COUNT(DISTINCT tag_id) = {count})"
+            ));
+        }
+        if let Some(prefix) = params
+            .code_prefix
+            .as_deref()
+            .map(crate::importer::code_search_key)
+            .filter(|k| !k.is_empty())
         {
-            where_clauses.push("UPPER(COALESCE(p.pgroup,'')) = ?".into());
+            // A chave só tem letras/dígitos, então não há curingas do LIKE a escapar.
+            let prefix = binds.bind("code_prefix", format!("{prefix}%"));
+            where_clauses.push(format!("p.code_norm LIKE {prefix}"));
+        }
+        if let Some(ids) = params.only_ids.as_ref() {
+            let placeholders = ids
+                .iter()
+                .enumerate()
+                .map(|(i, id)| binds.bind(&format!("only_id{i}"), *id))
+                .collect::<Vec<_>>()
+                .join(",");
+            where_clauses.push(if placeholders.is_empty() {
+                "0".into()
+            } else {
+                format!("p.id IN ({placeholders})")
+            });
         }
-        if params
-            .make
-            .as_ref()
-            .map(|s| !s.trim().is_empty())
-            .unwrap_or(false)
+        if let Some(q) = params
+            .image_filename_query
+            .as_deref()
+            .map(|s| s.trim().replace('\\', "/"))
+            .filter(|s| !s.is_empty())
         {
-            where_clauses.push("EXISTS (SELECT 1 FROM product_vehicles pvm JOIN vehicles vm ON vm.id=pvm.vehicle_id WHERE pvm.product_id=p.id AND UPPER(TRIM(COALESCE(vm.make,''))) = ?)".into());
-        }
-        if params.vehicle_id.is_some() {
-            // Match por id e também por nome do veículo em qualquer posição.
-            where_clauses.push(
-                "EXISTS (SELECT 1 FROM product_vehicles pv JOIN vehicles v2 ON v2.id=pv.vehicle_id WHERE pv.product_id=p.id AND (pv.vehicle_id = ? OR (? IS NOT NULL AND UPPER(v2.name) LIKE ?) OR (? IS NOT NULL AND UPPER(v2.name) LIKE ?)))"
-                    .into(),
-            );
+            // "_" é comum em nomes de arquivo ("7111_2.jpg"); casa literalmente, não como curinga.
+            let escaped = q
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+            let like = binds.bind("image_filename", format!("%{escaped}%"));
+            where_clauses.push(format!(
+                "EXISTS (SELECT 1 FROM images i WHERE i.product_id=p.id AND i.filename LIKE {like} ESCAPE '\\')"
+            ));
+        }
+        if !params.include_inactive.unwrap_or(false) {
+            // NULL = planilha sem STATUS; conta como ativo.
+            where_clauses.push("COALESCE(p.active, 1) <> 0".into());
+        }
+        if params.in_stock_only.unwrap_or(false) {
+            // Estoque nulo = desconhecido; só entra quem tem saldo positivo informado.
+            where_clauses.push("COALESCE(p.stock, 0) > 0".into());
         }
         let parsed_query = params
             .code_query
             .as_ref()
             .filter(|s| !s.trim().is_empty())
             .and_then(|s| parse_search_query(s));
+        let fields = selected_search_fields(params.search_fields.as_ref())?;
         if let Some(parsed) = parsed_query.as_ref() {
-            for _ in parsed.terms.iter() {
-                where_clauses.push(
-                    "(UPPER(p.code) LIKE ? OR UPPER(p.description) LIKE ? OR UPPER(COALESCE(p.oem,'')) LIKE ? OR UPPER(COALESCE(p.similar,'')) LIKE ? OR EXISTS (SELECT 1 FROM product_vehicles pv3 JOIN vehicles v3 ON v3.id=pv3.vehicle_id WHERE pv3.product_id=p.id AND UPPER(v3.name) LIKE ?))"
-                        .into(),
-                );
+            for (i, term) in parsed.terms.iter().enumerate() {
+                let like = binds.bind(&format!("term{i}"), format!("%{}%", term));
+                let checks = fields
+                    .iter()
+                    .map(|(_, sql)| sql.replace("{like}", &like))
+                    .collect::<Vec<_>>()
+                    .join(" OR ");
+                where_clauses.push(format!("({checks})"));
             }
-            for aliases in parsed.year_aliases.iter() {
-                let year_checks = std::iter::repeat("(',' || COALESCE(vy.years,'') || ',') LIKE ?")
-                    .take(aliases.len())
+            for (i, aliases) in parsed.year_aliases.iter().enumerate() {
+                let year_checks = aliases
+                    .iter()
+                    .enumerate()
+                    .map(|(j, alias)| {
+                        let like = binds.bind(&format!("year{i}_{j}"), format!("%,{},%", alias));
+                        format!("(',' || COALESCE(vy.years,'') || ',') LIKE {like}")
+                    })
                     .collect::<Vec<_>>()
                     .join(" OR ");
                 where_clauses.push(format!(
@@ -985,63 +5673,46 @@ mod core {
                 ));
             }
         }
-        if !where_clauses.is_empty() {
-            sql.push_str(" WHERE ");
-            sql.push_str(&where_clauses.join(" AND "));
-        }
-        sql.push_str(" ORDER BY b.name, p.description");
-        if let Some(limit) = params.limit {
-            sql.push_str(&format!(" LIMIT {}", limit));
-        }
-
-        let mut values: Vec<rusqlite::types::Value> = Vec::new();
-        if let Some(b) = params.brand_id {
-            values.push(b.into());
-        }
-        if let Some(g) = params.group.as_ref().filter(|s| !s.trim().is_empty()) {
-            values.push(g.to_ascii_uppercase().into());
-        }
-        if let Some(mk) = params.make.as_ref().filter(|s| !s.trim().is_empty()) {
-            values.push(mk.to_ascii_uppercase().into());
-        }
-        if let Some(v) = params.vehicle_id {
-            values.push(v.into());
-            // Passa o nome completo e tambÇ¸m o token inicial para permitir LIKE mais amplo
-            if let Some(ref name) = vehicle_name {
-                let upper = name.to_ascii_uppercase();
-                values.push(upper.clone().into()); // nome completo para ? IS NOT NULL
-                values.push(format!("%{}%", upper).into()); // match em qualquer posiÇõÇœo
-            } else {
-                values.push(rusqlite::types::Value::Null);
-                values.push(rusqlite::types::Value::Null);
-            }
-            if let Some(ref token) = vehicle_token {
-                values.push(token.clone().into()); // token para ? IS NOT NULL
-                values.push(format!("%{}%", token).into());
-            } else {
-                values.push(rusqlite::types::Value::Null);
-                values.push(rusqlite::types::Value::Null);
-            }
-        }
-        if let Some(parsed) = parsed_query.as_ref() {
-            for term in parsed.terms.iter() {
-                let like = format!("%{}%", term);
-                values.push(like.clone().into()); // code
-                values.push(like.clone().into()); // description
-                values.push(like.clone().into()); // oem
-                values.push(like.clone().into()); // similar
-                values.push(like.into()); // vehicle name
-            }
-            for aliases in parsed.year_aliases.iter() {
-                for alias in aliases {
-                    values.push(format!("%,{},%", alias).into());
-                }
-            }
-        }
+        let where_sql = if where_clauses.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", where_clauses.join(" AND "))
+        };
+        Ok(SearchFilter {
+            where_sql,
+            binds,
+            parsed_query,
+            fields,
+        })
+    }
 
+    /// Como `search_products`; com `explain` também devolve o EXPLAIN QUERY PLAN e o tempo.
+    pub(crate) fn search_products_explained(
+        conn: &Connection,
+        params: &SearchParams,
+    ) -> Result<(Vec<ProductListItem>, Option<SearchExplain>), String> {
+        let SearchFilter {
+            where_sql,
+            mut binds,
+            parsed_query,
+            fields,
+        } = search_filter(conn, params)?;
+        // `vehicles` lista todos os veículos do produto, mesmo com filtro de montadora.
+        let mut sql = format!("SELECT p.id, p.code, p.description, b.name, {PRODUCT_VEHICLES_AGG} AS vehicles, p.stock, p.brand_id FROM products p JOIN brands b ON b.id=p.brand_id{where_sql}");
+        sql.push_str(" ORDER BY ");
+        sql.push_str(search_order_by(params.sort.as_deref())?);
+        let offset = params.offset.filter(|o| *o > 0);
+        if params.limit.is_some() || offset.is_some() {
+            // SQLite exige LIMIT antes de OFFSET; -1 = sem limite.
+            let limit = binds.bind("limit", params.limit.unwrap_or(-1));
+            let offset = binds.bind("offset", offset.unwrap_or(0));
+            sql.push_str(&format!(" LIMIT {limit} OFFSET {offset}"));
+        }
+
+        let started = std::time::Instant::now();
         let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
         let mut rows = stmt
-            .query(rusqlite::params_from_iter(values))
+            .query(binds.as_params().as_slice())
             .map_err(|e| e.to_string())?;
         let mut out = Vec::new();
         while let Some(row) = rows.next().map_err(|e| e.to_string())? {
@@ -1050,10 +5721,51 @@ mod core {
                 code: row.get(1).map_err(|e| e.to_string())?,
                 description: row.get(2).map_err(|e| e.to_string())?,
                 brand: row.get(3).map_err(|e| e.to_string())?,
+                brand_id: row.get(6).map_err(|e| e.to_string())?,
                 vehicles: row.get(4).ok(),
+                stock: row.get(5).map_err(|e| e.to_string())?,
+                highlights: Vec::new(),
             });
         }
-        Ok(out)
+        drop(rows);
+        let elapsed_ms = started.elapsed().as_secs_f64() * 1000.0;
+        let explain = if params.explain.unwrap_or(false) {
+            let mut stmt = conn
+                .prepare(&format!("EXPLAIN QUERY PLAN {sql}"))
+                .map_err(|e| e.to_string())?;
+            // Colunas: id, parent, notused, detail.
+            let plan = stmt
+                .query_map(binds.as_params().as_slice(), |row| row.get::<_, String>(3))
+                .map_err(|e| e.to_string())?
+                .collect::<rusqlite::Result<Vec<_>>>()
+                .map_err(|e| e.to_string())?;
+            Some(SearchExplain { plan, elapsed_ms })
+        } else {
+            None
+        };
+        if params.highlight.unwrap_or(false) {
+            if let Some(parsed) = parsed_query.as_ref() {
+                let wants = |name: &str| fields.iter().any(|(f, _)| *f == name);
+                for item in out.iter_mut() {
+                    let mut ranges = Vec::new();
+                    if wants("code") {
+                        ranges.extend(match_ranges("code", &item.code, &parsed.terms));
+                    }
+                    if wants("description") {
+                        ranges.extend(match_ranges(
+                            "description",
+                            &item.description,
+                            &parsed.terms,
+                        ));
+                    }
+                    if let Some(vehicles) = item.vehicles.as_deref().filter(|_| wants("vehicle")) {
+                        ranges.extend(match_ranges("vehicles", vehicles, &parsed.terms));
+                    }
+                    item.highlights = ranges;
+                }
+            }
+        }
+        Ok((out, explain))
     }
 
     fn normalized_filter_values(values: Option<&Vec<String>>) -> Vec<String> {
@@ -1086,17 +5798,12 @@ mod core {
     }
 
     fn is_print_image_file(path: &Path) -> bool {
-        let lower = path
-            .file_name()
-            .and_then(|name| name.to_str())
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
             .unwrap_or("")
             .to_ascii_lowercase();
-        lower.ends_with(".png")
-            || lower.ends_with(".jpg")
-            || lower.ends_with(".jpeg")
-            || lower.ends_with(".webp")
-            || lower.ends_with(".bmp")
-            || lower.ends_with(".cimg")
+        ext == "cimg" || DEFAULT_IMAGE_EXTENSIONS.contains(&ext.as_str())
     }
 
     fn print_image_priority(rel: &str) -> i32 {
@@ -1325,6 +6032,7 @@ mod core {
         let conn =
             open_db(&db_path(&app).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
         migrate(&conn).map_err(|e| e.to_string())?;
+        register_launch_sql_fn(&conn, launch_tokens()).map_err(|e| e.to_string())?;
 
         let vehicle_label_expr = "UPPER(TRIM(CASE WHEN INSTR(REPLACE(v.name,'/',' '),' ')>0 THEN SUBSTR(REPLACE(v.name,'/',' '),1,INSTR(REPLACE(v.name,'/',' '),' ')-1) ELSE v.name END))";
         let mut sql = String::from(
@@ -1342,7 +6050,7 @@ mod core {
                     SELECT i.filename
                     FROM images i
                     WHERE i.product_id = p.id
-                      AND LOWER(REPLACE(i.filename,'\\','/')) NOT LIKE '%/lancamentos/%'
+                      AND NOT is_launch_path(i.filename)
                     ORDER BY i.filename
                     LIMIT 1
                 ) AS image
@@ -1380,7 +6088,7 @@ mod core {
         );
         if params.launch_only {
             where_clauses.push(
-                "(UPPER(COALESCE(p.pgroup,'')) LIKE '%LANC%' OR UPPER(COALESCE(p.details,'')) LIKE '%LANC%' OR EXISTS (SELECT 1 FROM images il WHERE il.product_id = p.id AND LOWER(REPLACE(il.filename,'\\','/')) LIKE '%/lancamentos/%'))"
+                "(UPPER(COALESCE(p.pgroup,'')) LIKE '%LANC%' OR UPPER(COALESCE(p.details,'')) LIKE '%LANC%' OR EXISTS (SELECT 1 FROM images il WHERE il.product_id = p.id AND is_launch_path(il.filename)))"
                     .into(),
             );
         }
@@ -1397,7 +6105,8 @@ mod core {
             " ORDER BY UPPER(TRIM(COALESCE(p.pgroup,''))), UPPER(TRIM(COALESCE(NULLIF(MIN(TRIM(COALESCE(v.make,''))), ''),''))), UPPER(TRIM(MIN(TRIM(v.name)))), UPPER(TRIM(p.description)), UPPER(TRIM(p.code))",
         );
         if let Some(limit) = params.limit.filter(|v| *v > 0) {
-            sql.push_str(&format!(" LIMIT {}", limit));
+            sql.push_str(" LIMIT ?");
+            values.push(rusqlite::types::Value::Integer(limit));
         }
 
         let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
@@ -1508,6 +6217,7 @@ mod core {
         let conn =
             open_db(&db_path(&app).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
         migrate(&conn).map_err(|e| e.to_string())?;
+        register_launch_sql_fn(&conn, launch_tokens()).map_err(|e| e.to_string())?;
 
         let vehicle_label_expr = "UPPER(TRIM(CASE WHEN INSTR(REPLACE(v.name,'/',' '),' ')>0 THEN SUBSTR(REPLACE(v.name,'/',' '),1,INSTR(REPLACE(v.name,'/',' '),' ')-1) ELSE v.name END))";
         let mut sql = String::from(
@@ -1552,7 +6262,7 @@ mod core {
         );
         if params.launch_only {
             where_clauses.push(
-                "(UPPER(COALESCE(p.pgroup,'')) LIKE '%LANC%' OR UPPER(COALESCE(p.details,'')) LIKE '%LANC%' OR EXISTS (SELECT 1 FROM images il WHERE il.product_id = p.id AND LOWER(REPLACE(il.filename,'\\','/')) LIKE '%/lancamentos/%'))"
+                "(UPPER(COALESCE(p.pgroup,'')) LIKE '%LANC%' OR UPPER(COALESCE(p.details,'')) LIKE '%LANC%' OR EXISTS (SELECT 1 FROM images il WHERE il.product_id = p.id AND is_launch_path(il.filename)))"
                     .into(),
             );
         }
@@ -1567,7 +6277,8 @@ mod core {
             " ORDER BY UPPER(TRIM(COALESCE(p.pgroup,''))), UPPER(TRIM(COALESCE(NULLIF(MIN(TRIM(COALESCE(v.make,''))), ''),''))), UPPER(TRIM(MIN(TRIM(v.name)))), UPPER(TRIM(p.description)), UPPER(TRIM(p.code))",
         );
         if let Some(limit) = params.limit.filter(|v| *v > 0) {
-            sql.push_str(&format!(" LIMIT {}", limit));
+            sql.push_str(" LIMIT ?");
+            values.push(rusqlite::types::Value::Integer(limit));
         }
 
         let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
@@ -1626,7 +6337,14 @@ mod core {
     ) -> Result<ProductDetails, String> {
         let conn =
             open_db(&db_path(&app).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
-        let mut stmt = conn.prepare("SELECT p.id, p.code, p.description, p.application, p.details, p.ean_gtin, p.altura, p.largura, p.comprimento, p.similar, b.name FROM products p JOIN brands b ON b.id = p.brand_id WHERE p.id = ?1").map_err(|e| e.to_string())?;
+        product_details(&conn, product_id)
+    }
+
+    pub(crate) fn product_details(
+        conn: &Connection,
+        product_id: i64,
+    ) -> Result<ProductDetails, String> {
+        let mut stmt = conn.prepare("SELECT p.id, p.code, p.description, p.application, p.details, p.ean_gtin, p.altura, p.largura, p.comprimento, p.similar, b.name, p.brand_id FROM products p JOIN brands b ON b.id = p.brand_id WHERE p.id = ?1").map_err(|e| e.to_string())?;
         let (
             id,
             code,
@@ -1639,6 +6357,7 @@ mod core {
             comprimento,
             similar,
             brand,
+            brand_id,
         ): (
             i64,
             String,
@@ -1651,6 +6370,7 @@ mod core {
             Option<String>,
             Option<String>,
             String,
+            i64,
         ) = stmt
             .query_row(params![product_id], |row| {
                 Ok((
@@ -1665,6 +6385,7 @@ mod core {
                     row.get(8)?,
                     row.get(9)?,
                     row.get(10)?,
+                    row.get(11)?,
                 ))
             })
             .map_err(|e| e.to_string())?;
@@ -1676,11 +6397,29 @@ mod core {
             .map_err(|e| e.to_string())?
             .filter_map(|r| r.ok())
             .collect();
+        let mut veh_stmt = conn
+            .prepare(
+                "SELECT v.id, v.name, v.category FROM product_vehicles pv JOIN vehicles v ON v.id = pv.vehicle_id
+                 WHERE pv.product_id = ?1 ORDER BY v.name",
+            )
+            .map_err(|e| e.to_string())?;
+        let vehicles: Vec<Vehicle> = veh_stmt
+            .query_map(params![product_id], |row| {
+                Ok(Vehicle {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    category: row.get(2)?,
+                })
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<rusqlite::Result<_>>()
+            .map_err(|e| e.to_string())?;
         Ok(ProductDetails {
             id,
             code,
             description,
             brand,
+            brand_id,
             application,
             details,
             ean_gtin,
@@ -1689,9 +6428,99 @@ mod core {
             comprimento,
             similar,
             images,
+            vehicles,
         })
     }
 
+    /// Erro de `get_product_by_code_cmd` quando nenhum produto tem o código.
+    pub const PRODUCT_NOT_FOUND_ERROR: &str = "NOT_FOUND";
+
+    #[tauri::command]
+    pub fn get_product_by_code_cmd(app: AppHandle, code: String) -> Result<ProductDetails, String> {
+        let conn =
+            open_db(&db_path(&app).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+        product_by_code(&conn, &code)
+    }
+
+    /// Busca pelo código como a importação grava (espaços colapsados, maiúsculo).
+    pub(crate) fn product_by_code(conn: &Connection, code: &str) -> Result<ProductDetails, String> {
+        let code = crate::importer::normalize_code(code);
+        let id: Option<i64> = conn
+            .query_row(
+                "SELECT id FROM products WHERE code = ?1",
+                params![code],
+                |r| r.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        match id {
+            Some(id) => product_details(conn, id),
+            None => Err(PRODUCT_NOT_FOUND_ERROR.to_string()),
+        }
+    }
+
+    /// Imagens de vários produtos numa consulta só (miniaturas da grade de resultados).
+    #[tauri::command]
+    pub fn get_images_for_products_cmd(
+        app: AppHandle,
+        ids: Vec<i64>,
+    ) -> Result<HashMap<i64, Vec<String>>, String> {
+        let conn =
+            open_db(&db_path(&app).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+        images_for_products(&conn, &ids).map_err(|e| e.to_string())
+    }
+
+    /// Todo id pedido aparece no mapa (lista vazia se não tiver imagem), na mesma ordem
+    /// de nomes que `product_details`.
+    pub(crate) fn images_for_products(
+        conn: &Connection,
+        ids: &[i64],
+    ) -> Result<HashMap<i64, Vec<String>>> {
+        let mut out: HashMap<i64, Vec<String>> = ids.iter().map(|id| (*id, Vec::new())).collect();
+        if out.is_empty() {
+            return Ok(out);
+        }
+        let rows = query_in_chunks(
+            conn,
+            "SELECT product_id, filename FROM images WHERE product_id IN ({in}) ORDER BY product_id, filename",
+            ids,
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+        )?;
+        for (id, filename) in rows {
+            out.entry(id).or_default().push(filename);
+        }
+        Ok(out)
+    }
+
+    /// Parâmetros por consulta em `query_in_chunks`; abaixo do limite antigo do SQLite (999).
+    const MAX_IN_PARAMS: usize = 900;
+
+    /// Roda `sql` (com `{in}` no lugar da lista) em blocos de até `MAX_IN_PARAMS` chaves e
+    /// junta as linhas na ordem dos blocos. Chaves repetidas vão uma vez só, na primeira posição.
+    fn query_in_chunks<K, T, F>(
+        conn: &Connection,
+        sql: &str,
+        keys: &[K],
+        mut map: F,
+    ) -> Result<Vec<T>>
+    where
+        K: rusqlite::ToSql + Eq + std::hash::Hash,
+        F: FnMut(&rusqlite::Row<'_>) -> rusqlite::Result<T>,
+    {
+        let mut seen = HashSet::new();
+        let unique: Vec<&K> = keys.iter().filter(|k| seen.insert(*k)).collect();
+        let mut out = Vec::new();
+        for chunk in unique.chunks(MAX_IN_PARAMS) {
+            let placeholders = vec!["?"; chunk.len()].join(",");
+            let mut stmt = conn.prepare(&sql.replace("{in}", &placeholders))?;
+            let rows = stmt.query_map(rusqlite::params_from_iter(chunk), &mut map)?;
+            for row in rows {
+                out.push(row?);
+            }
+        }
+        Ok(out)
+    }
+
     fn looks_like_catalog_asset(bytes: &[u8]) -> bool {
         bytes.starts_with(b"CIMG")
             || bytes.starts_with(b"SQLite format 3\0")
@@ -1703,14 +6532,64 @@ mod core {
             || (bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP")
     }
 
-    fn write_download_bytes(dest: &Path, bytes: &[u8]) -> Result<()> {
+    /// Extensão do arquivo em andamento; sobras de um download interrompido são apagadas
+    /// na sincronização seguinte (`remove_partial_downloads`).
+    const PARTIAL_DOWNLOAD_EXT: &str = "part";
+
+    fn partial_download_path(dest: &Path) -> PathBuf {
+        let mut name = dest.file_name().unwrap_or_default().to_os_string();
+        name.push(".");
+        name.push(PARTIAL_DOWNLOAD_EXT);
+        dest.with_file_name(name)
+    }
+
+    /// Confere o sha (quando informado), grava em `<dest>.part` e só então renomeia:
+    /// `dest` nunca fica pela metade nem com conteúdo diferente do esperado.
+    fn write_download_bytes(
+        dest: &Path,
+        bytes: &[u8],
+        expected_sha256: Option<&str>,
+    ) -> Result<()> {
+        if let Some(expected) = expected_sha256.map(str::trim).filter(|s| !s.is_empty()) {
+            let actual: String = Sha256::digest(bytes)
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect();
+            if !actual.eq_ignore_ascii_case(expected) {
+                anyhow::bail!(
+                    "sha256 inválido para {}: esperado {}, obtido {}",
+                    dest.display(),
+                    expected,
+                    actual
+                );
+            }
+        }
         if let Some(parent) = dest.parent() {
             fs::create_dir_all(parent)?;
         }
-        fs::write(dest, bytes)?;
+        let part = partial_download_path(dest);
+        if let Err(e) = fs::write(&part, bytes).and_then(|_| fs::rename(&part, dest)) {
+            let _ = fs::remove_file(&part);
+            return Err(e.into());
+        }
         Ok(())
     }
 
+    /// Apaga os `.part` deixados por downloads interrompidos; devolve quantos removeu.
+    fn remove_partial_downloads(dir: &Path) -> usize {
+        WalkDir::new(dir)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .filter(|e| {
+                e.path()
+                    .extension()
+                    .is_some_and(|ext| ext == PARTIAL_DOWNLOAD_EXT)
+            })
+            .filter(|e| fs::remove_file(e.path()).is_ok())
+            .count()
+    }
+
     fn safe_manifest_rel_path(path: &str) -> Result<PathBuf> {
         let normalized = path.replace('\\', "/");
         let rel = Path::new(&normalized);
@@ -1767,7 +6646,11 @@ mod core {
         validate_catalog_db_file(path).is_ok()
     }
 
-    async fn download_to_file_raw(url: &str, dest: &Path) -> Result<()> {
+    async fn download_to_file_raw(
+        url: &str,
+        dest: &Path,
+        expected_sha256: Option<&str>,
+    ) -> Result<()> {
         let raw_client = Client::builder()
             .timeout(Duration::from_secs(20))
             .no_gzip()
@@ -1781,18 +6664,89 @@ mod core {
             .send()
             .await?
             .error_for_status()?;
+        let content_length = resp.content_length();
         let bytes = resp.bytes().await?;
+        check_content_length(content_length, bytes.len())?;
         if !looks_like_catalog_asset(bytes.as_ref()) {
             anyhow::bail!(
                 "fallback bruto retornou payload inesperado para {}",
                 dest.display()
             );
         }
-        write_download_bytes(dest, bytes.as_ref())
+        write_download_bytes(dest, bytes.as_ref(), expected_sha256)
+    }
+
+    /// Corpo menor (ou maior) que o Content-Length anunciado: resposta cortada no caminho.
+    #[derive(Debug, PartialEq)]
+    struct SizeMismatch {
+        expected: u64,
+        received: u64,
+    }
+
+    impl std::fmt::Display for SizeMismatch {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(
+                f,
+                "download incompleto: Content-Length {} mas recebidos {} bytes",
+                self.expected, self.received
+            )
+        }
+    }
+
+    impl std::error::Error for SizeMismatch {}
+
+    /// Sem Content-Length (ou com descompressão automática, que o remove) não há o que comparar.
+    fn check_content_length(expected: Option<u64>, received: usize) -> Result<(), SizeMismatch> {
+        match expected {
+            Some(expected) if expected != received as u64 => Err(SizeMismatch {
+                expected,
+                received: received as u64,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Tentativas por arquivo quando a resposta chega truncada.
+    const DOWNLOAD_ATTEMPTS: usize = 2;
+
+    fn is_truncated_download(err: &anyhow::Error) -> bool {
+        err.downcast_ref::<SizeMismatch>().is_some()
+            || err
+                .downcast_ref::<reqwest::Error>()
+                .is_some_and(|e| e.is_body() || e.is_decode())
+    }
+
+    /// Baixa `url` para `dest`; resposta truncada (Content-Length divergente ou conexão
+    /// fechada no meio) é tentada de novo até `DOWNLOAD_ATTEMPTS` vezes.
+    async fn download_to_file(
+        client: &Client,
+        url: &str,
+        dest: &Path,
+        expected_sha256: Option<&str>,
+    ) -> Result<()> {
+        let mut attempt = 1;
+        loop {
+            match download_to_file_once(client, url, dest, expected_sha256).await {
+                Err(err) if attempt < DOWNLOAD_ATTEMPTS && is_truncated_download(&err) => {
+                    eprintln!(
+                        "download_to_file: {} truncado, tentando de novo: {}",
+                        url, err
+                    );
+                    attempt += 1;
+                }
+                res => return res,
+            }
+        }
     }
 
-    async fn download_to_file(client: &Client, url: &str, dest: &Path) -> Result<()> {
+    async fn download_to_file_once(
+        client: &Client,
+        url: &str,
+        dest: &Path,
+        expected_sha256: Option<&str>,
+    ) -> Result<()> {
         let resp = client.get(url).send().await?.error_for_status()?;
+        let content_length = resp.content_length();
         let content_encoding = resp
             .headers()
             .get(CONTENT_ENCODING)
@@ -1807,11 +6761,12 @@ mod core {
                     content_encoding,
                     err
                 );
-                return download_to_file_raw(url, dest).await;
+                return download_to_file_raw(url, dest, expected_sha256).await;
             }
             Err(err) => return Err(err.into()),
         };
-        write_download_bytes(dest, bytes.as_ref())
+        check_content_length(content_length, bytes.len())?;
+        write_download_bytes(dest, bytes.as_ref(), expected_sha256)
     }
 
     async fn download_to_file_verified(
@@ -1819,12 +6774,14 @@ mod core {
         url: &str,
         dest: &Path,
         expected_sha256: Option<&str>,
+        keys: &[String],
     ) -> Result<()> {
         let tmp = dest.with_extension("download.tmp");
         if tmp.exists() {
             let _ = fs::remove_file(&tmp);
         }
-        download_to_file(client, url, &tmp).await?;
+        // O sha do banco é conferido abaixo, no arquivo temporário.
+        download_to_file(client, url, &tmp, None).await?;
         if let Some(expected) = expected_sha256.map(|s| s.trim()).filter(|s| !s.is_empty()) {
             let actual = sha256_file(&tmp)?;
             if !actual.eq_ignore_ascii_case(expected) {
@@ -1837,45 +6794,151 @@ mod core {
                 );
             }
         }
-        validate_catalog_db_file(&tmp)?;
+        install_downloaded_db(&tmp, dest, keys)
+    }
+
+    /// Catálogo distribuído criptografado (mesmo wrapper CIMG das imagens): troca o arquivo
+    /// pela versão em claro antes de qualquer `open_db`. Banco comum fica como está.
+    /// Devolve se houve decriptação.
+    fn decrypt_catalog_db_file(path: &Path, keys: &[String]) -> Result<bool> {
+        let bytes = fs::read(path)?;
+        if !bytes.starts_with(b"CIMG") {
+            return Ok(false);
+        }
+        let mut last_err = None;
+        for key in keys.iter().filter(|k| !k.trim().is_empty()) {
+            match crate::desc::decrypt_image(&bytes, key) {
+                Ok(plain) => {
+                    let tmp = path.with_extension("decrypt.tmp");
+                    if let Err(e) = fs::write(&tmp, plain).and_then(|_| fs::rename(&tmp, path)) {
+                        let _ = fs::remove_file(&tmp);
+                        return Err(e.into());
+                    }
+                    return Ok(true);
+                }
+                Err(e) => last_err = Some(e),
+            }
+        }
+        Err(last_err
+            .map(|e| anyhow::anyhow!("Falha ao descriptografar catalog.db: {}", e))
+            .unwrap_or_else(|| {
+                anyhow::anyhow!(
+                    "catalog.db criptografado requer chave (DESCRYPT_KEY não configurada)."
+                )
+            }))
+    }
+
+    fn db_file_is_encrypted(path: &Path) -> bool {
+        use std::io::Read;
+        let mut magic = [0u8; 4];
+        fs::File::open(path)
+            .and_then(|mut f| f.read_exact(&mut magic))
+            .is_ok()
+            && &magic == b"CIMG"
+    }
+
+    /// Deixa catalog.db em claro antes de abrir. Seed recém-copiado que não decifra é apagado,
+    /// para o próximo início semear de novo; CIMG que já estava na pasta é decifrado se a
+    /// chave aparecer, senão recusado com mensagem clara (em vez de "file is not a database").
+    fn prepare_db_file(db_file: &Path, just_seeded: bool, keys: &[String]) -> Result<()> {
+        match decrypt_catalog_db_file(db_file, keys) {
+            Ok(_) => Ok(()),
+            Err(e) if just_seeded => {
+                let _ = fs::remove_file(db_file);
+                Err(e)
+            }
+            Err(e) => Err(anyhow::anyhow!(
+                "{} está criptografado e não pôde ser aberto ({}); configure a chave ou apague o arquivo para semear de novo",
+                db_file.display(),
+                e
+            )),
+        }
+    }
+
+    /// Valida e migra o banco baixado ainda no arquivo temporário; só substitui `dest`
+    /// se tudo der certo, para um download incompatível não quebrar o banco instalado.
+    fn install_downloaded_db(tmp: &Path, dest: &Path, keys: &[String]) -> Result<()> {
+        let prepared = decrypt_catalog_db_file(tmp, keys)
+            .and_then(|_| validate_catalog_db_file(tmp))
+            .and_then(|_| {
+                let conn = open_db(tmp)?;
+                migrate(&conn)?;
+                Ok(())
+            });
+        if let Err(err) = prepared {
+            let _ = fs::remove_file(tmp);
+            let _ = fs::remove_file(tmp.with_extension("tmp-wal"));
+            let _ = fs::remove_file(tmp.with_extension("tmp-shm"));
+            return Err(err);
+        }
         if let Some(parent) = dest.parent() {
             fs::create_dir_all(parent)?;
         }
-        fs::rename(&tmp, dest).or_else(|_| {
-            fs::copy(&tmp, dest)?;
-            fs::remove_file(&tmp)?;
+        fs::rename(tmp, dest).or_else(|_| {
+            fs::copy(tmp, dest)?;
+            fs::remove_file(tmp)?;
             Ok::<(), std::io::Error>(())
         })?;
         Ok(())
     }
 
-    fn index_from_file_list(conn: &mut Connection, files: &[String]) -> Result<ImageIndexResult> {
+    /// Quantos arquivos sem produto entram na amostra de `ImageIndexResult`.
+    const UNMATCHED_SAMPLE_LIMIT: usize = 50;
+
+    fn index_from_file_list(
+        conn: &mut Connection,
+        files: &[ManifestImageItem],
+        link_ambiguous: bool,
+    ) -> Result<ImageIndexResult> {
         let tx = conn.transaction()?;
         let mut scanned = 0usize;
         let mut matched = 0usize;
         let mut inserted = 0usize;
+        let mut unmatched = 0usize;
+        let mut unmatched_sample: Vec<String> = Vec::new();
+        let mut ambiguous = 0usize;
+        let mut ambiguous_sample: Vec<AmbiguousImage> = Vec::new();
+        // Um SELECT só e casamento em memória: manifests com dezenas de milhares de arquivos
+        // fariam milhares de consultas por candidato.
+        let product_ids: HashMap<String, i64> = {
+            let mut stmt = tx.prepare("SELECT code, id FROM products")?;
+            let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
         // Limpa a tabela antes de reindexar para evitar associações antigas/erradas
         tx.execute("DELETE FROM images", [])?;
-        for f in files {
+        for item in files {
             scanned += 1;
             // Usa apenas o ultimo segmento como nome de arquivo logico
-            let rel = f.replace('\\', "/");
+            let rel = item.file.replace('\\', "/");
             let last = rel.rsplit('/').next().unwrap_or(&rel);
             let stem = last.split('.').next().unwrap_or(last);
-            let candidates = candidate_codes(stem);
-            let mut found: Option<i64> = None;
-            for c in candidates {
-                if let Ok(pid) =
-                    tx.query_row("SELECT id FROM products WHERE code=?1", params![c], |r| {
-                        r.get(0)
-                    })
-                {
-                    found = Some(pid);
-                    break;
+            // Código explícito do manifest primeiro; o nome do arquivo só se ele não existir.
+            let explicit = item
+                .code
+                .as_deref()
+                .map(crate::importer::normalize_code)
+                .filter(|c| !c.is_empty());
+            let found = match explicit.and_then(|c| product_ids.get(&c).copied().map(|p| (c, p))) {
+                Some(hit) => vec![hit],
+                None => candidate_matches(stem, |c| product_ids.get(c).copied()),
+            };
+            if found.is_empty() {
+                unmatched += 1;
+                if unmatched_sample.len() < UNMATCHED_SAMPLE_LIMIT {
+                    unmatched_sample.push(rel);
+                }
+                continue;
+            }
+            if found.len() > 1 {
+                ambiguous += 1;
+                note_ambiguous(&mut ambiguous_sample, rel.clone(), &found);
+                if !link_ambiguous {
+                    continue;
                 }
             }
-            if let Some(pid) = found {
-                matched += 1;
+            matched += 1;
+            for (_, pid) in &found {
                 if tx
                     .execute(
                         "INSERT OR IGNORE INTO images(product_id, filename) VALUES(?1,?2)",
@@ -1887,23 +6950,113 @@ mod core {
                 }
             }
         }
+        apply_image_overrides(&tx)?;
         tx.commit()?;
         Ok(ImageIndexResult {
             scanned,
             matched,
             inserted,
+            skipped_placeholders: 0,
+            unmatched,
+            unmatched_sample,
+            empty_manifest: false,
+            ambiguous,
+            ambiguous_sample,
         })
     }
 
+    /// Correções manuais por código, aplicadas depois da indexação automática:
+    /// `exclude` desfaz um vínculo errado e `link` força um que o nome do arquivo não revela.
+    fn apply_image_overrides(conn: &Connection) -> Result<usize> {
+        let removed = conn.execute(
+            "DELETE FROM images WHERE EXISTS (
+               SELECT 1 FROM image_overrides o JOIN products p ON p.code = o.product_code
+               WHERE o.action = 'exclude' AND p.id = images.product_id AND o.filename = images.filename)",
+            [],
+        )?;
+        let linked = conn.execute(
+            "INSERT OR IGNORE INTO images(product_id, filename)
+             SELECT p.id, o.filename FROM image_overrides o JOIN products p ON p.code = o.product_code
+             WHERE o.action = 'link'",
+            [],
+        )?;
+        Ok(removed + linked)
+    }
+
+    pub(crate) fn set_image_override(
+        conn: &Connection,
+        code: &str,
+        filename: &str,
+        action: &str,
+    ) -> Result<(), String> {
+        let action = action.trim().to_ascii_lowercase();
+        if action != "link" && action != "exclude" {
+            return Err(format!("Ação inválida: {action} (use link ou exclude)"));
+        }
+        let code = crate::importer::normalize_code(code);
+        let filename = filename.trim().replace('\\', "/");
+        if code.is_empty() || filename.is_empty() {
+            return Err("Código e arquivo são obrigatórios".to_string());
+        }
+        conn.execute(
+            "INSERT OR REPLACE INTO image_overrides(product_code, filename, action) VALUES(?1, ?2, ?3)",
+            params![code, filename, action],
+        )
+        .map_err(|e| e.to_string())?;
+        apply_image_overrides(conn).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
     #[tauri::command]
-    pub fn set_branding_image(kind: String, source_path: String) -> Result<BrandingResult, String> {
-        use std::io::Write;
+    pub fn set_image_override_cmd(
+        app: AppHandle,
+        code: String,
+        filename: String,
+        action: String,
+    ) -> Result<(), String> {
+        let conn =
+            open_db(&db_path(&app).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+        ensure_schema(&conn).map_err(|e| e.to_string())?;
+        set_image_override(&conn, &code, &filename, &action)
+    }
+
+    /// Remove só a regra; o vínculo atual fica como está até a próxima indexação.
+    #[tauri::command]
+    pub fn remove_image_override_cmd(
+        app: AppHandle,
+        code: String,
+        filename: String,
+    ) -> Result<bool, String> {
+        let conn =
+            open_db(&db_path(&app).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+        ensure_schema(&conn).map_err(|e| e.to_string())?;
+        let removed = conn
+            .execute(
+                "DELETE FROM image_overrides WHERE product_code = ?1 AND filename = ?2",
+                params![
+                    crate::importer::normalize_code(&code),
+                    filename.trim().replace('\\', "/")
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(removed > 0)
+    }
+
+    #[tauri::command]
+    pub fn set_branding_image(
+        kind: String,
+        source_path: String,
+        overwrite: Option<bool>,
+    ) -> Result<BrandingResult, String> {
         let cwd = std::env::current_dir().map_err(|e| e.to_string())?;
         let out_dir = if cwd.ends_with("src-tauri") {
             cwd.parent().unwrap_or(&cwd).join("public").join("images")
         } else {
             cwd.join("public").join("images")
         };
+        if !Path::new(&source_path).is_file() {
+            return Err(format!("Imagem não encontrada: {}", source_path));
+        }
         fs::create_dir_all(&out_dir).map_err(|e| e.to_string())?;
         let ext = std::path::Path::new(&source_path)
             .extension()
@@ -1915,6 +7068,7 @@ mod core {
             format!("bg.{}", ext)
         };
         let dest = out_dir.join(&fixed);
+        check_export_dest(&dest, overwrite.unwrap_or(false))?;
         fs::copy(&source_path, &dest).map_err(|e| e.to_string())?;
         let json_path = out_dir.join("branding.json");
         let mut logo: Option<String> = None;
@@ -1948,9 +7102,11 @@ mod core {
             background = Some(fixed.clone());
         }
         let obj = serde_json::json!({ "logo": logo, "background": background, "headerLogos": header_logos });
-        let mut f = std::fs::File::create(&json_path).map_err(|e| e.to_string())?;
-        f.write_all(serde_json::to_string_pretty(&obj).unwrap().as_bytes())
-            .map_err(|e| e.to_string())?;
+        write_file_atomic(
+            &json_path,
+            serde_json::to_string_pretty(&obj).unwrap().as_bytes(),
+        )
+        .map_err(|e| e.to_string())?;
         Ok(BrandingResult {
             ok: true,
             logo,
@@ -1961,7 +7117,6 @@ mod core {
 
     #[tauri::command]
     pub fn set_header_logos(paths: Vec<String>) -> Result<BrandingResult, String> {
-        use std::io::Write;
         let cwd = std::env::current_dir().map_err(|e| e.to_string())?;
         let out_dir = if cwd.ends_with("src-tauri") {
             cwd.parent().unwrap_or(&cwd).join("public").join("images")
@@ -2010,9 +7165,11 @@ mod core {
         }
         let obj =
             serde_json::json!({ "logo": logo, "background": background, "headerLogos": copied });
-        let mut f = std::fs::File::create(&json_path).map_err(|e| e.to_string())?;
-        f.write_all(serde_json::to_string_pretty(&obj).unwrap().as_bytes())
-            .map_err(|e| e.to_string())?;
+        write_file_atomic(
+            &json_path,
+            serde_json::to_string_pretty(&obj).unwrap().as_bytes(),
+        )
+        .map_err(|e| e.to_string())?;
         Ok(BrandingResult {
             ok: true,
             logo,
@@ -2088,8 +7245,6 @@ mod core {
 
     #[tauri::command]
     pub fn refresh_branding_config() -> Result<BrandingResult, String> {
-        use std::io::Write;
-
         let out_dir = branding_images_dir()?;
         let logos_dir = out_dir.join("header-logos");
         fs::create_dir_all(&logos_dir).map_err(|e| e.to_string())?;
@@ -2120,29 +7275,153 @@ mod core {
             found
         };
 
-        let obj = serde_json::json!({
-            "logo": logo,
-            "background": background,
-            "headerLogos": header_logos
-        });
-        let mut f = std::fs::File::create(&json_path).map_err(|e| e.to_string())?;
-        f.write_all(serde_json::to_string_pretty(&obj).unwrap().as_bytes())
+        let obj = serde_json::json!({
+            "logo": logo,
+            "background": background,
+            "headerLogos": header_logos
+        });
+        write_file_atomic(
+            &json_path,
+            serde_json::to_string_pretty(&obj).unwrap().as_bytes(),
+        )
+        .map_err(|e| e.to_string())?;
+
+        Ok(BrandingResult {
+            ok: true,
+            logo,
+            background,
+            header_logos: Some(header_logos),
+        })
+    }
+
+    /// Compara só o manifest com o banco local, sem baixar nada além dele.
+    #[tauri::command]
+    pub async fn check_update_cmd(
+        app: AppHandle,
+        manifest_url: String,
+    ) -> Result<UpdateInfo, String> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(20))
+            .build()
+            .map_err(|e| e.to_string())?;
+        let (_, dbf, _) = ensure_dirs(&app).map_err(|e| e.to_string())?;
+        let (manifest, _) = fetch_or_seed_manifest(&client, &app, &manifest_url).await?;
+        let local_version = if catalog_db_is_usable(&dbf) {
+            let conn = open_db(&dbf).map_err(|e| e.to_string())?;
+            Some(get_db_version(&conn).unwrap_or(0))
+        } else {
+            None
+        };
+        Ok(update_info(&manifest, local_version))
+    }
+
+    /// `local_version` None = banco local ausente/inutilizável, então sempre há atualização.
+    fn update_info(manifest: &CatalogManifest, local_version: Option<i64>) -> UpdateInfo {
+        UpdateInfo {
+            remote_version: manifest.db.version,
+            local_version: local_version.unwrap_or(0),
+            update_available: local_version.is_none_or(|v| manifest.db.version > v),
+            image_count: manifest.images.as_ref().map_or(0, |i| i.files.len()),
+        }
+    }
+
+    #[tauri::command]
+    pub async fn preview_sync_cmd(
+        app: AppHandle,
+        manifest_url: String,
+    ) -> Result<SyncPreview, String> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(20))
+            .build()
             .map_err(|e| e.to_string())?;
+        let (_, dbf, imgs_dir) = ensure_dirs(&app).map_err(|e| e.to_string())?;
+        let (manifest, manifest_hash) =
+            fetch_or_seed_manifest(&client, &app, &manifest_url).await?;
+        let local = if catalog_db_is_usable(&dbf) {
+            let conn = open_db(&dbf).map_err(|e| e.to_string())?;
+            // Só atualiza esquema antigo; o migrate completo regravaria grupos/merges.
+            ensure_schema(&conn).map_err(|e| e.to_string())?;
+            Some(conn)
+        } else {
+            None
+        };
+        preview_sync(local.as_ref(), &manifest, &manifest_hash, &imgs_dir)
+            .map_err(|e| e.to_string())
+    }
 
-        Ok(BrandingResult {
-            ok: true,
-            logo,
-            background,
-            header_logos: Some(header_logos),
+    /// Mesmas regras de `sync_from_manifest`: `local` None = banco ausente/inutilizável,
+    /// então o banco vem do manifest e o images_cache local não conta.
+    fn preview_sync(
+        local: Option<&Connection>,
+        manifest: &CatalogManifest,
+        manifest_hash: &str,
+        imgs_dir: &Path,
+    ) -> Result<SyncPreview> {
+        let from_version = local.map_or(0, |c| get_db_version(c).unwrap_or(0));
+        let manifest_changed = local
+            .is_none_or(|c| get_manifest_hash(c).ok().flatten().as_deref() != Some(manifest_hash));
+        let (images_to_download, images_up_to_date) = match manifest.images.as_ref() {
+            Some(imgs) => {
+                let empty;
+                let conn = match local {
+                    Some(c) => c,
+                    None => {
+                        empty = Connection::open_in_memory()?;
+                        migrate(&empty)?;
+                        &empty
+                    }
+                };
+                let plan = plan_image_downloads(conn, imgs_dir, imgs, manifest_changed);
+                (plan.needed.len(), plan.up_to_date)
+            }
+            None => (0, 0),
+        };
+        let db_will_update = local.is_none() || manifest.db.version > from_version;
+        Ok(SyncPreview {
+            db_will_update,
+            from_version,
+            to_version: manifest.db.version,
+            images_to_download,
+            images_up_to_date,
+            images_approximate: db_will_update,
         })
     }
 
+    /// Estado gerenciado pelo Tauri: só uma sincronização por vez mexe no banco e nas imagens.
+    #[derive(Default)]
+    pub struct SyncState {
+        running: std::sync::atomic::AtomicBool,
+    }
+
+    /// Libera o `SyncState` ao sair do escopo, inclusive em erro.
+    pub struct SyncGuard<'a>(&'a SyncState);
+
+    impl SyncState {
+        pub fn try_begin(&self) -> Result<SyncGuard<'_>, String> {
+            use std::sync::atomic::Ordering;
+            self.running
+                .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+                .map(|_| SyncGuard(self))
+                .map_err(|_| "Sincronização já em andamento".to_string())
+        }
+    }
+
+    impl Drop for SyncGuard<'_> {
+        fn drop(&mut self) {
+            self.0
+                .running
+                .store(false, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
     #[tauri::command]
     pub async fn sync_from_manifest(
         app: AppHandle,
+        state: tauri::State<'_, SyncState>,
         manifest_url: String,
         skip_images: Option<bool>,
     ) -> Result<SyncResult, String> {
+        let _guard = state.try_begin()?;
         let skip_images = skip_images.unwrap_or(false);
         let client = Client::builder()
             .timeout(Duration::from_secs(20))
@@ -2168,6 +7447,16 @@ mod core {
         } else {
             true
         };
+        if local_db_usable && !manifest_changed {
+            let conn = open_db(&dbf).map_err(|e| e.to_string())?;
+            if sync_is_noop(&conn, &manifest, &manifest_hash, &imgs_dir) {
+                return Ok(SyncResult {
+                    updated_db: false,
+                    downloaded_images: 0,
+                    db_version: local_version,
+                });
+            }
+        }
         if !local_db_usable || manifest.db.version > local_version {
             // Manifest mudou: limpar pasta de lançamentos para evitar resquícios antigos.
             clear_launches_dir(&imgs_dir).ok();
@@ -2176,6 +7465,7 @@ mod core {
                 &manifest.db.url,
                 &dbf,
                 manifest.db.sha256.as_deref(),
+                &crate::call_img::resolve_keys(&app, &data_dir),
             )
             .await
             {
@@ -2187,8 +7477,9 @@ mod core {
                     manifest.db.version, manifest.db.url, err
                 ));
             }
+            // O catalog.db baixado pode ter esquema anterior ao do app (ex.: cópia sem migrar).
             let conn = open_db(&dbf).map_err(|e| e.to_string())?;
-            migrate(&conn).map_err(|e| e.to_string())?;
+            ensure_schema(&conn).map_err(|e| e.to_string())?;
             if get_db_version(&conn).unwrap_or(0) < manifest.db.version {
                 set_db_version(&conn, manifest.db.version).ok();
             }
@@ -2227,19 +7518,179 @@ mod core {
         }
         let conn = open_db(&dbf).map_err(|e| e.to_string())?;
         seed_brand_groups(&conn).ok();
+        configure_launch_tokens(&conn);
         set_manifest_hash(&conn, &manifest_hash).ok();
         let manifest_path = data_dir.join("manifest.json");
         if manifest_changed || !manifest_path.exists() {
-            let _ = std::fs::write(
+            let _ = write_file_atomic(
                 &manifest_path,
-                serde_json::to_string_pretty(&manifest).unwrap_or_default(),
+                serde_json::to_string_pretty(&manifest)
+                    .unwrap_or_default()
+                    .as_bytes(),
             );
         }
         let final_version = get_db_version(&conn).unwrap_or(0);
-        Ok(SyncResult {
+        let result = SyncResult {
             updated_db,
             downloaded_images,
             db_version: final_version,
+        };
+        if let Some(payload) = catalog_updated_payload(&result) {
+            let _ = app.emit("catalog_updated", payload);
+        }
+        Ok(result)
+    }
+
+    /// Só quando o catalog.db foi trocado: a UI recarrega marcas, grupos e veículos.
+    /// Imagens novas têm o próprio evento (`images_downloaded`).
+    fn catalog_updated_payload(result: &SyncResult) -> Option<serde_json::Value> {
+        result
+            .updated_db
+            .then(|| json!({ "db_version": result.db_version }))
+    }
+
+    /// Nada a fazer quando versão e hash do manifest batem com o local e todas as imagens
+    /// já estão no disco com o sha registrado em images_cache.
+    fn sync_is_noop(
+        conn: &Connection,
+        manifest: &CatalogManifest,
+        manifest_hash: &str,
+        imgs_dir: &Path,
+    ) -> bool {
+        if get_db_version(conn).unwrap_or(0) != manifest.db.version
+            || get_manifest_hash(conn).ok().flatten().as_deref() != Some(manifest_hash)
+        {
+            return false;
+        }
+        let Some(imgs) = manifest.images.as_ref() else {
+            return true;
+        };
+        imgs.files.iter().all(|item| {
+            let Ok(rel_path) = safe_manifest_rel_path(&item.file) else {
+                return true; // ignorado também pelo download
+            };
+            if !imgs_dir.join(rel_path).exists() {
+                return false;
+            }
+            let Some(man_sha) = item.sha256.as_deref() else {
+                return true;
+            };
+            conn.query_row(
+                "SELECT sha256 FROM images_cache WHERE filename=?1",
+                params![&item.file],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()
+            .ok()
+            .flatten()
+            .as_deref()
+                == Some(man_sha)
+        })
+    }
+
+    /// Baixa quando falta no disco ou quando o sha do manifest não bate com o images_cache
+    /// (sem sha no manifest, só rebaixa se o manifest mudou).
+    fn image_needs_download(
+        conn: &Connection,
+        local_path: &Path,
+        item: &ManifestImageItem,
+        manifest_changed: bool,
+    ) -> bool {
+        if !local_path.exists() {
+            return true;
+        }
+        let Some(man_sha) = item.sha256.as_deref() else {
+            return manifest_changed;
+        };
+        let cached: Option<String> = conn
+            .query_row(
+                "SELECT sha256 FROM images_cache WHERE filename=?1",
+                params![&item.file],
+                |row| row.get(0),
+            )
+            .optional()
+            .unwrap_or(None);
+        cached.as_deref() != Some(man_sha)
+    }
+
+    /// Itens do manifest a baixar (com o destino local), já em dia e com caminho inválido.
+    struct ImageDownloadPlan<'a> {
+        needed: Vec<(&'a ManifestImageItem, PathBuf)>,
+        up_to_date: usize,
+        invalid: usize,
+    }
+
+    fn plan_image_downloads<'a>(
+        conn: &Connection,
+        imgs_dir: &Path,
+        imgs: &'a ManifestImages,
+        manifest_changed: bool,
+    ) -> ImageDownloadPlan<'a> {
+        let mut plan = ImageDownloadPlan {
+            needed: Vec::new(),
+            up_to_date: 0,
+            invalid: 0,
+        };
+        for item in imgs.files.iter() {
+            let Ok(rel_path) = safe_manifest_rel_path(&item.file) else {
+                eprintln!("Ignorando caminho inválido no manifest: {}", item.file);
+                plan.invalid += 1;
+                continue;
+            };
+            let local_path = imgs_dir.join(&rel_path);
+            if image_needs_download(conn, &local_path, item, manifest_changed) {
+                plan.needed.push((item, local_path));
+            } else {
+                plan.up_to_date += 1;
+            }
+        }
+        plan
+    }
+
+    #[tauri::command]
+    pub fn clear_image_cache_cmd(app: AppHandle) -> Result<usize, String> {
+        let conn =
+            open_db(&db_path(&app).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+        ensure_schema(&conn).map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM images_cache", [])
+            .map_err(|e| e.to_string())
+    }
+
+    #[tauri::command]
+    pub fn rebuild_image_cache_cmd(app: AppHandle) -> Result<usize, String> {
+        let (_, dbf, imgs_dir) = ensure_dirs(&app).map_err(|e| e.to_string())?;
+        let conn = open_db(&dbf).map_err(|e| e.to_string())?;
+        ensure_schema(&conn).map_err(|e| e.to_string())?;
+        rebuild_image_cache(&conn, &imgs_dir).map_err(|e| e.to_string())
+    }
+
+    /// Recalcula o sha de cada arquivo em `imgs_dir` e substitui o images_cache inteiro,
+    /// com as chaves no mesmo formato do manifest ("pasta/arquivo.jpg").
+    fn rebuild_image_cache(conn: &Connection, imgs_dir: &Path) -> Result<usize> {
+        let mut entries: Vec<(String, String)> = Vec::new();
+        for entry in WalkDir::new(imgs_dir).into_iter().filter_map(|e| e.ok()) {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let rel = entry
+                .path()
+                .strip_prefix(imgs_dir)
+                .unwrap_or(entry.path())
+                .to_string_lossy()
+                .replace('\\', "/");
+            match sha256_file(entry.path()) {
+                Ok(sha) => entries.push((rel, sha)),
+                Err(e) => eprintln!("Falha ao calcular hash de {}: {}", rel, e),
+            }
+        }
+        in_savepoint(conn, "rebuild_image_cache", || {
+            conn.execute("DELETE FROM images_cache", [])?;
+            let mut insert = conn
+                .prepare("INSERT OR REPLACE INTO images_cache(filename, sha256) VALUES(?1, ?2)")?;
+            for (rel, sha) in entries.iter() {
+                insert.execute(params![rel, sha])?;
+            }
+            Ok(entries.len())
         })
     }
 
@@ -2259,6 +7710,7 @@ mod core {
         let semaphore = Arc::new(Semaphore::new(max_concurrency));
         let mut downloaded_images: usize = 0;
         let mut errors: usize = 0;
+        remove_partial_downloads(imgs_dir);
 
         // Avalia quem precisa ser baixado consultando cache local.
         let conn_cache = match open_db(db_path) {
@@ -2274,50 +7726,18 @@ mod core {
             rel_name: String,
             sha256: Option<String>,
         }
-        let mut jobs: Vec<DownloadJob> = Vec::new();
-        for item in imgs.files.iter() {
-            let Ok(rel_path) = safe_manifest_rel_path(&item.file) else {
-                eprintln!("Ignorando caminho inválido no manifest: {}", item.file);
-                errors += 1;
-                continue;
-            };
-            let local_path = imgs_dir.join(&rel_path);
-            let mut need = !local_path.exists();
-            if !need {
-                if let Some(ref man_sha) = item.sha256 {
-                    let cached: Option<String> = conn_cache
-                        .query_row(
-                            "SELECT sha256 FROM images_cache WHERE filename=?1",
-                            params![&item.file],
-                            |row| row.get(0),
-                        )
-                        .optional()
-                        .unwrap_or(None);
-                    if cached.as_deref() != Some(man_sha.as_str()) {
-                        need = true;
-                    }
-                } else if manifest_changed {
-                    need = true;
-                }
-            }
-            if need {
-                let url = if item.file.starts_with("http://") || item.file.starts_with("https://") {
-                    item.file.clone()
-                } else if let Ok(base) = url::Url::parse(&imgs.base_url) {
-                    base.join(&item.file)
-                        .map(|u| u.to_string())
-                        .unwrap_or_else(|_| format!("{}{}", imgs.base_url, item.file))
-                } else {
-                    format!("{}{}", imgs.base_url, item.file)
-                };
-                jobs.push(DownloadJob {
-                    url,
-                    local_path,
-                    rel_name: item.file.clone(),
-                    sha256: item.sha256.clone(),
-                });
-            }
-        }
+        let plan = plan_image_downloads(&conn_cache, imgs_dir, imgs, manifest_changed);
+        errors += plan.invalid;
+        let jobs: Vec<DownloadJob> = plan
+            .needed
+            .into_iter()
+            .map(|(item, local_path)| DownloadJob {
+                url: imgs.url_for(&item.file),
+                local_path,
+                rel_name: item.file.clone(),
+                sha256: item.sha256.clone(),
+            })
+            .collect();
         drop(conn_cache);
 
         let mut set = JoinSet::new();
@@ -2333,7 +7753,9 @@ mod core {
                         let _ = std::fs::create_dir_all(parent);
                     }
                 }
-                match download_to_file(&client, &job.url, &job.local_path).await {
+                match download_to_file(&client, &job.url, &job.local_path, job.sha256.as_deref())
+                    .await
+                {
                     Ok(_) => Ok((job.rel_name, job.sha256)),
                     Err(e) => Err((job.rel_name, e.to_string())),
                 }
@@ -2373,64 +7795,119 @@ mod core {
         (downloaded_images, errors)
     }
 
-    fn clear_launches_dir(imgs_dir: &std::path::Path) -> std::io::Result<()> {
-        for entry in std::fs::read_dir(imgs_dir)? {
-            if let Ok(e) = entry {
-                let path = e.path();
-                if path.is_dir() {
-                    if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                        if is_launch_component(name) {
-                            let _ = std::fs::remove_dir_all(&path);
-                        }
-                    }
-                }
+    /// Pastas de lançamento em qualquer nível (ex.: images/2024/lancamentos). Não desce
+    /// dentro de uma pasta já encontrada, então nenhuma imagem é contada duas vezes.
+    fn find_launch_dirs(imgs_dir: &Path) -> Vec<PathBuf> {
+        let mut dirs = Vec::new();
+        let mut it = WalkDir::new(imgs_dir).min_depth(1).into_iter();
+        while let Some(entry) = it.next() {
+            let Ok(entry) = entry else { continue };
+            if !entry.file_type().is_dir() {
+                continue;
             }
+            if entry.file_name().to_str().is_some_and(is_launch_component) {
+                dirs.push(entry.into_path());
+                it.skip_current_dir();
+            }
+        }
+        dirs
+    }
+
+    fn clear_launches_dir(imgs_dir: &std::path::Path) -> std::io::Result<()> {
+        for dir in find_launch_dirs(imgs_dir) {
+            let _ = std::fs::remove_dir_all(&dir);
         }
         Ok(())
     }
 
     #[tauri::command]
-    pub fn list_launch_images(app: AppHandle) -> Result<Vec<String>, String> {
-        use std::path::PathBuf;
-        use walkdir::WalkDir;
+    pub fn list_launch_images(
+        app: AppHandle,
+        extensions: Option<Vec<String>>,
+    ) -> Result<Vec<LaunchImage>, String> {
         let (_, _dbf, imgs_dir) = ensure_dirs(&app).map_err(|e| e.to_string())?;
-        let mut launch_dir: Option<PathBuf> = None;
-        for entry in std::fs::read_dir(&imgs_dir).map_err(|e| e.to_string())? {
-            if let Ok(e) = entry {
-                let p = e.path();
-                if p.is_dir() {
-                    if let Some(name) = p.file_name().and_then(|n| n.to_str()) {
-                        if is_launch_component(name) {
-                            launch_dir = Some(p);
-                            break;
-                        }
-                    }
-                }
+        let allow = extension_allowlist(extensions.as_deref(), DEFAULT_IMAGE_EXTENSIONS);
+        Ok(collect_launch_images(&find_launch_dirs(&imgs_dir), &allow))
+    }
+
+    /// Tira das pastas de lançamento as imagens com data de modificação mais antiga que
+    /// `older_than_days`; a biblioteca principal não é tocada.
+    #[tauri::command]
+    pub fn expire_launch_images_cmd(
+        app: AppHandle,
+        older_than_days: u64,
+    ) -> Result<ExpireLaunchResult, String> {
+        let (_, _dbf, imgs_dir) = ensure_dirs(&app).map_err(|e| e.to_string())?;
+        let max_age = Duration::from_secs(older_than_days.saturating_mul(24 * 60 * 60));
+        Ok(expire_launch_images(
+            &find_launch_dirs(&imgs_dir),
+            max_age,
+            std::time::SystemTime::now(),
+        ))
+    }
+
+    /// Arquivo sem data legível fica (na dúvida, não apaga).
+    fn expire_launch_images(
+        dirs: &[PathBuf],
+        max_age: Duration,
+        now: std::time::SystemTime,
+    ) -> ExpireLaunchResult {
+        let mut result = ExpireLaunchResult::default();
+        for entry in dirs
+            .iter()
+            .flat_map(|dir| WalkDir::new(dir).into_iter())
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+        {
+            let meta = entry.metadata().ok();
+            let expired = meta
+                .as_ref()
+                .and_then(|m| m.modified().ok())
+                .and_then(|modified| now.duration_since(modified).ok())
+                .is_some_and(|age| age > max_age);
+            if expired && fs::remove_file(entry.path()).is_ok() {
+                result.removed_files += 1;
+                result.removed_bytes += meta.map(|m| m.len()).unwrap_or(0);
+            } else {
+                result.kept_files += 1;
             }
         }
-        let dir = match launch_dir {
-            Some(d) => d,
-            None => return Ok(vec![]),
-        };
-        let allow = ["jpg", "jpeg", "png", "webp", "gif", "bmp"];
-        let mut files: Vec<String> = WalkDir::new(&dir)
-            .into_iter()
+        result
+    }
+
+    /// Imagens das pastas de lançamento, mais recentes primeiro. As dimensões vêm só do
+    /// cabeçalho do arquivo; formato ilegível deixa width/height vazios.
+    fn collect_launch_images(dirs: &[PathBuf], allow: &[String]) -> Vec<LaunchImage> {
+        let mut files: Vec<LaunchImage> = dirs
+            .iter()
+            .flat_map(|dir| WalkDir::new(dir).into_iter())
             .filter_map(|e| e.ok())
             .filter(|e| e.path().is_file())
-            .filter(|e| {
-                e.path()
-                    .extension()
-                    .and_then(|ex| ex.to_str())
-                    .map(|s| {
-                        let lower = s.to_ascii_lowercase();
-                        allow.contains(&lower.as_str())
-                    })
-                    .unwrap_or(false)
+            .filter(|e| has_allowed_extension(e.path(), allow))
+            .map(|e| {
+                let meta = e.metadata().ok();
+                let modified_ms = meta
+                    .as_ref()
+                    .and_then(|m| m.modified().ok())
+                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|d| d.as_millis() as i64)
+                    .unwrap_or(0);
+                let dims = image::image_dimensions(e.path()).ok();
+                LaunchImage {
+                    path: e.path().to_string_lossy().to_string(),
+                    size_bytes: meta.map(|m| m.len()).unwrap_or(0),
+                    modified_ms,
+                    width: dims.map(|d| d.0),
+                    height: dims.map(|d| d.1),
+                }
             })
-            .map(|e| e.path().to_string_lossy().to_string())
             .collect();
-        files.sort();
-        Ok(files)
+        files.sort_by(|a, b| {
+            b.modified_ms
+                .cmp(&a.modified_ms)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        files
     }
 
     #[tauri::command]
@@ -2438,6 +7915,26 @@ mod core {
         open::that(path).map_err(|e| e.to_string())
     }
 
+    /// Pasta a abrir no gerenciador de arquivos: a própria, se `path` for pasta, ou a que
+    /// contém o arquivo.
+    fn reveal_dir(path: &Path) -> Result<&Path, String> {
+        if !path.exists() {
+            return Err(format!("Caminho não encontrado: {}", path.display()));
+        }
+        if path.is_dir() {
+            Ok(path)
+        } else {
+            Ok(path.parent().unwrap_or(path))
+        }
+    }
+
+    #[tauri::command]
+    pub fn reveal_in_explorer_cmd(path: String) -> Result<(), String> {
+        let dir = reveal_dir(Path::new(&path))?;
+        open::that_detached(dir)
+            .map_err(|e| format!("Falha ao abrir gerenciador de arquivos: {}", e))
+    }
+
     fn find_app_root_upwards(start: &Path, max_levels: usize) -> Option<PathBuf> {
         for dir in start.ancestors().take(max_levels + 1) {
             if dir.join("package.json").exists()
@@ -3027,9 +8524,53 @@ mod core {
         }
     }
 
+    /// Mesma ordem do script: variável de ambiente, depois `.env.development` e `.env`
+    /// na raiz do projeto (o primeiro arquivo que define a chave vence).
+    fn r2_env_lookup(project_root: &Path) -> impl Fn(&str) -> Option<String> {
+        let mut from_files: HashMap<String, String> = HashMap::new();
+        for name in [".env.development", ".env"] {
+            let Ok(iter) = dotenvy::from_path_iter(project_root.join(name)) else {
+                continue;
+            };
+            for (k, v) in iter.flatten() {
+                if !v.trim().is_empty() {
+                    from_files.entry(k).or_insert(v);
+                }
+            }
+        }
+        move |key: &str| {
+            std::env::var(key)
+                .ok()
+                .filter(|v| !v.trim().is_empty())
+                .or_else(|| from_files.get(key).cloned())
+        }
+    }
+
+    /// Falha antes de chamar o Node quando falta alguma credencial obrigatória, tanto na
+    /// tela quanto em `lookup` (campo vazio = usar a variável de ambiente).
+    fn check_r2_creds(r2: &R2Creds, lookup: impl Fn(&str) -> Option<String>) -> Result<(), String> {
+        for (field, value, env) in [
+            ("account_id", &r2.account_id, "R2_ACCOUNT_ID"),
+            ("bucket", &r2.bucket, "R2_BUCKET"),
+            ("access_key_id", &r2.access_key_id, "R2_ACCESS_KEY_ID"),
+            (
+                "secret_access_key",
+                &r2.secret_access_key,
+                "R2_SECRET_ACCESS_KEY",
+            ),
+        ] {
+            if value.trim().is_empty() && lookup(env).is_none_or(|v| v.trim().is_empty()) {
+                return Err(format!(
+                    "Credencial do R2 ausente: {field} (preencha na tela ou defina {env})"
+                ));
+            }
+        }
+        Ok(())
+    }
+
     #[tauri::command]
     pub async fn gen_manifest_r2(
-        _app: AppHandle,
+        app: AppHandle,
         version: i64,
         db_url: String,
         out_path: String,
@@ -3037,6 +8578,12 @@ mod core {
     ) -> Result<String, String> {
         // Executa o script Node local para gerar o manifest a partir do R2
         let cwd = std::env::current_dir().map_err(|e| e.to_string())?;
+        let project_root: std::path::PathBuf = if cwd.ends_with("src-tauri") {
+            cwd.parent().unwrap_or(&cwd).to_path_buf()
+        } else {
+            cwd.clone()
+        };
+        check_r2_creds(&r2, r2_env_lookup(&project_root))?;
         // Resolve caminho do script considerando dev (../scripts) ou raiz (scripts)
         let script_path = if cwd.ends_with("src-tauri") {
             cwd.parent()
@@ -3081,23 +8628,92 @@ mod core {
                 cmd.env("R2_PUBLIC_BASE_URL", pub_url);
             }
         }
-        let project_root: std::path::PathBuf = if cwd.ends_with("src-tauri") {
-            cwd.parent().unwrap_or(&cwd).to_path_buf()
-        } else {
-            cwd.clone()
-        };
         cmd.current_dir(&project_root);
-        let output = cmd
-            .output()
-            .map_err(|e| format!("Falha ao iniciar Node: {}", e))?;
+        // A geração leva minutos em buckets grandes: cada linha vira evento para a UI.
+        let output = tokio::task::spawn_blocking(move || {
+            run_streaming(cmd, |stream, line| {
+                let _ = app.emit(
+                    "manifest_gen_log",
+                    json!({ "stream": stream, "line": line }),
+                );
+            })
+        })
+        .await
+        .map_err(|e| format!("Falha ao aguardar processo do Node: {}", e))?
+        .map_err(|e| format!("Falha ao iniciar Node: {}", e))?;
         if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            return Err(format!("Manifest R2 falhou: {}\n{}", stderr, stdout));
+            return Err(format!(
+                "Manifest R2 falhou: {}\n{}",
+                output.stderr, output.stdout
+            ));
         }
         Ok(out_path)
     }
 
+    struct StreamedOutput {
+        status: std::process::ExitStatus,
+        stdout: String,
+        stderr: String,
+    }
+
+    /// Roda `cmd` repassando cada linha de stdout/stderr para `on_line(stream, linha)` assim
+    /// que chega, e devolve também o texto completo de cada saída (para a mensagem de erro).
+    fn run_streaming(
+        mut cmd: PCommand,
+        mut on_line: impl FnMut(&str, &str),
+    ) -> std::io::Result<StreamedOutput> {
+        use std::io::BufRead;
+        let mut child = cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let (tx, rx) = std::sync::mpsc::channel::<(&'static str, String)>();
+        let mut readers = Vec::new();
+        let pipes: [(&'static str, Option<Box<dyn std::io::Read + Send>>); 2] = [
+            ("stdout", child.stdout.take().map(|p| Box::new(p) as _)),
+            ("stderr", child.stderr.take().map(|p| Box::new(p) as _)),
+        ];
+        for (stream, pipe) in pipes {
+            let Some(pipe) = pipe else { continue };
+            let tx = tx.clone();
+            readers.push(std::thread::spawn(move || {
+                let mut reader = std::io::BufReader::new(pipe);
+                let mut buf = Vec::new();
+                // Lossy: no Windows o Node pode escrever fora de UTF-8.
+                while matches!(reader.read_until(b'\n', &mut buf), Ok(n) if n > 0) {
+                    let line = String::from_utf8_lossy(&buf)
+                        .trim_end_matches(['\r', '\n'])
+                        .to_string();
+                    if tx.send((stream, line)).is_err() {
+                        break;
+                    }
+                    buf.clear();
+                }
+            }));
+        }
+        drop(tx);
+        let (mut stdout, mut stderr) = (String::new(), String::new());
+        for (stream, line) in rx {
+            on_line(stream, &line);
+            let target = if stream == "stdout" {
+                &mut stdout
+            } else {
+                &mut stderr
+            };
+            target.push_str(&line);
+            target.push('\n');
+        }
+        for reader in readers {
+            let _ = reader.join();
+        }
+        Ok(StreamedOutput {
+            status: child.wait()?,
+            stdout,
+            stderr,
+        })
+    }
+
     #[tauri::command]
     pub async fn run_rclone_sync() -> Result<RcloneSyncResult, String> {
         let cwd = std::env::current_dir().map_err(|e| e.to_string())?;
@@ -3138,6 +8754,139 @@ mod core {
         })
     }
 
+    /// Troca a pasta de dados (db, imagens, branding); `None` volta para a padrão.
+    /// Os arquivos atuais não são movidos.
+    #[tauri::command]
+    pub fn set_data_dir_cmd(app: AppHandle, path: Option<String>) -> Result<String, String> {
+        let default = app.path().app_local_data_dir().map_err(|e| e.to_string())?;
+        let dir = path
+            .as_deref()
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .map(PathBuf::from);
+        crate::db::set_data_dir_override(&default, dir.as_deref()).map_err(|e| e.to_string())?;
+        let (data_dir, _, _) = ensure_dirs(&app).map_err(|e| e.to_string())?;
+        Ok(data_dir.to_string_lossy().to_string())
+    }
+
+    /// Pasta das imagens (ex.: outro disco); `None`/vazio volta para `images` na pasta de dados.
+    /// Devolve a pasta efetiva. As imagens antigas ficam onde estão; a próxima sync baixa de novo.
+    #[tauri::command]
+    pub fn set_images_dir_cmd(app: AppHandle, path: Option<String>) -> Result<String, String> {
+        let data_dir = crate::db::app_data_dir(&app).map_err(|e| e.to_string())?;
+        let dir = path
+            .as_deref()
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .map(PathBuf::from);
+        crate::db::set_images_dir_override(&data_dir, dir.as_deref()).map_err(|e| e.to_string())?;
+        let (_, _, imgs_dir) = ensure_dirs(&app).map_err(|e| e.to_string())?;
+        Ok(imgs_dir.to_string_lossy().to_string())
+    }
+
+    /// "Começar do zero" pelo suporte: apaga banco, imagens e manifest local. Exige
+    /// `confirm = true` e recusa enquanto uma sincronização estiver rodando.
+    #[tauri::command]
+    pub fn reset_app_data_cmd(
+        app: AppHandle,
+        state: tauri::State<'_, SyncState>,
+        confirm: bool,
+    ) -> Result<(), String> {
+        if !confirm {
+            return Err("Confirme para apagar os dados locais".to_string());
+        }
+        let _guard = state.try_begin()?;
+        let data_dir = crate::db::app_data_dir(&app).map_err(|e| e.to_string())?;
+        reset_data_dir(data_dir).map_err(|e| e.to_string())
+    }
+
+    /// Os comandos não guardam conexão aberta, então basta apagar os arquivos. Chave e
+    /// pastas personalizadas (data_dir.txt/images_dir.txt) ficam: são configuração, não dados.
+    /// Pasta de imagens personalizada pode ter outras fotos: dela só saem os arquivos que o
+    /// catálogo baixou (images_cache).
+    fn reset_data_dir(data_dir: PathBuf) -> Result<()> {
+        let (data_dir, dbf, imgs_dir) = crate::db::ensure_dirs_in(data_dir)?;
+        let default_imgs = data_dir.join(crate::db::IMAGES_DIR_NAME);
+        let managed: Vec<String> = if imgs_dir != default_imgs && dbf.exists() {
+            open_db(&dbf)
+                .and_then(|conn| {
+                    let mut stmt = conn.prepare("SELECT filename FROM images_cache")?;
+                    let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+                    Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+                })
+                .unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        for suffix in ["", "-wal", "-shm"] {
+            let mut name = dbf.clone().into_os_string();
+            name.push(suffix);
+            let path = PathBuf::from(name);
+            if path.exists() {
+                fs::remove_file(&path)?;
+            }
+        }
+        if default_imgs.exists() {
+            fs::remove_dir_all(&default_imgs)?;
+        }
+        for rel in managed {
+            let rel_path = Path::new(&rel);
+            if !rel_path
+                .components()
+                .all(|c| matches!(c, std::path::Component::Normal(_)))
+            {
+                continue;
+            }
+            for path in [
+                imgs_dir.join(rel_path),
+                imgs_dir.join(format!("{rel}.cimg")),
+            ] {
+                if path.is_file() {
+                    fs::remove_file(&path)?;
+                }
+            }
+        }
+        let manifest_path = data_dir.join("manifest.json");
+        if manifest_path.exists() {
+            fs::remove_file(&manifest_path)?;
+        }
+        let (_, dbf, _) = crate::db::ensure_dirs_in(data_dir)?;
+        migrate(&open_db(&dbf)?)?;
+        Ok(())
+    }
+
+    #[tauri::command]
+    pub fn get_system_info_cmd(app: AppHandle) -> Result<SystemInfo, String> {
+        let (data_dir, dbf, imgs_dir) = ensure_dirs(&app).map_err(|e| e.to_string())?;
+        let key = crate::call_img::resolve_key(&app, &data_dir);
+        Ok(system_info(&data_dir, &dbf, &imgs_dir, key.as_deref()))
+    }
+
+    fn system_info(data_dir: &Path, dbf: &Path, imgs_dir: &Path, key: Option<&str>) -> SystemInfo {
+        let conn = if dbf.exists() {
+            open_db(dbf).ok()
+        } else {
+            None
+        };
+        let db_version = conn
+            .as_ref()
+            .and_then(|conn| get_db_version(conn).ok())
+            .unwrap_or(0);
+        let last_import = conn
+            .as_ref()
+            .and_then(|conn| crate::importer::import_provenance(conn).ok())
+            .flatten();
+        SystemInfo {
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            db_version,
+            data_dir: data_dir.to_string_lossy().to_string(),
+            db_path: dbf.to_string_lossy().to_string(),
+            images_dir: imgs_dir.to_string_lossy().to_string(),
+            key_configured: key.is_some_and(|k| !k.trim().is_empty()),
+            last_import,
+        }
+    }
+
     #[tauri::command]
     pub fn get_app_version_config() -> Result<AppVersionInfo, String> {
         read_app_version_info()
@@ -3207,6 +8956,23 @@ mod core {
         crate::call_img::read_image_base64(&app, path_or_rel)
     }
 
+    #[tauri::command]
+    pub fn read_image_meta_cmd(
+        app: AppHandle,
+        path_or_rel: String,
+    ) -> Result<crate::call_img::ImageMeta, String> {
+        crate::call_img::read_image_meta(&app, path_or_rel)
+    }
+
+    #[tauri::command]
+    pub fn validate_key_cmd(
+        app: AppHandle,
+        key: String,
+        sample_path: String,
+    ) -> Result<bool, String> {
+        crate::call_img::validate_key(&app, &key, &sample_path)
+    }
+
     #[tauri::command]
     pub fn save_pdf_base64(path: String, data_base64: String) -> Result<(), String> {
         use base64::Engine;
@@ -3231,6 +8997,7 @@ mod core {
     pub async fn index_images_from_manifest(
         app: AppHandle,
         manifest_url: String,
+        link_ambiguous: Option<bool>,
     ) -> Result<ImageIndexResult, String> {
         let client = Client::builder()
             .timeout(Duration::from_secs(20))
@@ -3239,61 +9006,124 @@ mod core {
         let (_, dbf, _) = ensure_dirs(&app).map_err(|e| e.to_string())?;
         let (manifest, _manifest_hash) =
             fetch_or_seed_manifest(&client, &app, &manifest_url).await?;
-        let files: Vec<String> = if let Some(imgs) = manifest.images {
-            imgs.files.into_iter().map(|it| it.file).collect()
-        } else {
-            Vec::new()
-        };
         let mut conn = open_db(&dbf).map_err(|e| e.to_string())?;
         migrate(&conn).map_err(|e| e.to_string())?;
-        index_from_file_list(&mut conn, &files).map_err(|e| e.to_string())
+        index_manifest_images(&mut conn, &manifest, link_ambiguous.unwrap_or(false))
+            .map_err(|e| e.to_string())
+    }
+
+    /// Sem imagens no manifest não há o que indexar; o resultado sai marcado com
+    /// `empty_manifest` e os vínculos atuais ficam como estão.
+    fn index_manifest_images(
+        conn: &mut Connection,
+        manifest: &CatalogManifest,
+        link_ambiguous: bool,
+    ) -> Result<ImageIndexResult> {
+        match manifest
+            .images
+            .as_ref()
+            .filter(|imgs| !imgs.files.is_empty())
+        {
+            Some(imgs) => index_from_file_list(conn, &imgs.files, link_ambiguous),
+            None => Ok(ImageIndexResult {
+                scanned: 0,
+                matched: 0,
+                inserted: 0,
+                skipped_placeholders: 0,
+                unmatched: 0,
+                unmatched_sample: Vec::new(),
+                empty_manifest: true,
+                ambiguous: 0,
+                ambiguous_sample: Vec::new(),
+            }),
+        }
     }
 
     #[tauri::command]
     pub async fn cleanup_images_from_manifest(
         app: AppHandle,
         manifest_url: String,
+        extensions: Option<Vec<String>>,
     ) -> Result<CleanupResult, String> {
+        // Com `extensions`, só arquivos desses tipos entram na limpeza; sem, todos.
+        let allow = extensions
+            .filter(|list| !list.is_empty())
+            .map(|list| extension_allowlist(Some(&list), &[]));
         let client = Client::builder()
             .timeout(Duration::from_secs(20))
             .build()
             .map_err(|e| e.to_string())?;
         let (manifest, _manifest_hash) =
             fetch_or_seed_manifest(&client, &app, &manifest_url).await?;
-        let imgs = manifest
+        let (_, _dbf, imgs_dir) = ensure_dirs(&app).map_err(|e| e.to_string())?;
+        Ok(cleanup_from_manifest(
+            &imgs_dir,
+            &manifest,
+            allow.as_deref(),
+        ))
+    }
+
+    /// Manifest sem arquivos de imagens válidos não apaga nada (evita remoção total);
+    /// devolve `empty_manifest` para a UI avisar o operador.
+    fn cleanup_from_manifest(
+        imgs_dir: &Path,
+        manifest: &CatalogManifest,
+        allow: Option<&[String]>,
+    ) -> CleanupResult {
+        let manifest_files: HashSet<String> = manifest
             .images
-            .ok_or_else(|| "Manifest não possui bloco de imagens".to_string())?;
-        let mut manifest_files: HashSet<String> = HashSet::new();
-        for item in imgs.files.iter() {
-            if safe_manifest_rel_path(&item.file).is_ok() {
-                manifest_files.insert(normalize_rel_path(&item.file));
-            }
-        }
+            .iter()
+            .flat_map(|imgs| imgs.files.iter())
+            .filter(|item| safe_manifest_rel_path(&item.file).is_ok())
+            .map(|item| normalize_rel_path(&item.file))
+            .collect();
         if manifest_files.is_empty() {
-            return Err(
-                "Manifest sem arquivos de imagens; abortando limpeza para evitar remoção total"
-                    .to_string(),
-            );
+            return CleanupResult {
+                removed_files: 0,
+                kept_files: 0,
+                total_scanned: 0,
+                manifest_files: 0,
+                empty_manifest: true,
+            };
         }
+        cleanup_images_dir(imgs_dir, &manifest_files, allow)
+    }
 
-        let (_, _dbf, imgs_dir) = ensure_dirs(&app).map_err(|e| e.to_string())?;
+    /// Remove de `imgs_dir` o que não está em `manifest_files` (já normalizados).
+    fn cleanup_images_dir(
+        imgs_dir: &Path,
+        manifest_files: &HashSet<String>,
+        allow: Option<&[String]>,
+    ) -> CleanupResult {
         let mut removed = 0usize;
         let mut kept = 0usize;
         let mut total = 0usize;
 
-        for entry in WalkDir::new(&imgs_dir).into_iter().filter_map(|e| e.ok()) {
+        for entry in WalkDir::new(imgs_dir).into_iter().filter_map(|e| e.ok()) {
             if entry.path().is_dir() {
                 continue;
             }
+            if let Some(allow) = allow {
+                if !has_allowed_extension(entry.path(), allow) {
+                    continue;
+                }
+            }
             total += 1;
             let rel = entry
                 .path()
-                .strip_prefix(&imgs_dir)
+                .strip_prefix(imgs_dir)
                 .unwrap_or(entry.path())
                 .to_string_lossy()
                 .to_string();
             let rel_norm = normalize_rel_path(&rel);
-            if manifest_files.contains(&rel_norm) {
+            // Lançamentos têm ciclo próprio (limpos na troca de manifest), não saem aqui.
+            if is_launch_path(&rel) {
+                kept += 1;
+                continue;
+            }
+            // "x.jpg.cimg" é a versão criptografada de "x.jpg" listado no manifest.
+            let logical = rel_norm.strip_suffix(".cimg").unwrap_or(&rel_norm);
+            if manifest_files.contains(&rel_norm) || manifest_files.contains(logical) {
                 kept += 1;
                 continue;
             }
@@ -3308,15 +9138,63 @@ mod core {
             }
         }
 
-        Ok(CleanupResult {
+        CleanupResult {
             removed_files: removed,
             kept_files: kept,
             total_scanned: total,
             manifest_files: manifest_files.len(),
-        })
+            empty_manifest: false,
+        }
+    }
+
+    /// Por que o manifest empacotado não pôde ser usado como seed.
+    #[derive(Debug, PartialEq)]
+    enum SeedManifestError {
+        ResourceDirUnavailable(String),
+        Missing(PathBuf),
+        Invalid { path: PathBuf, error: String },
+    }
+
+    impl std::fmt::Display for SeedManifestError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                SeedManifestError::ResourceDirUnavailable(e) => {
+                    write!(f, "pasta de recursos (resource_dir) indisponível: {}", e)
+                }
+                SeedManifestError::Missing(p) => {
+                    write!(f, "manifest.json não encontrado em {}", p.display())
+                }
+                SeedManifestError::Invalid { path, error } => {
+                    write!(f, "manifest.json inválido em {}: {}", path.display(), error)
+                }
+            }
+        }
+    }
+
+    fn seed_manifest_from(
+        resource_dir: Result<PathBuf, String>,
+    ) -> Result<(CatalogManifest, String), SeedManifestError> {
+        let res_dir = resource_dir.map_err(SeedManifestError::ResourceDirUnavailable)?;
+        let path = res_dir.join("manifest.json");
+        if !path.is_file() {
+            return Err(SeedManifestError::Missing(path));
+        }
+        let txt = std::fs::read_to_string(&path).map_err(|e| SeedManifestError::Invalid {
+            path: path.clone(),
+            error: e.to_string(),
+        })?;
+        let h = hash_str(&txt);
+        let m: CatalogManifest =
+            serde_json::from_str(&txt).map_err(|e| SeedManifestError::Invalid {
+                path: path.clone(),
+                error: e.to_string(),
+            })?;
+        Ok((m, h))
     }
 
     // Tenta baixar manifest por HTTP; se falhar, usa seed do bundle (manifest.json em resources).
+    /// Ordem: arquivo local (quando a URL não é http/https), download, manifest.json do bundle.
+    /// Se tudo falhar, a mensagem traz o erro do download e o motivo do seed não servir.
     async fn fetch_or_seed_manifest(
         client: &Client,
         app: &AppHandle,
@@ -3344,19 +9222,16 @@ mod core {
                 let m: CatalogManifest = serde_json::from_str(&txt).map_err(|e| e.to_string())?;
                 Ok((m, h))
             }
-            Err(_e) => {
+            Err(http_err) => {
                 // Fallback seed do bundle
-                if let Ok(res_dir) = app.path().resource_dir() {
-                    let p = res_dir.join("manifest.json");
-                    if p.exists() {
-                        let txt = std::fs::read_to_string(&p).map_err(|e| e.to_string())?;
-                        let h = hash_str(&txt);
-                        let m: CatalogManifest =
-                            serde_json::from_str(&txt).map_err(|e| e.to_string())?;
-                        return Ok((m, h));
-                    }
-                }
-                Err("Falha ao obter manifest e sem seed local".to_string())
+                seed_manifest_from(app.path().resource_dir().map_err(|e| e.to_string())).map_err(
+                    |seed_err| {
+                        format!(
+                            "Falha ao obter manifest ({}) e sem seed local: {}",
+                            http_err, seed_err
+                        )
+                    },
+                )
             }
         }
     }
@@ -3368,32 +9243,547 @@ mod core {
         out.iter().map(|b| format!("{:02x}", b)).collect()
     }
 
+    /// Destino de exportação: a pasta precisa existir e um arquivo existente só é
+    /// substituído com `overwrite`.
+    fn check_export_dest(dest: &Path, overwrite: bool) -> Result<(), String> {
+        if dest.as_os_str().is_empty() {
+            return Err("Destino de exportação vazio".to_string());
+        }
+        let parent = dest
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        if !parent.is_dir() {
+            return Err(format!("Pasta de destino não existe: {}", parent.display()));
+        }
+        if dest.is_dir() {
+            return Err(format!("Destino é uma pasta: {}", dest.display()));
+        }
+        if dest.exists() && !overwrite {
+            return Err(format!(
+                "Arquivo já existe: {} (confirme para sobrescrever)",
+                dest.display()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Grava em `.{nome}.tmp` ao lado e renomeia por cima: quem lê nunca vê arquivo pela metade.
+    fn write_file_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+        use std::io::Write;
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let tmp = path.with_file_name(format!(".{file_name}.tmp"));
+        let written = (|| {
+            let mut f = fs::File::create(&tmp)?;
+            f.write_all(bytes)?;
+            f.sync_all()
+        })();
+        if let Err(e) = written.and_then(|_| fs::rename(&tmp, path)) {
+            let _ = fs::remove_file(&tmp);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Exporta para um arquivo irmão temporário e só então renomeia sobre o destino,
+    /// para uma falha no meio não destruir uma exportação anterior.
+    /// Com `encrypt_key`, o arquivo sai no wrapper CIMG (o mesmo que `decrypt_catalog_db_file` lê).
+    fn export_db_file(
+        conn: &Connection,
+        dest: &Path,
+        overwrite: bool,
+        encrypt_key: Option<&str>,
+        exclude_launch: bool,
+    ) -> Result<(), String> {
+        check_export_dest(dest, overwrite)?;
+        let file_name = dest
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "catalog.db".to_string());
+        let tmp = dest.with_file_name(format!(".{file_name}.export.tmp"));
+        if tmp.exists() {
+            let _ = std::fs::remove_file(&tmp);
+        }
+        if let Err(e) = conn.execute("VACUUM INTO ?1", params![tmp.to_string_lossy()]) {
+            let _ = std::fs::remove_file(&tmp);
+            return Err(format!("Falha no VACUUM INTO: {}", e));
+        }
+        if exclude_launch {
+            if let Err(e) = strip_launch_images(&tmp) {
+                let _ = std::fs::remove_file(&tmp);
+                return Err(format!("Falha ao remover imagens de lançamento: {}", e));
+            }
+        }
+        if let Some(key) = encrypt_key {
+            let sealed = fs::read(&tmp)
+                .map_err(anyhow::Error::from)
+                .and_then(|plain| {
+                    crate::desc::encrypt_image(&plain, key, crate::desc::DEFAULT_KDF_ITERS)
+                })
+                .and_then(|sealed| fs::write(&tmp, sealed).map_err(Into::into));
+            if let Err(e) = sealed {
+                let _ = std::fs::remove_file(&tmp);
+                return Err(format!("Falha ao criptografar banco: {}", e));
+            }
+        }
+        std::fs::rename(&tmp, dest).map_err(|e| {
+            let _ = std::fs::remove_file(&tmp);
+            format!("Falha ao gravar destino: {}", e)
+        })
+    }
+
+    /// Na cópia exportada: apaga as linhas de images que apontam para pastas de lançamento
+    /// (mesma regra do `is_launch_path`) e compacta, para os nomes não ficarem em páginas livres.
+    fn strip_launch_images(db_file: &Path) -> Result<usize> {
+        let conn = open_db(db_file)?;
+        let launch_ids: Vec<i64> = {
+            let mut stmt = conn.prepare("SELECT rowid, filename FROM images")?;
+            let rows = stmt.query_map([], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })?;
+            rows.filter_map(|r| r.ok())
+                .filter(|(_, filename)| is_launch_path(filename))
+                .map(|(id, _)| id)
+                .collect()
+        };
+        conn.execute_batch("BEGIN")?;
+        {
+            let mut delete = conn.prepare("DELETE FROM images WHERE rowid = ?1")?;
+            for id in launch_ids.iter() {
+                delete.execute(params![id])?;
+            }
+        }
+        conn.execute_batch("COMMIT")?;
+        conn.execute_batch("VACUUM")?;
+        Ok(launch_ids.len())
+    }
+
     #[tauri::command]
-    pub fn export_db_to(app: AppHandle, dest_path: String) -> Result<ExportResult, String> {
-        let (_, dbf, _) = ensure_dirs(&app).map_err(|e| e.to_string())?;
+    pub fn export_db_to(
+        app: AppHandle,
+        dest_path: String,
+        overwrite: Option<bool>,
+        encrypt: Option<bool>,
+        exclude_launch: Option<bool>,
+    ) -> Result<ExportResult, String> {
+        let (data_dir, dbf, _) = ensure_dirs(&app).map_err(|e| e.to_string())?;
+        let key = if encrypt.unwrap_or(false) {
+            Some(
+                crate::call_img::resolve_key(&app, &data_dir).ok_or_else(|| {
+                    "Chave de criptografia não configurada (DESCRYPT_KEY)".to_string()
+                })?,
+            )
+        } else {
+            None
+        };
         let dest = std::path::PathBuf::from(&dest_path);
-        if dest.exists() {
-            std::fs::remove_file(&dest)
-                .map_err(|e| format!("Falha ao remover destino existente: {}", e))?;
+        let conn = open_db(&dbf).map_err(|e| e.to_string())?;
+        export_db_file(
+            &conn,
+            &dest,
+            overwrite.unwrap_or(false),
+            key.as_deref(),
+            exclude_launch.unwrap_or(false),
+        )?;
+        Ok(ExportResult {
+            ok: true,
+            output: dest_path,
+        })
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum ProductExportFormat {
+        /// Separado por `;` (o Excel em pt-BR abre direto), aspas quando preciso.
+        Csv,
+        /// Array JSON, um objeto por produto.
+        Json,
+    }
+
+    /// Linhas gravadas entre flushes do arquivo de exportação.
+    const EXPORT_FLUSH_EVERY: usize = 1000;
+    const EXPORT_COLUMNS: [&str; 8] = [
+        "code",
+        "description",
+        "brand",
+        "group",
+        "oem",
+        "similar",
+        "stock",
+        "vehicles",
+    ];
+
+    fn csv_field(value: &str) -> String {
+        if value.contains([';', '"', '\n', '\r']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value.to_string()
+        }
+    }
+
+    /// Escreve cada produto assim que sai do cursor do SQLite: a memória não cresce
+    /// com o tamanho do catálogo. Devolve quantas linhas foram gravadas.
+    fn write_products_export(
+        conn: &Connection,
+        out: &mut dyn std::io::Write,
+        format: ProductExportFormat,
+    ) -> Result<usize> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT p.code, p.description, b.name, p.pgroup, p.oem, p.similar, p.stock,
+                    {PRODUCT_VEHICLES_AGG}
+             FROM products p JOIN brands b ON b.id = p.brand_id ORDER BY p.code"
+        ))?;
+        let mut rows = stmt.query([])?;
+        match format {
+            ProductExportFormat::Csv => writeln!(out, "{}", EXPORT_COLUMNS.join(";"))?,
+            ProductExportFormat::Json => write!(out, "[")?,
+        }
+        let mut count = 0usize;
+        while let Some(row) = rows.next()? {
+            let text = |i: usize| -> rusqlite::Result<String> {
+                Ok(row.get::<_, Option<String>>(i)?.unwrap_or_default())
+            };
+            let stock: Option<i64> = row.get(6)?;
+            match format {
+                ProductExportFormat::Csv => {
+                    let fields = [
+                        text(0)?,
+                        text(1)?,
+                        text(2)?,
+                        text(3)?,
+                        text(4)?,
+                        text(5)?,
+                        stock.map(|s| s.to_string()).unwrap_or_default(),
+                        text(7)?,
+                    ];
+                    let line: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+                    writeln!(out, "{}", line.join(";"))?;
+                }
+                ProductExportFormat::Json => {
+                    if count > 0 {
+                        write!(out, ",")?;
+                    }
+                    serde_json::to_writer(
+                        &mut *out,
+                        &json!({
+                            "code": text(0)?,
+                            "description": text(1)?,
+                            "brand": text(2)?,
+                            "group": row.get::<_, Option<String>>(3)?,
+                            "oem": row.get::<_, Option<String>>(4)?,
+                            "similar": row.get::<_, Option<String>>(5)?,
+                            "stock": stock,
+                            "vehicles": row.get::<_, Option<String>>(7)?,
+                        }),
+                    )?;
+                }
+            }
+            count += 1;
+            if count.is_multiple_of(EXPORT_FLUSH_EVERY) {
+                out.flush()?;
+            }
+        }
+        if format == ProductExportFormat::Json {
+            write!(out, "]")?;
+        }
+        out.flush()?;
+        Ok(count)
+    }
+
+    /// Grava em `.{nome}.tmp` e renomeia no fim, como as outras exportações.
+    fn export_products_file(
+        conn: &Connection,
+        dest: &Path,
+        overwrite: bool,
+        format: ProductExportFormat,
+    ) -> Result<usize, String> {
+        write_export_file(dest, overwrite, |out| {
+            write_products_export(conn, out, format)
+        })
+        .map_err(|e| format!("Falha ao exportar produtos: {}", e))
+    }
+
+    /// Grava em `.{nome}.tmp` e renomeia no fim: quem abre o destino nunca vê arquivo pela
+    /// metade. `write` devolve quantas linhas gravou.
+    fn write_export_file(
+        dest: &Path,
+        overwrite: bool,
+        write: impl FnOnce(&mut dyn std::io::Write) -> Result<usize>,
+    ) -> Result<usize, String> {
+        check_export_dest(dest, overwrite)?;
+        let file_name = dest
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let tmp = dest.with_file_name(format!(".{file_name}.tmp"));
+        let written = (|| -> Result<usize> {
+            let mut out = std::io::BufWriter::new(fs::File::create(&tmp)?);
+            let count = write(&mut out)?;
+            out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+            Ok(count)
+        })();
+        match written.and_then(|count| fs::rename(&tmp, dest).map(|_| count).map_err(Into::into)) {
+            Ok(count) => Ok(count),
+            Err(e) => {
+                let _ = fs::remove_file(&tmp);
+                Err(e.to_string())
+            }
+        }
+    }
+
+    /// Relatório da indexação para conferência: `product_code;filename` de cada vínculo e,
+    /// depois de uma linha em branco, os códigos que ficaram sem nenhuma imagem.
+    #[tauri::command]
+    pub fn export_image_map_cmd(
+        app: AppHandle,
+        dest_path: String,
+        overwrite: Option<bool>,
+    ) -> Result<ExportResult, String> {
+        let (_, dbf, _) = ensure_dirs(&app).map_err(|e| e.to_string())?;
+        let conn = open_db(&dbf).map_err(|e| e.to_string())?;
+        ensure_schema(&conn).map_err(|e| e.to_string())?;
+        write_export_file(Path::new(&dest_path), overwrite.unwrap_or(false), |out| {
+            write_image_map(&conn, out)
+        })
+        .map_err(|e| format!("Falha ao exportar mapa de imagens: {}", e))?;
+        Ok(ExportResult {
+            ok: true,
+            output: dest_path,
+        })
+    }
+
+    /// Devolve quantos vínculos produto/arquivo foram gravados.
+    fn write_image_map(conn: &Connection, out: &mut dyn std::io::Write) -> Result<usize> {
+        writeln!(out, "product_code;filename")?;
+        let mut stmt = conn.prepare(
+            "SELECT p.code, i.filename FROM images i JOIN products p ON p.id = i.product_id
+             ORDER BY p.code, i.filename",
+        )?;
+        let mut rows = stmt.query([])?;
+        let mut linked = 0usize;
+        while let Some(row) = rows.next()? {
+            let code: String = row.get(0)?;
+            let filename: String = row.get(1)?;
+            writeln!(out, "{};{}", csv_field(&code), csv_field(&filename))?;
+            linked += 1;
+        }
+        writeln!(out)?;
+        writeln!(out, "product_code_sem_imagem")?;
+        let mut stmt = conn.prepare(
+            "SELECT p.code FROM products p
+             WHERE NOT EXISTS (SELECT 1 FROM images i WHERE i.product_id = p.id)
+             ORDER BY p.code",
+        )?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let code: String = row.get(0)?;
+            writeln!(out, "{}", csv_field(&code))?;
         }
+        Ok(linked)
+    }
+
+    fn export_products_cmd(
+        app: &AppHandle,
+        dest_path: String,
+        overwrite: Option<bool>,
+        format: ProductExportFormat,
+    ) -> Result<ExportResult, String> {
+        let (_, dbf, _) = ensure_dirs(app).map_err(|e| e.to_string())?;
         let conn = open_db(&dbf).map_err(|e| e.to_string())?;
-        let quoted = dest.to_string_lossy().replace('"', "\\\"");
-        let sql = format!("VACUUM INTO \"{}\"", quoted);
-        if let Err(e) = conn.execute(&sql, []) {
-            return Err(format!("Falha no VACUUM INTO: {}", e));
+        ensure_schema(&conn).map_err(|e| e.to_string())?;
+        export_products_file(
+            &conn,
+            Path::new(&dest_path),
+            overwrite.unwrap_or(false),
+            format,
+        )?;
+        Ok(ExportResult {
+            ok: true,
+            output: dest_path,
+        })
+    }
+
+    #[tauri::command]
+    pub fn export_csv_cmd(
+        app: AppHandle,
+        dest_path: String,
+        overwrite: Option<bool>,
+    ) -> Result<ExportResult, String> {
+        export_products_cmd(&app, dest_path, overwrite, ProductExportFormat::Csv)
+    }
+
+    #[tauri::command]
+    pub fn export_json_cmd(
+        app: AppHandle,
+        dest_path: String,
+        overwrite: Option<bool>,
+    ) -> Result<ExportResult, String> {
+        export_products_cmd(&app, dest_path, overwrite, ProductExportFormat::Json)
+    }
+
+    /// Estado gerenciado pelo Tauri: uma exportação via backup por vez, com o sinal de
+    /// cancelamento dela.
+    #[derive(Default)]
+    pub struct ExportState {
+        running: std::sync::atomic::AtomicBool,
+        cancel: Arc<std::sync::atomic::AtomicBool>,
+    }
+
+    /// Libera o `ExportState` ao sair do escopo, inclusive em erro.
+    pub struct ExportGuard<'a>(&'a ExportState);
+
+    impl ExportState {
+        /// Recusa uma segunda exportação enquanto a primeira roda; ao começar, zera o sinal
+        /// de cancelamento e devolve o guard junto com o sinal desta exportação.
+        pub fn try_begin(
+            &self,
+        ) -> Result<(ExportGuard<'_>, Arc<std::sync::atomic::AtomicBool>), String> {
+            use std::sync::atomic::Ordering;
+            self.running
+                .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+                .map_err(|_| "Exportação já em andamento".to_string())?;
+            self.cancel.store(false, Ordering::SeqCst);
+            Ok((ExportGuard(self), Arc::clone(&self.cancel)))
+        }
+
+        /// Pede o cancelamento da exportação em andamento; sem nenhuma, não faz nada.
+        pub fn cancel(&self) -> bool {
+            use std::sync::atomic::Ordering;
+            let running = self.running.load(Ordering::SeqCst);
+            if running {
+                self.cancel.store(true, Ordering::SeqCst);
+            }
+            running
+        }
+    }
+
+    impl Drop for ExportGuard<'_> {
+        fn drop(&mut self) {
+            self.0
+                .running
+                .store(false, std::sync::atomic::Ordering::SeqCst);
         }
+    }
+
+    /// Páginas copiadas por passo do backup; entre passos há progresso e checagem de cancelamento.
+    const BACKUP_PAGES_PER_STEP: i32 = 256;
+
+    /// Alternativa ao VACUUM INTO para bancos grandes: copia em passos com `export_progress`
+    /// e pode ser interrompida por `cancel_export_cmd`.
+    #[tauri::command]
+    pub async fn export_db_backup_cmd(
+        app: AppHandle,
+        state: tauri::State<'_, ExportState>,
+        dest_path: String,
+        overwrite: Option<bool>,
+    ) -> Result<ExportResult, String> {
+        let (_guard, cancel) = state.try_begin()?;
+        let (_, dbf, _) = ensure_dirs(&app).map_err(|e| e.to_string())?;
+        let dest = PathBuf::from(&dest_path);
+        let app_bg = app.clone();
+        tokio::task::spawn_blocking(move || {
+            let conn = open_db(&dbf).map_err(|e| e.to_string())?;
+            backup_db_file(
+                &conn,
+                &dest,
+                overwrite.unwrap_or(false),
+                &cancel,
+                &mut |pages_done, pages_total| {
+                    let _ = app_bg.emit(
+                        "export_progress",
+                        json!({ "pages_done": pages_done, "pages_total": pages_total }),
+                    );
+                },
+            )
+        })
+        .await
+        .map_err(|e| format!("Falha ao aguardar exportação: {}", e))??;
         Ok(ExportResult {
             ok: true,
             output: dest_path,
         })
     }
 
+    #[tauri::command]
+    pub fn cancel_export_cmd(state: tauri::State<'_, ExportState>) -> bool {
+        state.cancel()
+    }
+
+    fn backup_db_file(
+        conn: &Connection,
+        dest: &Path,
+        overwrite: bool,
+        cancel: &std::sync::atomic::AtomicBool,
+        on_progress: &mut dyn FnMut(i32, i32),
+    ) -> Result<(), String> {
+        use rusqlite::backup::{Backup, StepResult};
+        check_export_dest(dest, overwrite)?;
+        let file_name = dest
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "catalog.db".to_string());
+        let tmp = dest.with_file_name(format!(".{file_name}.backup.tmp"));
+        if tmp.exists() {
+            let _ = fs::remove_file(&tmp);
+        }
+        let copied = (|| -> Result<(), String> {
+            let mut out = Connection::open(&tmp).map_err(|e| e.to_string())?;
+            let backup = Backup::new(conn, &mut out).map_err(|e| e.to_string())?;
+            loop {
+                if cancel.load(std::sync::atomic::Ordering::SeqCst) {
+                    return Err("Exportação cancelada".to_string());
+                }
+                let step = backup
+                    .step(BACKUP_PAGES_PER_STEP)
+                    .map_err(|e| format!("Falha no backup: {}", e))?;
+                let p = backup.progress();
+                on_progress(p.pagecount - p.remaining, p.pagecount);
+                match step {
+                    StepResult::Done => return Ok(()),
+                    StepResult::More => {}
+                    _ => std::thread::sleep(Duration::from_millis(50)),
+                }
+            }
+        })();
+        if let Err(e) = copied {
+            let _ = fs::remove_file(&tmp);
+            return Err(e);
+        }
+        fs::rename(&tmp, dest).map_err(|e| {
+            let _ = fs::remove_file(&tmp);
+            format!("Falha ao gravar destino: {}", e)
+        })
+    }
+
     #[tauri::command]
     pub fn import_excel(
         app: AppHandle,
         path: String,
+        sheets: Option<Vec<String>>,
+        all_sheets: Option<bool>,
+        locale: Option<String>,
+    ) -> Result<crate::importer::ImportResult, String> {
+        crate::importer::import_excel(app, path, sheets, all_sheets.unwrap_or(false), locale)
+    }
+
+    #[tauri::command]
+    pub async fn import_excel_from_url(
+        app: AppHandle,
+        url: String,
+        sheets: Option<Vec<String>>,
+        all_sheets: Option<bool>,
+        locale: Option<String>,
     ) -> Result<crate::importer::ImportResult, String> {
-        crate::importer::import_excel(app, path)
+        crate::importer::import_excel_from_url(
+            app,
+            url,
+            sheets,
+            all_sheets.unwrap_or(false),
+            locale,
+        )
+        .await
     }
     fn candidate_codes(stem: &str) -> Vec<String> {
         use std::collections::HashSet;
@@ -3433,47 +9823,136 @@ mod core {
         out
     }
 
+    /// Produtos distintos a que os candidatos do nome levam, com o código que casou primeiro.
+    /// Nome inteiro igual a um código resolve sozinho; só os candidatos mais soltos (prefixos,
+    /// só alfanuméricos) podem dar mais de um item = nome ambíguo.
+    fn candidate_matches(
+        stem: &str,
+        mut lookup: impl FnMut(&str) -> Option<i64>,
+    ) -> Vec<(String, i64)> {
+        let exact = stem.trim().to_ascii_uppercase();
+        if let Some(pid) = lookup(&exact) {
+            return vec![(exact, pid)];
+        }
+        let mut out: Vec<(String, i64)> = Vec::new();
+        for code in candidate_codes(stem) {
+            if let Some(pid) = lookup(&code) {
+                if !out.iter().any(|(_, p)| *p == pid) {
+                    out.push((code, pid));
+                }
+            }
+        }
+        out
+    }
+
+    /// Registra o arquivo ambíguo na amostra do resultado.
+    fn note_ambiguous(sample: &mut Vec<AmbiguousImage>, file: String, matches: &[(String, i64)]) {
+        if sample.len() < UNMATCHED_SAMPLE_LIMIT {
+            sample.push(AmbiguousImage {
+                file,
+                codes: matches.iter().map(|(code, _)| code.clone()).collect(),
+            });
+        }
+    }
+
+    /// `link_ambiguous`: arquivo que casa com vários produtos vai para todos; sem ele
+    /// fica sem vínculo e só aparece em `ambiguous_sample`.
     #[tauri::command]
-    pub fn index_images(app: AppHandle, root: String) -> Result<ImageIndexResult, String> {
+    pub fn index_images(
+        app: AppHandle,
+        root: String,
+        extensions: Option<Vec<String>>,
+        batch_size: Option<usize>,
+        link_ambiguous: Option<bool>,
+    ) -> Result<ImageIndexResult, String> {
         let (_, dbf, _imgs_dir) = ensure_dirs(&app).map_err(|e| e.to_string())?;
         let mut conn = open_db(&dbf).map_err(|e| e.to_string())?;
-        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let allow = extension_allowlist(extensions.as_deref(), DEFAULT_IMAGE_EXTENSIONS);
+        index_images_in_dir(
+            &mut conn,
+            Path::new(&root),
+            &allow,
+            batch_size,
+            link_ambiguous.unwrap_or(false),
+            &mut |scanned, matched| {
+                let _ = app.emit("index_progress", index_progress_payload(scanned, matched));
+            },
+        )
+    }
+
+    fn index_progress_payload(scanned: usize, matched: usize) -> serde_json::Value {
+        json!({ "scanned": scanned, "matched": matched })
+    }
+
+    /// A cada quantos arquivos examinados o índice avisa o front (`index_progress`).
+    const INDEX_PROGRESS_EVERY: usize = 500;
 
-        let root_path = std::path::PathBuf::from(&root);
+    /// `batch_size` faz commit a cada N inserções (pastas enormes não seguram o lock de escrita
+    /// até o fim); sem ele tudo roda numa transação só. `on_progress(scanned, matched)` é
+    /// chamado a cada `INDEX_PROGRESS_EVERY` arquivos e uma última vez ao terminar.
+    fn index_images_in_dir(
+        conn: &mut Connection,
+        root_path: &Path,
+        allow: &[String],
+        batch_size: Option<usize>,
+        link_ambiguous: bool,
+        on_progress: &mut dyn FnMut(usize, usize),
+    ) -> Result<ImageIndexResult, String> {
+        let batch_size = batch_size.filter(|n| *n > 0);
+        let mut tx = conn.transaction().map_err(|e| e.to_string())?;
+        let mut pending = 0usize;
+
+        let placeholders = placeholder_hashes(&tx);
         let mut scanned = 0usize;
         let mut matched = 0usize;
         let mut inserted = 0usize;
-        for entry in WalkDir::new(&root_path).into_iter().filter_map(|e| e.ok()) {
+        let mut skipped_placeholders = 0usize;
+        let mut unmatched = 0usize;
+        let mut unmatched_sample: Vec<String> = Vec::new();
+        let mut ambiguous = 0usize;
+        let mut ambiguous_sample: Vec<AmbiguousImage> = Vec::new();
+        for entry in WalkDir::new(root_path).into_iter().filter_map(|e| e.ok()) {
             if !entry.file_type().is_file() {
                 continue;
             }
             let p = entry.path();
-            let ext = p
-                .extension()
-                .and_then(|e| e.to_str())
-                .map(|s| s.to_ascii_lowercase())
-                .unwrap_or_default();
-            if !["jpg", "jpeg", "png", "webp", "bmp"].contains(&ext.as_str()) {
+            if !has_allowed_extension(p, allow) {
                 continue;
             }
             scanned += 1;
+            if scanned.is_multiple_of(INDEX_PROGRESS_EVERY) {
+                on_progress(scanned, matched);
+            }
             let stem = p.file_stem().and_then(|s| s.to_str()).unwrap_or("");
-            let candidates = candidate_codes(stem);
-            let mut found: Option<i64> = None;
-            for c in candidates {
-                let res: Result<i64, _> =
-                    tx.query_row("SELECT id FROM products WHERE code=?1", params![c], |r| {
-                        r.get(0)
-                    });
-                if let Ok(pid) = res {
-                    found = Some(pid);
-                    break;
+            let found = candidate_matches(stem, |c| {
+                tx.query_row("SELECT id FROM products WHERE code=?1", params![c], |r| {
+                    r.get(0)
+                })
+                .ok()
+            });
+            if found.is_empty() {
+                unmatched += 1;
+                if unmatched_sample.len() < UNMATCHED_SAMPLE_LIMIT {
+                    let rel = pathdiff::diff_paths(p, root_path).unwrap_or_else(|| p.to_path_buf());
+                    unmatched_sample.push(rel.to_string_lossy().replace('\\', "/"));
+                }
+                continue;
+            }
+            if !placeholders.is_empty() && sha256_file(p).is_ok_and(|h| placeholders.contains(&h)) {
+                skipped_placeholders += 1;
+                continue;
+            }
+            let rel = pathdiff::diff_paths(p, root_path).unwrap_or_else(|| p.to_path_buf());
+            let rel_str = rel.to_string_lossy().replace('\\', "/");
+            if found.len() > 1 {
+                ambiguous += 1;
+                note_ambiguous(&mut ambiguous_sample, rel_str.clone(), &found);
+                if !link_ambiguous {
+                    continue;
                 }
             }
-            if let Some(pid) = found {
-                matched += 1;
-                let rel = pathdiff::diff_paths(p, &root_path).unwrap_or_else(|| p.to_path_buf());
-                let rel_str = rel.to_string_lossy().replace('\\', "/");
+            matched += 1;
+            for (_, pid) in &found {
                 if tx
                     .execute(
                         "INSERT OR IGNORE INTO images(product_id, filename) VALUES(?1,?2)",
@@ -3482,16 +9961,70 @@ mod core {
                     .is_ok()
                 {
                     inserted += 1;
+                    pending += 1;
                 }
             }
+            if batch_size.is_some_and(|n| pending >= n) {
+                tx.commit().map_err(|e| e.to_string())?;
+                tx = conn.transaction().map_err(|e| e.to_string())?;
+                pending = 0;
+            }
         }
+        apply_image_overrides(&tx).map_err(|e| e.to_string())?;
         tx.commit().ok();
+        on_progress(scanned, matched);
         Ok(ImageIndexResult {
             scanned,
             matched,
             inserted,
+            skipped_placeholders,
+            unmatched,
+            unmatched_sample,
+            empty_manifest: false,
+            ambiguous,
+            ambiguous_sample,
         })
     }
+
+    /// Hashes de imagens "sem foto" guardados em meta, um por linha.
+    fn placeholder_hashes(conn: &Connection) -> HashSet<String> {
+        conn.query_row(
+            "SELECT value FROM meta WHERE key = ?1",
+            params![META_PLACEHOLDER_HASHES_KEY],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+        .lines()
+        .map(|h| h.trim().to_ascii_lowercase())
+        .filter(|h| !h.is_empty())
+        .collect()
+    }
+
+    fn register_placeholder(conn: &Connection, file: &Path) -> Result<String> {
+        let hash = sha256_file(file)?;
+        let mut hashes: Vec<String> = placeholder_hashes(conn).into_iter().collect();
+        if !hashes.contains(&hash) {
+            hashes.push(hash.clone());
+        }
+        hashes.sort();
+        conn.execute(
+            "INSERT OR REPLACE INTO meta(key, value) VALUES(?1, ?2)",
+            params![META_PLACEHOLDER_HASHES_KEY, hashes.join("\n")],
+        )?;
+        Ok(hash)
+    }
+
+    /// Marca o arquivo como placeholder: cópias dele deixam de ser indexadas como foto.
+    #[tauri::command]
+    pub fn register_placeholder_image_cmd(app: AppHandle, path: String) -> Result<String, String> {
+        let conn =
+            open_db(&db_path(&app).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+        ensure_schema(&conn).map_err(|e| e.to_string())?;
+        register_placeholder(&conn, Path::new(&path)).map_err(|e| e.to_string())
+    }
 }
 
 // Re-export types for the frontend typings (via invoke JSON)
@@ -3510,10 +10043,22 @@ pub fn run() {
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_process::init())
         .plugin(tauri_plugin_updater::Builder::new().build())
+        .manage(core::SyncState::default())
+        .manage(core::SearchCursors::default())
+        .manage(core::ExportState::default())
         .invoke_handler(tauri::generate_handler![
             greet,
             core::init_app,
             core::get_brands_cmd,
+            core::reseed_groups_cmd,
+            core::rename_brand_cmd,
+            core::rebuild_makes_cmd,
+            core::get_products_by_vehicle_cmd,
+            core::get_product_count_cmd,
+            core::repair_orphans_cmd,
+            core::clear_image_cache_cmd,
+            core::export_image_map_cmd,
+            core::rebuild_image_cache_cmd,
             core::get_vehicles_cmd,
             core::get_makes_cmd,
             core::get_vehicles_by_make_cmd,
@@ -3521,18 +10066,44 @@ pub fn run() {
             core::get_vehicles_filtered_cmd,
             core::get_types_cmd,
             core::get_groups_stats_cmd,
+            core::get_category_tree_cmd,
+            core::list_tags_cmd,
+            core::add_tag_cmd,
+            core::remove_tag_cmd,
+            core::set_image_override_cmd,
+            core::remove_image_override_cmd,
+            core::register_placeholder_image_cmd,
             core::search_products_cmd,
+            core::open_search_cursor_cmd,
+            core::fetch_cursor_cmd,
+            core::close_cursor_cmd,
+            core::list_codes_cmd,
             core::get_print_catalog_cmd,
             core::export_print_excel_cmd,
             core::get_product_details_cmd,
+            core::get_product_by_code_cmd,
+            core::get_images_for_products_cmd,
             core::sync_from_manifest,
+            core::check_update_cmd,
+            core::preview_sync_cmd,
+            core::get_system_info_cmd,
+            core::set_data_dir_cmd,
+            core::set_images_dir_cmd,
+            core::reset_app_data_cmd,
             core::index_images_from_manifest,
             core::cleanup_images_from_manifest,
             core::list_launch_images,
+            core::expire_launch_images_cmd,
             core::import_excel,
+            core::import_excel_from_url,
             core::index_images,
             core::export_db_to,
+            core::export_db_backup_cmd,
+            core::export_csv_cmd,
+            core::export_json_cmd,
+            core::cancel_export_cmd,
             core::open_path_cmd,
+            core::reveal_in_explorer_cmd,
             core::set_branding_image,
             core::set_header_logos,
             core::refresh_branding_config,
@@ -3541,6 +10112,8 @@ pub fn run() {
             core::get_app_version_config,
             core::set_app_version_config,
             core::read_image_base64,
+            core::validate_key_cmd,
+            core::read_image_meta_cmd,
             core::save_pdf_base64
         ])
         .run(tauri::generate_context!())
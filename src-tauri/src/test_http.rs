@@ -0,0 +1,35 @@
+//! Servidor HTTP local para os testes de download.
+
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Escuta em uma porta livre e responde `response` (bytes brutos, cabeçalhos inclusos)
+/// a cada conexão. Devolve a URL base (`http://host:porta/`) e o contador de requisições.
+pub fn serve_raw(response: Vec<u8>) -> (String, Arc<AtomicUsize>) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let hits = Arc::new(AtomicUsize::new(0));
+    let hits_srv = hits.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { break };
+            let mut buf = [0u8; 4096];
+            let _ = stream.read(&mut buf);
+            hits_srv.fetch_add(1, Ordering::SeqCst);
+            let _ = stream.write_all(&response);
+        }
+    });
+    (format!("http://{addr}/"), hits)
+}
+
+/// Resposta `200 OK` completa com o content-type dado.
+pub fn ok_response(content_type: &str, body: &[u8]) -> Vec<u8> {
+    let mut response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    )
+    .into_bytes();
+    response.extend_from_slice(body);
+    response
+}
@@ -10,6 +10,7 @@ import {
   indexImagesFromManifest,
   listLaunchImages,
   readImageBase64,
+  isKeyRequired,
   importExcel,
   exportDbTo,
   setBrandingImage,
@@ -1220,6 +1221,26 @@ function App() {
     };
   }, []);
 
+  // Sync trocou o catalog.db: listas dos filtros ficaram velhas.
+  useEffect(() => {
+    const unlisten = listen("catalog_updated", async () => {
+      try {
+        const { brands: b, vehicles: v, makes: mk } = await loadInitialCatalog();
+        setBrands(b || []);
+        setVehicles(v || []);
+        setAllVehicles(v || []);
+        setMakes(mk || []);
+        setPrintGroups((await fetchGroups(null, null)) || []);
+        await loadGroupsFor(null, null);
+      } catch (e) {
+        setStatusMsg(`Falha ao recarregar catalogos: ${e}`);
+      }
+    });
+    return () => {
+      unlisten.then((fn) => fn()).catch(() => {});
+    };
+  }, []);
+
   // Tenta usar o updater nativo do Tauri: baixa e instala sem abrir link externo.
   useEffect(() => {
     let cancelled = false;
@@ -1295,7 +1316,11 @@ function App() {
       try {
         setToolsMsg("Limpando imagens obsoletas (manifest)...");
         const res = await cleanupImagesFromManifest(manifestUrl);
-        setToolsMsg(`Limpeza de imagens concluida: ${res?.removed_files || res?.removedFiles || 0} removidas.`);
+        if (res?.empty_manifest) {
+          setToolsMsg("Manifest sem imagens: limpeza não executada.");
+        } else {
+          setToolsMsg(`Limpeza de imagens concluida: ${res?.removed_files || res?.removedFiles || 0} removidas.`);
+        }
       } catch (e) {
         setToolsMsg(`Falha ao limpar imagens: ${e}`);
       }
@@ -1401,11 +1426,15 @@ function App() {
           try {
             const cleanRes = await cleanupImagesFromManifest(manifestUrl);
             const removed = cleanRes?.removed_files || cleanRes?.removedFiles || 0;
-            cleanupMsg = ` Limpeza: ${removed} removidas.`;
+            cleanupMsg = cleanRes?.empty_manifest ? "" : ` Limpeza: ${removed} removidas.`;
           } catch (cleanupError) {
             cleanupMsg = ` Falha na limpeza: ${cleanupError}`;
           }
-          setSecondaryStatus(`Indexados ${idxRes?.matched || 0}/${idxRes?.scanned || 0} imagens.${cleanupMsg}`);
+          setSecondaryStatus(
+            idxRes?.empty_manifest
+              ? "Manifest sem bloco de imagens: nada indexado nem limpo."
+              : `Indexados ${idxRes?.matched || 0}/${idxRes?.scanned || 0} imagens.${cleanupMsg}`
+          );
         } catch (e) {
           setSecondaryStatus(`Falha ao indexar: ${e}`);
         }
@@ -1773,6 +1802,7 @@ function App() {
       }
       const unique = new Set();
       const imgs = [];
+      let keyMissing = false;
       for (const img of dedupByName) {
         const normalized = normalizePath(imagesDir, img);
         try {
@@ -1781,11 +1811,13 @@ function App() {
             unique.add(src);
             imgs.push(src);
           }
-        } catch (_) {
+        } catch (err) {
           // Se falhar (arquivo ausente/criptografia), apenas ignore para evitar thumbs quebradas
+          if (isKeyRequired(err)) keyMissing = true;
         }
       }
       setSelectedImages(imgs);
+      if (keyMissing) setStatusMsg("Imagens criptografadas: configure a chave de descriptografia.");
     } catch (e) {
       setStatusMsg(`Falha ao carregar detalhes: ${e}`);
     }
@@ -1807,7 +1839,7 @@ function App() {
       const list = [];
       const uniq = new Set();
       for (const f of files) {
-        const full = normalizePath(imagesDir, f);
+        const full = normalizePath(imagesDir, f.path);
         try {
           const src = await loadLocalImageSrc(full);
           if (src && !uniq.has(src)) {
@@ -1839,6 +1871,10 @@ function App() {
       setStatusMsg(`Sincronizado: db v${res?.db_version || res?.dbVersion || "?"} | imgs +${res?.downloaded_images || res?.downloadedImages || 0}`);
       const idxRes = await indexImagesFromManifest(target);
       const cleanRes = await cleanupImagesFromManifest(target);
+      if (idxRes?.empty_manifest || cleanRes?.empty_manifest) {
+        setToolsMsg("Sync concluído, mas o manifest não tem imagens: nada indexado nem limpo.");
+        return;
+      }
       setToolsMsg(
         `Sync concluído. Indexados ${idxRes?.matched || 0}/${idxRes?.scanned || 0}; removidas ${cleanRes?.removed_files || cleanRes?.removedFiles || 0} imagens obsoletas.`
       );
@@ -1894,6 +1930,10 @@ function App() {
     try {
       const idxRes = await indexImagesFromManifest(target);
       const cleanRes = await cleanupImagesFromManifest(target);
+      if (idxRes?.empty_manifest || cleanRes?.empty_manifest) {
+        setToolsMsg("Manifest sem imagens: nada indexado nem limpo.");
+        return;
+      }
       setToolsMsg(
         `Indexados ${idxRes?.matched || 0}/${idxRes?.scanned || 0} imagens. Removidas ${cleanRes?.removed_files || cleanRes?.removedFiles || 0} obsoletas.`
       );
@@ -1909,7 +1949,11 @@ function App() {
       setExcelPath(picked);
       setToolsMsg("Importando Excel...");
       const res = await importExcel(picked);
-      setToolsMsg(`Importado: linhas ${res?.processed_rows ?? "?"}, produtos ${res?.upserted_products ?? "?"}, versão do banco ${res?.new_db_version ?? "?"}`);
+      const semMontadora = res?.vehicles_without_make ? `, veículos sem montadora ${res.vehicles_without_make}` : "";
+      const novos = res?.created_vehicles || res?.created_makes ? `, ${res.created_vehicles} veículos e ${res.created_makes} montadoras` : "";
+      const pulados = res?.skipped_rows ? `, ${res.skipped_rows} linhas ilegíveis puladas` : "";
+      const codNumericos = res?.numeric_codes ? ` (atenção: ${res.numeric_codes} códigos numéricos, confira zeros à esquerda)` : "";
+      setToolsMsg(`Importado: linhas ${res?.processed_rows ?? "?"}, produtos ${res?.upserted_products ?? "?"}, versão do banco ${res?.new_db_version ?? "?"}${novos}${semMontadora}${pulados}${codNumericos}`);
       const { brands: b, vehicles: v, makes: mk } = await loadInitialCatalog();
       setBrands(b || []);
       setVehicles(v || []);
@@ -1928,7 +1972,8 @@ function App() {
       if (!picked) return;
       setExportPath(picked);
       setToolsMsg("Exportando banco...");
-      const res = await exportDbTo(picked);
+      // O diálogo de salvar já confirmou a sobrescrita com o usuário.
+      const res = await exportDbTo(picked, { overwrite: true });
       setToolsMsg(res?.ok ? `DB exportado: ${res.output || picked}` : "Falha ao exportar");
     } catch (e) {
       setToolsMsg(`Falha ao exportar: ${e}`);
@@ -1940,7 +1985,17 @@ function App() {
       const picked = await openDialog({ multiple: false, filters: [{ name: "Imagens", extensions: ["png", "jpg", "jpeg", "webp"] }] });
       if (!picked || Array.isArray(picked)) return;
       setToolsMsg(`Aplicando ${kind}...`);
-      const res = await setBrandingImage(kind, picked);
+      let res;
+      try {
+        res = await setBrandingImage(kind, picked);
+      } catch (e) {
+        if (!String(e).includes("confirme para sobrescrever")) throw e;
+        if (!window.confirm(`Já existe um ${kind} aplicado. Substituir pela nova imagem?`)) {
+          setToolsMsg(`${kind} mantido`);
+          return;
+        }
+        res = await setBrandingImage(kind, picked, { overwrite: true });
+      }
       const returnedPath = kind === "logo" ? res?.logo : res?.background;
       const finalPath = returnedPath || picked;
       if (kind === "logo") {
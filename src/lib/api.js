@@ -8,14 +8,37 @@ export async function fetchBrands() {
   return await invoke("get_brands_cmd");
 }
 
-export async function fetchVehicles() {
-  return await invoke("get_vehicles_cmd");
+export async function fetchVehicles(opts = {}) {
+  return await invoke("get_vehicles_cmd", {
+    query: opts.query ?? null,
+    limit: opts.limit ?? null,
+    offset: opts.offset ?? null,
+  });
 }
 
+// { items, limit, max_limit, limit_clamped }: sem `limit` vale o padrão do app, e pedidos acima do teto são cortados.
 export async function searchProducts(params) {
   return await invoke("search_products_cmd", { params });
 }
 
+// Buscas grandes em lotes: abre o cursor, pede `n` itens por vez (lista vazia = fim) e fecha.
+export async function openSearchCursor(params) {
+  return await invoke("open_search_cursor_cmd", { params });
+}
+
+export async function fetchCursor(cursorId, n) {
+  return await invoke("fetch_cursor_cmd", { cursorId, n });
+}
+
+export async function closeCursor(cursorId) {
+  return await invoke("close_cursor_cmd", { cursorId });
+}
+
+// Só os códigos do filtro, ordenados por código; para etiquetas. Sem `limit` vêm todos.
+export async function listCodes(params, opts = {}) {
+  return await invoke("list_codes_cmd", { params, limit: opts.limit ?? null, offset: opts.offset ?? null });
+}
+
 export async function fetchPrintCatalog(params) {
   return await invoke("get_print_catalog_cmd", { params });
 }
@@ -28,25 +51,51 @@ export async function getProductDetails(productId) {
   return await invoke("get_product_details_cmd", { productId });
 }
 
+// Leitor de código de barras / links diretos; rejeita com "NOT_FOUND" quando não existe.
+export async function getProductByCode(code) {
+  return await invoke("get_product_by_code_cmd", { code });
+}
+
+// { [id]: ["IPS/7111.jpg", ...] } para a grade; ids sem imagem vêm com lista vazia.
+export async function getImagesForProducts(ids) {
+  return await invoke("get_images_for_products_cmd", { ids });
+}
+
 export async function syncFromManifest(manifestUrl, opts = {}) {
   return await invoke("sync_from_manifest", { manifestUrl, skipImages: !!opts.skipImages });
 }
 
-export async function importExcel(path) {
-  return await invoke("import_excel", { path });
+export async function importExcel(path, opts = {}) {
+  return await invoke("import_excel", {
+    path,
+    sheets: opts.sheets ?? null,
+    allSheets: !!opts.allSheets,
+    locale: opts.locale ?? null,
+  });
+}
+
+export async function importExcelFromUrl(url, opts = {}) {
+  return await invoke("import_excel_from_url", {
+    url,
+    sheets: opts.sheets ?? null,
+    allSheets: !!opts.allSheets,
+    locale: opts.locale ?? null,
+  });
 }
 
 export async function fetchMakes() {
   return await invoke("get_makes_cmd");
 }
 
-export async function fetchGroups(brandId, brandName) {
+export async function fetchGroups(brandId, brandName, opts = {}) {
   const numericId = brandId === undefined || brandId === null || brandId === "" ? null : Number(brandId);
   return await invoke("get_groups_cmd", {
     brand_id: numericId,
     brandId: numericId,
     brand_name: brandName,
     brandName,
+    withCounts: !!opts.withCounts,
+    minCount: opts.minCount ?? null,
   });
 }
 
@@ -54,20 +103,44 @@ export async function fetchVehiclesFiltered(brandId, group, make) {
   return await invoke("get_vehicles_filtered_cmd", { brand_id: brandId, group, make });
 }
 
-export async function indexImagesFromManifest(manifestUrl) {
-  return await invoke("index_images_from_manifest", { manifestUrl });
+// Arquivos que casam com mais de um produto vêm em `ambiguous_sample`; com `linkAmbiguous` vão para todos.
+export async function indexImagesFromManifest(manifestUrl, opts = {}) {
+  return await invoke("index_images_from_manifest", { manifestUrl, linkAmbiguous: !!opts.linkAmbiguous });
+}
+
+export async function cleanupImagesFromManifest(manifestUrl, opts = {}) {
+  return await invoke("cleanup_images_from_manifest", { manifestUrl, extensions: opts.extensions ?? null });
+}
+
+export async function indexImages(root, opts = {}) {
+  return await invoke("index_images", {
+    root,
+    extensions: opts.extensions ?? null,
+    batchSize: opts.batchSize ?? null,
+    linkAmbiguous: !!opts.linkAmbiguous,
+  });
+}
+
+export async function exportDbTo(destPath, opts = {}) {
+  return await invoke("export_db_to", {
+    destPath,
+    overwrite: !!opts.overwrite,
+    encrypt: !!opts.encrypt,
+    excludeLaunch: !!opts.excludeLaunch,
+  });
 }
 
-export async function cleanupImagesFromManifest(manifestUrl) {
-  return await invoke("cleanup_images_from_manifest", { manifestUrl });
+export async function exportDbBackup(destPath, opts = {}) {
+  return await invoke("export_db_backup_cmd", { destPath, overwrite: !!opts.overwrite });
 }
 
-export async function exportDbTo(destPath) {
-  return await invoke("export_db_to", { destPath });
+// Só uma exportação via backup roda por vez; devolve false se não havia nenhuma.
+export async function cancelExport() {
+  return await invoke("cancel_export_cmd");
 }
 
-export async function setBrandingImage(kind, path) {
-  return await invoke("set_branding_image", { kind, sourcePath: path });
+export async function setBrandingImage(kind, path, opts = {}) {
+  return await invoke("set_branding_image", { kind, sourcePath: path, overwrite: !!opts.overwrite });
 }
 
 export async function setHeaderLogos(paths) {
@@ -78,16 +151,25 @@ export async function refreshBrandingConfig() {
   return await invoke("refresh_branding_config");
 }
 
+// Imagem criptografada sem chave configurada: rejeita com "KEY_REQUIRED".
+export function isKeyRequired(err) {
+  return String(err ?? "") === "KEY_REQUIRED";
+}
+
 export async function readImageBase64(pathOrRel) {
   return await invoke("read_image_base64", { pathOrRel });
 }
 
+export async function readImageMeta(pathOrRel) {
+  return await invoke("read_image_meta_cmd", { pathOrRel });
+}
+
 export async function savePdfBase64(path, dataBase64) {
   return await invoke("save_pdf_base64", { path, dataBase64 });
 }
 
-export async function listLaunchImages() {
-  return await invoke("list_launch_images");
+export async function listLaunchImages(opts = {}) {
+  return await invoke("list_launch_images", { extensions: opts.extensions ?? null });
 }
 
 export async function runRcloneSync() {
@@ -102,3 +184,130 @@ export async function setAppVersionConfig(version) {
   return await invoke("set_app_version_config", { version });
 }
 
+
+export async function fetchCategoryTree() {
+  return await invoke("get_category_tree_cmd");
+}
+
+export async function listTags() {
+  return await invoke("list_tags_cmd");
+}
+
+export async function addTag(productId, name) {
+  return await invoke("add_tag_cmd", { productId, name });
+}
+
+export async function removeTag(productId, tagId) {
+  return await invoke("remove_tag_cmd", { productId, tagId });
+}
+
+export async function setImageOverride(code, filename, action) {
+  return await invoke("set_image_override_cmd", { code, filename, action });
+}
+
+export async function removeImageOverride(code, filename) {
+  return await invoke("remove_image_override_cmd", { code, filename });
+}
+
+export async function registerPlaceholderImage(path) {
+  return await invoke("register_placeholder_image_cmd", { path });
+}
+
+export async function revealInExplorer(path) {
+  return await invoke("reveal_in_explorer_cmd", { path });
+}
+
+export async function fetchTypes(brandId, opts = {}) {
+  return await invoke("get_types_cmd", { brandId: brandId ?? null, withCounts: !!opts.withCounts });
+}
+
+export async function reseedGroups() {
+  return await invoke("reseed_groups_cmd");
+}
+
+// Nome já usado por outra marca: rejeita, a menos que `merge` (produtos vão para a existente).
+export async function renameBrand(brandId, newName, opts = {}) {
+  return await invoke("rename_brand_cmd", { brandId, newName, merge: !!opts.merge });
+}
+
+export async function checkUpdate(manifestUrl) {
+  return await invoke("check_update_cmd", { manifestUrl });
+}
+
+// Mostra o que a sincronização faria (versão do banco, imagens a baixar) sem baixar nada;
+// com `images_approximate` as contagens de imagens são estimativa (o banco será trocado).
+export async function previewSync(manifestUrl) {
+  return await invoke("preview_sync_cmd", { manifestUrl });
+}
+
+export async function validateKey(key, samplePath) {
+  return await invoke("validate_key_cmd", { key, samplePath });
+}
+
+export async function rebuildMakes() {
+  return await invoke("rebuild_makes_cmd");
+}
+
+export async function getSystemInfo() {
+  return await invoke("get_system_info_cmd");
+}
+
+export async function setDataDir(path) {
+  return await invoke("set_data_dir_cmd", { path: path ?? null });
+}
+
+// Pasta das imagens fora da pasta de dados; null volta ao padrão. Devolve a pasta efetiva.
+export async function setImagesDir(path) {
+  return await invoke("set_images_dir_cmd", { path: path ?? null });
+}
+
+// Apaga banco, imagens e manifest local; só roda com confirm === true.
+export async function resetAppData(confirm) {
+  return await invoke("reset_app_data_cmd", { confirm: confirm === true });
+}
+
+export async function repairOrphans() {
+  return await invoke("repair_orphans_cmd");
+}
+
+export async function clearImageCache() {
+  return await invoke("clear_image_cache_cmd");
+}
+
+export async function rebuildImageCache() {
+  return await invoke("rebuild_image_cache_cmd");
+}
+
+export async function exportCsv(destPath, opts = {}) {
+  return await invoke("export_csv_cmd", { destPath, overwrite: !!opts.overwrite });
+}
+
+export async function exportJson(destPath, opts = {}) {
+  return await invoke("export_json_cmd", { destPath, overwrite: !!opts.overwrite });
+}
+
+export async function exportImageMap(destPath, opts = {}) {
+  return await invoke("export_image_map_cmd", { destPath, overwrite: !!opts.overwrite });
+}
+
+export async function fetchProductsByVehicle(vehicleId, opts = {}) {
+  return await invoke("get_products_by_vehicle_cmd", {
+    vehicleId,
+    limit: opts.limit ?? null,
+    offset: opts.offset ?? null,
+  });
+}
+
+// Leitura travada pela sincronização em andamento; vale tentar de novo.
+export function isDbBusy(err) {
+  return String(err ?? "") === "DB_BUSY";
+}
+
+export async function fetchProductCount(brandId) {
+  return await invoke("get_product_count_cmd", { brandId: brandId ?? null });
+}
+
+// Remove imagens de lançamento mais antigas que `olderThanDays` (pela data do arquivo).
+export async function expireLaunchImages(olderThanDays) {
+  return await invoke("expire_launch_images_cmd", { olderThanDays });
+}
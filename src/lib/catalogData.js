@@ -35,7 +35,7 @@ export async function loadVehiclesByFilters(brandId, group, make) {
 export async function searchWithFilters({ brandId, group, vehicleId, make, codeQuery, limit = 200 }) {
   const numericBrand = toNumeric(brandId);
   const numericVehicle = toNumeric(vehicleId);
-  return await searchProducts({
+  const page = await searchProducts({
     brand_id: numericBrand,
     group: group || null,
     vehicle_id: numericVehicle,
@@ -43,4 +43,5 @@ export async function searchWithFilters({ brandId, group, vehicleId, make, codeQ
     code_query: codeQuery || null,
     limit,
   });
+  return page?.items || [];
 }